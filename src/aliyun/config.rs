@@ -2,6 +2,7 @@ use std::collections::HashMap;
 use std::env;
 
 use super::constants::*;
+use crate::error::required_together;
 
 /// Config carries all the configuration for Aliyun services.
 #[derive(Clone)]
@@ -40,6 +41,42 @@ pub struct Config {
     /// - this field if it's `is_some`
     /// - env value: [`ALIBABA_CLOUD_OIDC_TOKEN_FILE`]
     pub oidc_token_file: Option<String>,
+    /// `sts_endpoint` will be loaded from
+    ///
+    /// - this field if it's `is_some`
+    /// - env value: [`ALIBABA_CLOUD_STS_ENDPOINT`]
+    ///
+    /// The endpoint should be a full url like `https://sts.cn-hangzhou.aliyuncs.com`.
+    pub sts_endpoint: Option<String>,
+    /// `sts_region` will be loaded from
+    ///
+    /// - this field if it's `is_some`
+    /// - env value: [`ALIBABA_CLOUD_STS_REGION`]
+    ///
+    /// If set, `https://sts.{sts_region}.aliyuncs.com` will be used unless
+    /// `sts_endpoint` is set.
+    pub sts_region: Option<String>,
+    /// `ecs_ram_role` will be loaded from
+    ///
+    /// - this field if it's `is_some`
+    /// - env value: [`ALIBABA_CLOUD_ECS_METADATA`]
+    ///
+    /// If not set, the role attached to the ecs instance will be discovered
+    /// from metadata service.
+    pub ecs_ram_role: Option<String>,
+    /// `imds_v1_disabled` will be loaded from
+    ///
+    /// - this field if it's `true`
+    /// - env value: [`ALIBABA_CLOUD_IMDSV1_DISABLED`]
+    ///
+    /// If disabled, loading from ecs metadata will fail if the metadata
+    /// token can't be fetched instead of falling back to normal mode.
+    pub imds_v1_disabled: bool,
+    /// `ecs_metadata_endpoint` will be loaded from
+    ///
+    /// - this field
+    /// - default to [`ECS_METADATA_ENDPOINT`]
+    pub ecs_metadata_endpoint: String,
 }
 
 impl Default for Config {
@@ -52,6 +89,11 @@ impl Default for Config {
             role_session_name: "resign".to_string(),
             oidc_provider_arn: None,
             oidc_token_file: None,
+            sts_endpoint: None,
+            sts_region: None,
+            ecs_ram_role: None,
+            imds_v1_disabled: false,
+            ecs_metadata_endpoint: ECS_METADATA_ENDPOINT.to_string(),
         }
     }
 }
@@ -76,7 +118,30 @@ impl Config {
         if let Some(v) = envs.get(ALIBABA_CLOUD_OIDC_TOKEN_FILE) {
             self.oidc_token_file.get_or_insert(v.clone());
         }
+        if let Some(v) = envs.get(ALIBABA_CLOUD_STS_ENDPOINT) {
+            self.sts_endpoint.get_or_insert(v.clone());
+        }
+        if let Some(v) = envs.get(ALIBABA_CLOUD_STS_REGION) {
+            self.sts_region.get_or_insert(v.clone());
+        }
+        if let Some(v) = envs.get(ALIBABA_CLOUD_ECS_METADATA) {
+            self.ecs_ram_role.get_or_insert(v.clone());
+        }
+        if let Some(v) = envs.get(ALIBABA_CLOUD_IMDSV1_DISABLED) {
+            self.imds_v1_disabled |= v.eq_ignore_ascii_case("true");
+        }
 
         self
     }
+
+    /// Check that `access_key_id` and `access_key_secret` are set together.
+    ///
+    /// It's called while loading credentials, users can call it eagerly to
+    /// find misconfigurations on startup.
+    pub fn validate(&self) -> crate::Result<()> {
+        required_together(&[
+            ("access_key_id", self.access_key_id.is_some()),
+            ("access_key_secret", self.access_key_secret.is_some()),
+        ])
+    }
 }
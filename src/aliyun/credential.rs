@@ -4,15 +4,24 @@ use std::sync::Mutex;
 
 use anyhow::anyhow;
 use anyhow::Result;
+use async_trait::async_trait;
 use log::debug;
-use reqwest::Client;
+use percent_encoding::utf8_percent_encode;
 use serde::Deserialize;
 
 use super::config::Config;
+use super::constants::ALIYUN_RPC_ENCODE_SET;
+use super::constants::ECS_METADATA_TOKEN_TTL;
+use crate::hash::base64_hmac_sha1;
+use crate::http_send::check_available;
+use crate::http_send::HttpSend;
+use crate::loader::CredentialLoad;
 use crate::time::format_rfc3339;
 use crate::time::now;
 use crate::time::parse_rfc3339;
 use crate::time::DateTime;
+use crate::trace::credential_refreshed;
+use crate::trace::record_cache_hit;
 
 /// Credential that holds the access_key and secret_key.
 #[derive(Default, Clone)]
@@ -46,41 +55,111 @@ impl Credential {
 
         true
     }
+
+    /// The time when this credential will be expired.
+    ///
+    /// Returns `None` for long-lived credentials that never expire.
+    pub fn expires_at(&self) -> Option<DateTime> {
+        self.expires_in
+    }
+
+    /// Whether this credential has been expired.
+    pub fn is_expired(&self) -> bool {
+        self.expires_in.is_some_and(|v| v <= now())
+    }
+
+    /// Whether this credential will be expired within `duration`.
+    ///
+    /// Long-lived credentials that never expire always return `false`.
+    pub fn is_expired_within(&self, duration: std::time::Duration) -> bool {
+        let expires_in = match self.expires_in {
+            Some(v) => v,
+            None => return false,
+        };
+        match chrono::Duration::from_std(duration)
+            .ok()
+            .and_then(|d| now().checked_add_signed(d))
+        {
+            Some(deadline) => expires_in <= deadline,
+            // Duration is too large to represent, the credential must be
+            // expired within it.
+            None => true,
+        }
+    }
+}
+
+#[cfg(feature = "refresher")]
+impl crate::CredentialExpiry for Credential {
+    fn expires_at(&self) -> Option<DateTime> {
+        self.expires_in
+    }
 }
 
 /// Loader will load credential from different methods.
 #[cfg_attr(test, derive(Debug))]
 pub struct Loader {
-    client: Client,
+    client: Arc<dyn HttpSend>,
     config: Config,
 
+    disable_ecs_metadata: bool,
+
     credential: Arc<Mutex<Option<Credential>>>,
+    refresh_lock: Arc<tokio::sync::Mutex<()>>,
+    ecs_metadata_token: Arc<Mutex<Option<(String, DateTime)>>>,
 }
 
 impl Loader {
     /// Create a new loader via client and config.
-    pub fn new(client: Client, config: Config) -> Self {
+    pub fn new(client: impl HttpSend, config: Config) -> Self {
         Self {
-            client,
+            client: Arc::new(client),
             config,
 
+            disable_ecs_metadata: false,
+
             credential: Arc::default(),
+            refresh_lock: Arc::default(),
+            ecs_metadata_token: Arc::default(),
         }
     }
 
+    /// Disable load from ecs metadata.
+    pub fn with_disable_ecs_metadata(mut self) -> Self {
+        self.disable_ecs_metadata = true;
+        self
+    }
+
     /// Load credential.
-    pub async fn load(&self) -> Result<Option<Credential>> {
-        // Return cached credential if it's valid.
-        match self.credential.lock().expect("lock poisoned").clone() {
-            Some(cred) if cred.is_valid() => return Ok(Some(cred)),
-            _ => (),
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "reqsign::load",
+            level = "debug",
+            skip_all,
+            fields(provider = "aliyun", cache_hit = tracing::field::Empty)
+        )
+    )]
+    pub async fn load(&self) -> crate::Result<Option<Credential>> {
+        if let Some(cred) = self.cached() {
+            record_cache_hit(true);
+            return Ok(Some(cred));
+        }
+
+        // Only one task loads credential at the same time, others will wait
+        // and reuse its result.
+        let _guard = self.refresh_lock.lock().await;
+        if let Some(cred) = self.cached() {
+            record_cache_hit(true);
+            return Ok(Some(cred));
         }
+        record_cache_hit(false);
 
         let cred = if let Some(cred) = self.load_inner().await? {
             cred
         } else {
             return Ok(None);
         };
+        credential_refreshed("aliyun", cred.expires_in);
 
         let mut lock = self.credential.lock().expect("lock poisoned");
         *lock = Some(cred.clone());
@@ -88,7 +167,28 @@ impl Loader {
         Ok(Some(cred))
     }
 
+    /// Return cached credential if it's valid.
+    fn cached(&self) -> Option<Credential> {
+        self.credential
+            .lock()
+            .expect("lock poisoned")
+            .clone()
+            .filter(|cred| cred.is_valid())
+    }
+
     async fn load_inner(&self) -> Result<Option<Credential>> {
+        // Errors of other sources are skipped, but misconfigurations should
+        // be reported instead of falling back to them silently.
+        self.config.validate()?;
+
+        if let Ok(Some(cred)) = self
+            .load_via_assume_role()
+            .await
+            .map_err(|err| debug!("load credential via assume_role failed: {err:?}"))
+        {
+            return Ok(Some(cred));
+        }
+
         if let Ok(Some(cred)) = self
             .load_via_static()
             .map_err(|err| debug!("load credential via static failed: {err:?}"))
@@ -104,6 +204,14 @@ impl Loader {
             return Ok(Some(cred));
         }
 
+        if let Ok(Some(cred)) = self
+            .load_via_ecs_metadata()
+            .await
+            .map_err(|err| debug!("load credential via ecs_metadata failed: {err:?}"))
+        {
+            return Ok(Some(cred));
+        }
+
         Ok(None)
     }
 
@@ -138,20 +246,74 @@ impl Loader {
         let role_session_name = &self.config.role_session_name;
 
         // Construct request to Aliyun STS Service.
-        let url = format!("https://sts.aliyuncs.com/?Action=AssumeRoleWithOIDC&OIDCProviderArn={}&RoleArn={}&RoleSessionName={}&Format=JSON&Version=2015-04-01&Timestamp={}&OIDCToken={}", provider_arn, role_arn, role_session_name, format_rfc3339(now()), token);
+        let url = format!("{}/?Action=AssumeRoleWithOIDC&OIDCProviderArn={}&RoleArn={}&RoleSessionName={}&Format=JSON&Version=2015-04-01&Timestamp={}&OIDCToken={}", self.sts_endpoint(), provider_arn, role_arn, role_session_name, format_rfc3339(now()), token);
 
-        let req = self.client.get(&url).header(
-            http::header::CONTENT_TYPE.as_str(),
-            "application/x-www-form-urlencoded",
-        );
+        let req = http::Request::get(&url)
+            .header(
+                http::header::CONTENT_TYPE.as_str(),
+                "application/x-www-form-urlencoded",
+            )
+            .body(Vec::new())?;
+
+        let resp = check_available(self.client.send(req).await)?;
+        if resp.status() != http::StatusCode::OK {
+            let content = String::from_utf8_lossy(resp.body());
+            return Err(anyhow!("request to Aliyun STS Services failed: {content}"));
+        }
+
+        let resp: AssumeRoleWithOidcResponse = serde_json::from_slice(resp.body())?;
+        let resp_cred = resp.credentials;
+
+        let cred = Credential {
+            access_key_id: resp_cred.access_key_id,
+            access_key_secret: resp_cred.access_key_secret,
+            security_token: Some(resp_cred.security_token),
+            expires_in: Some(parse_rfc3339(&resp_cred.expiration)?),
+        };
+
+        Ok(Some(cred))
+    }
 
-        let resp = req.send().await?;
+    /// Assume role via static credential.
+    ///
+    /// Reference: [AssumeRole](https://help.aliyun.com/document_detail/371864.html)
+    async fn load_via_assume_role(&self) -> Result<Option<Credential>> {
+        let (ak, sk, role_arn) = match (
+            &self.config.access_key_id,
+            &self.config.access_key_secret,
+            &self.config.role_arn,
+        ) {
+            (Some(ak), Some(sk), Some(role_arn)) => (ak, sk, role_arn),
+            _ => return Ok(None),
+        };
+
+        let mut params = vec![
+            ("Action", "AssumeRole".to_string()),
+            ("Format", "JSON".to_string()),
+            ("Version", "2015-04-01".to_string()),
+            ("AccessKeyId", ak.clone()),
+            ("SignatureMethod", "HMAC-SHA1".to_string()),
+            ("SignatureVersion", "1.0".to_string()),
+            ("SignatureNonce", rand::random::<u64>().to_string()),
+            ("Timestamp", format_rfc3339(now())),
+            ("RoleArn", role_arn.clone()),
+            ("RoleSessionName", self.config.role_session_name.clone()),
+            ("DurationSeconds", "3600".to_string()),
+        ];
+        if let Some(token) = &self.config.security_token {
+            params.push(("SecurityToken", token.clone()));
+        }
+
+        let url = format!("{}/?{}", self.sts_endpoint(), rpc_signed_query(params, sk));
+        let req = http::Request::get(&url).body(Vec::new())?;
+
+        let resp = check_available(self.client.send(req).await)?;
         if resp.status() != http::StatusCode::OK {
-            let content = resp.text().await?;
+            let content = String::from_utf8_lossy(resp.body());
             return Err(anyhow!("request to Aliyun STS Services failed: {content}"));
         }
 
-        let resp: AssumeRoleWithOidcResponse = serde_json::from_slice(&resp.bytes().await?)?;
+        let resp: AssumeRoleWithOidcResponse = serde_json::from_slice(resp.body())?;
         let resp_cred = resp.credentials;
 
         let cred = Credential {
@@ -163,6 +325,184 @@ impl Loader {
 
         Ok(Some(cred))
     }
+
+    /// Load credential of the ram role attached to ecs instance.
+    ///
+    /// Reference: [Instance RAM roles](https://www.alibabacloud.com/help/en/ecs/user-guide/attach-an-instance-ram-role-to-an-ecs-instance)
+    async fn load_via_ecs_metadata(&self) -> Result<Option<Credential>> {
+        if self.disable_ecs_metadata {
+            return Ok(None);
+        }
+
+        let role_name = match &self.config.ecs_ram_role {
+            Some(role_name) => role_name.clone(),
+            None => {
+                let content = self
+                    .ecs_metadata_get("/latest/meta-data/ram/security-credentials/")
+                    .await?;
+                match content.lines().next() {
+                    Some(role_name) if !role_name.trim().is_empty() => role_name.trim().to_string(),
+                    _ => return Ok(None),
+                }
+            }
+        };
+
+        let content = self
+            .ecs_metadata_get(&format!(
+                "/latest/meta-data/ram/security-credentials/{role_name}"
+            ))
+            .await?;
+        let resp: EcsMetadataRamSecurityCredentials = serde_json::from_str(&content)?;
+        if resp.code != "Success" {
+            return Err(anyhow!(
+                "request to Aliyun ECS Metadata Services failed: {content}"
+            ));
+        }
+
+        let cred = Credential {
+            access_key_id: resp.access_key_id,
+            access_key_secret: resp.access_key_secret,
+            security_token: Some(resp.security_token),
+            expires_in: Some(parse_rfc3339(&resp.expiration)?),
+        };
+
+        Ok(Some(cred))
+    }
+
+    /// Send a GET request to ecs metadata service.
+    ///
+    /// Metadata token will be attached in hardened mode.
+    async fn ecs_metadata_get(&self, path: &str) -> Result<String> {
+        let url = format!(
+            "{}{path}",
+            self.config.ecs_metadata_endpoint.trim_end_matches('/')
+        );
+
+        let mut req = http::Request::get(&url);
+        if let Some(token) = self.ecs_metadata_token().await? {
+            req = req.header("X-aliyun-ecs-metadata-token", token);
+        }
+
+        let resp = check_available(self.client.send(req.body(Vec::new())?).await)?;
+        if resp.status() != http::StatusCode::OK {
+            let content = String::from_utf8_lossy(resp.body());
+            return Err(anyhow!(
+                "request to Aliyun ECS Metadata Services failed: {content}"
+            ));
+        }
+
+        Ok(String::from_utf8(resp.into_body())?)
+    }
+
+    /// Get ecs metadata token for hardened mode.
+    ///
+    /// - Cached token will be used until it's expired.
+    /// - Returns `Ok(None)` if token can't be fetched and IMDSv1 is not disabled.
+    async fn ecs_metadata_token(&self) -> Result<Option<String>> {
+        if let Some((token, expires_in)) = self
+            .ecs_metadata_token
+            .lock()
+            .expect("lock poisoned")
+            .clone()
+        {
+            if expires_in > now() {
+                return Ok(Some(token));
+            }
+        }
+
+        match self.fetch_ecs_metadata_token().await {
+            Ok((token, expires_in)) => {
+                *self.ecs_metadata_token.lock().expect("lock poisoned") =
+                    Some((token.clone(), expires_in));
+                Ok(Some(token))
+            }
+            Err(err) if !self.config.imds_v1_disabled => {
+                debug!("fetch ecs metadata token failed, fallback to normal mode: {err:?}");
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn fetch_ecs_metadata_token(&self) -> Result<(String, DateTime)> {
+        let url = format!(
+            "{}/latest/api/token",
+            self.config.ecs_metadata_endpoint.trim_end_matches('/')
+        );
+        let req = http::Request::put(url)
+            .header(
+                "X-aliyun-ecs-metadata-token-ttl-seconds",
+                ECS_METADATA_TOKEN_TTL.as_secs().to_string(),
+            )
+            .body(Vec::new())?;
+        let resp = check_available(self.client.send(req).await)?;
+        if resp.status() != http::StatusCode::OK {
+            let content = String::from_utf8_lossy(resp.body());
+            return Err(anyhow!(
+                "request to Aliyun ECS Metadata Services failed: {content}"
+            ));
+        }
+        let token = String::from_utf8(resp.into_body())?;
+
+        // Refresh the token a bit earlier to avoid been rejected.
+        let expires_in = now() + chrono::Duration::from_std(ECS_METADATA_TOKEN_TTL)?
+            - chrono::Duration::minutes(1);
+        Ok((token, expires_in))
+    }
+
+    /// Get the sts endpoint.
+    ///
+    /// The returning format will be like `https://sts.cn-hangzhou.aliyuncs.com`
+    fn sts_endpoint(&self) -> String {
+        if let Some(endpoint) = &self.config.sts_endpoint {
+            return endpoint.trim_end_matches('/').to_string();
+        }
+
+        match &self.config.sts_region {
+            Some(region) => format!("https://sts.{region}.aliyuncs.com"),
+            None => "https://sts.aliyuncs.com".to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl CredentialLoad<Credential> for Loader {
+    async fn load(&self) -> crate::Result<Option<Credential>> {
+        Loader::load(self).await
+    }
+}
+
+/// Build signed query for Aliyun RPC style API.
+///
+/// Reference: [Request signatures](https://help.aliyun.com/document_detail/315526.html)
+fn rpc_signed_query(mut params: Vec<(&str, String)>, access_key_secret: &str) -> String {
+    params.sort();
+
+    let query = params
+        .iter()
+        .map(|(k, v)| {
+            format!(
+                "{}={}",
+                utf8_percent_encode(k, &ALIYUN_RPC_ENCODE_SET),
+                utf8_percent_encode(v, &ALIYUN_RPC_ENCODE_SET)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+
+    let string_to_sign = format!(
+        "GET&%2F&{}",
+        utf8_percent_encode(&query, &ALIYUN_RPC_ENCODE_SET)
+    );
+    let signature = base64_hmac_sha1(
+        format!("{access_key_secret}&").as_bytes(),
+        string_to_sign.as_bytes(),
+    );
+
+    format!(
+        "{query}&Signature={}",
+        utf8_percent_encode(&signature, &ALIYUN_RPC_ENCODE_SET)
+    )
 }
 
 #[derive(Default, Debug, Deserialize)]
@@ -181,8 +521,20 @@ struct AssumeRoleWithOidcCredentials {
     expiration: String,
 }
 
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+struct EcsMetadataRamSecurityCredentials {
+    access_key_id: String,
+    access_key_secret: String,
+    security_token: String,
+    expiration: String,
+
+    code: String,
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
     use std::env;
     use std::str::FromStr;
     use std::time::Duration;
@@ -245,6 +597,230 @@ mod tests {
         Ok(())
     }
 
+    /// Example of [Request signatures](https://help.aliyun.com/document_detail/315526.html)
+    #[test]
+    fn test_rpc_signed_query() {
+        let params = vec![
+            ("AccessKeyId", "testid".to_string()),
+            ("Action", "DescribeRegions".to_string()),
+            ("Format", "XML".to_string()),
+            ("SignatureMethod", "HMAC-SHA1".to_string()),
+            (
+                "SignatureNonce",
+                "3ee8c1b8-83d3-44af-a94f-4e0ad82fd6cf".to_string(),
+            ),
+            ("SignatureVersion", "1.0".to_string()),
+            ("Timestamp", "2016-02-23T12:46:24Z".to_string()),
+            ("Version", "2014-05-26".to_string()),
+        ];
+
+        assert_eq!(
+            rpc_signed_query(params, "testsecret"),
+            "AccessKeyId=testid&Action=DescribeRegions&Format=XML&SignatureMethod=HMAC-SHA1&SignatureNonce=3ee8c1b8-83d3-44af-a94f-4e0ad82fd6cf&SignatureVersion=1.0&Timestamp=2016-02-23T12%3A46%3A24Z&Version=2014-05-26&Signature=OLeaidS1JvxuMvnyHOwuJ%2BuX5qY%3D"
+        );
+    }
+
+    /// Mock Aliyun STS which returns `STS.{n}` for the n-th request.
+    #[derive(Debug, Default)]
+    struct MockSts {
+        expiration: String,
+        requests: Mutex<Vec<http::Request<Vec<u8>>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl HttpSend for MockSts {
+        async fn send(&self, req: http::Request<Vec<u8>>) -> Result<http::Response<Vec<u8>>> {
+            let mut requests = self.requests.lock().unwrap();
+            let body = format!(
+                r#"{{"Credentials":{{"AccessKeyId":"STS.{}","AccessKeySecret":"secret","SecurityToken":"token","Expiration":"{}"}}}}"#,
+                requests.len(),
+                self.expiration
+            );
+            requests.push(req);
+
+            Ok(http::Response::builder()
+                .status(StatusCode::OK)
+                .body(body.into_bytes())?)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_load_via_assume_role() -> Result<()> {
+        use chrono::TimeZone;
+        use chrono::Utc;
+
+        use crate::time::set_fake_now;
+
+        let start = Utc.with_ymd_and_hms(2022, 3, 1, 8, 0, 0).unwrap();
+        let expiration = start + chrono::Duration::hours(1);
+        let client = Arc::new(MockSts {
+            expiration: format_rfc3339(expiration),
+            ..Default::default()
+        });
+
+        let loader = Loader::new(
+            client.clone(),
+            Config {
+                access_key_id: Some("access_key_id".to_string()),
+                access_key_secret: Some("access_key_secret".to_string()),
+                role_arn: Some("acs:ram::123456789012****:role/adminrole".to_string()),
+                sts_region: Some("cn-hangzhou".to_string()),
+                ..Default::default()
+            },
+        );
+
+        set_fake_now(Some(start));
+        let cred = loader.load().await?.expect("credential must exist");
+        assert_eq!(cred.access_key_id, "STS.0");
+        assert_eq!(cred.security_token.as_deref(), Some("token"));
+
+        {
+            let requests = client.requests.lock().unwrap();
+            assert_eq!(requests.len(), 1);
+            let uri = requests[0].uri();
+            assert_eq!(uri.host(), Some("sts.cn-hangzhou.aliyuncs.com"));
+
+            let query: HashMap<String, String> =
+                form_urlencoded::parse(uri.query().unwrap_or_default().as_bytes())
+                    .into_owned()
+                    .collect();
+            assert_eq!(query["Action"], "AssumeRole");
+            assert_eq!(query["AccessKeyId"], "access_key_id");
+            assert_eq!(query["RoleArn"], "acs:ram::123456789012****:role/adminrole");
+            assert_eq!(query["RoleSessionName"], "resign");
+            assert_eq!(query["Timestamp"], "2022-03-01T08:00:00Z");
+            assert!(query.contains_key("Signature"));
+        }
+
+        // Credential should be cached until expiration.
+        set_fake_now(Some(expiration - chrono::Duration::minutes(30)));
+        let cred = loader.load().await?.expect("credential must exist");
+        assert_eq!(cred.access_key_id, "STS.0");
+
+        set_fake_now(Some(expiration - chrono::Duration::seconds(60)));
+        let cred = loader.load().await?.expect("credential must exist");
+        assert_eq!(cred.access_key_id, "STS.1");
+
+        set_fake_now(None);
+        Ok(())
+    }
+
+    /// Mock ecs metadata service which requires token if `hardened` is true.
+    #[derive(Debug, Default)]
+    struct MockEcsMetadata {
+        hardened: bool,
+        requests: Mutex<Vec<http::Request<Vec<u8>>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl HttpSend for MockEcsMetadata {
+        async fn send(&self, req: http::Request<Vec<u8>>) -> Result<http::Response<Vec<u8>>> {
+            let token = req
+                .headers()
+                .get("X-aliyun-ecs-metadata-token")
+                .map(|v| v.to_str().unwrap().to_string());
+
+            let (status, body) = match (req.method().as_str(), req.uri().path()) {
+                ("PUT", "/latest/api/token") if self.hardened => {
+                    assert_eq!(
+                        req.headers()["X-aliyun-ecs-metadata-token-ttl-seconds"],
+                        "21600"
+                    );
+                    (StatusCode::OK, "ecs_token".to_string())
+                }
+                ("PUT", "/latest/api/token") => (StatusCode::FORBIDDEN, String::new()),
+                _ if self.hardened && token.as_deref() != Some("ecs_token") => {
+                    (StatusCode::UNAUTHORIZED, String::new())
+                }
+                ("GET", "/latest/meta-data/ram/security-credentials/") => {
+                    (StatusCode::OK, "test_role".to_string())
+                }
+                ("GET", "/latest/meta-data/ram/security-credentials/test_role") => (
+                    StatusCode::OK,
+                    r#"{"AccessKeyId":"STS.access_key_id","AccessKeySecret":"access_key_secret","Expiration":"2099-01-01T00:00:00Z","SecurityToken":"security_token","LastUpdated":"2022-03-01T08:00:00Z","Code":"Success"}"#.to_string(),
+                ),
+                _ => (StatusCode::NOT_FOUND, String::new()),
+            };
+            self.requests.lock().unwrap().push(req);
+
+            Ok(http::Response::builder()
+                .status(status)
+                .body(body.into_bytes())?)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_load_via_ecs_metadata() -> Result<()> {
+        let client = Arc::new(MockEcsMetadata {
+            hardened: true,
+            ..Default::default()
+        });
+        let loader = Loader::new(client.clone(), Config::default());
+
+        let cred = loader
+            .load_via_ecs_metadata()
+            .await?
+            .expect("credential must exist");
+        assert_eq!(cred.access_key_id, "STS.access_key_id");
+        assert_eq!(cred.access_key_secret, "access_key_secret");
+        assert_eq!(cred.security_token.as_deref(), Some("security_token"));
+        assert_eq!(
+            cred.expires_in,
+            Some(parse_rfc3339("2099-01-01T00:00:00Z")?)
+        );
+
+        // Metadata token should be cached.
+        loader.load_via_ecs_metadata().await?;
+        let requests = client.requests.lock().unwrap();
+        assert_eq!(
+            requests
+                .iter()
+                .filter(|req| req.method() == http::Method::PUT)
+                .count(),
+            1
+        );
+        assert_eq!(requests.len(), 5);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_load_via_ecs_metadata_with_role_name() -> Result<()> {
+        let client = Arc::new(MockEcsMetadata::default());
+        let loader = Loader::new(
+            client.clone(),
+            Config {
+                ecs_ram_role: Some("test_role".to_string()),
+                ..Default::default()
+            },
+        );
+
+        // Fallback to normal mode if token can't be fetched.
+        let cred = loader
+            .load_via_ecs_metadata()
+            .await?
+            .expect("credential must exist");
+        assert_eq!(cred.access_key_id, "STS.access_key_id");
+        {
+            let requests = client.requests.lock().unwrap();
+            assert_eq!(requests.len(), 2);
+            assert_eq!(
+                requests[1].uri().path(),
+                "/latest/meta-data/ram/security-credentials/test_role"
+            );
+        }
+
+        let loader = Loader::new(
+            client,
+            Config {
+                ecs_ram_role: Some("test_role".to_string()),
+                imds_v1_disabled: true,
+                ..Default::default()
+            },
+        );
+        assert!(loader.load_via_ecs_metadata().await.is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_signer_with_oidc() -> Result<()> {
         let _ = env_logger::builder().is_test(true).try_init();
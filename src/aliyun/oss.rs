@@ -8,6 +8,7 @@ use anyhow::Result;
 use http::header::AUTHORIZATION;
 use http::header::CONTENT_TYPE;
 use http::header::DATE;
+use http::HeaderMap;
 use http::HeaderValue;
 use log::debug;
 use once_cell::sync::Lazy;
@@ -17,16 +18,21 @@ use super::credential::Credential;
 use crate::ctx::SigningContext;
 use crate::ctx::SigningMethod;
 use crate::hash::base64_hmac_sha1;
+use crate::request::sign_to_headers;
 use crate::request::SignableRequest;
 use crate::time;
 use crate::time::format_http_date;
 use crate::time::DateTime;
+use crate::Error;
 
 const CONTENT_MD5: &str = "content-md5";
 
 /// Singer for Aliyun OSS.
 pub struct Signer {
     bucket: String,
+
+    time: Option<DateTime>,
+    time_offset: chrono::Duration,
 }
 
 impl Signer {
@@ -34,17 +40,47 @@ impl Signer {
     pub fn new(bucket: &str) -> Self {
         Self {
             bucket: bucket.to_owned(),
+            time: None,
+            time_offset: chrono::Duration::zero(),
         }
     }
 
+    /// Specify the signing time.
+    ///
+    /// Current time will be used if not set. Fixed time is useful to
+    /// generate reproducible signatures.
+    pub fn with_time(mut self, time: DateTime) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    /// Specify the clock skew offset, see [`skew_from_response`](crate::time::skew_from_response).
+    pub fn with_time_offset(mut self, offset: chrono::Duration) -> Self {
+        self.time_offset = offset;
+        self
+    }
+
     /// Building a signing context.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "reqsign::sign",
+            level = "debug",
+            skip_all,
+            fields(provider = "aliyun")
+        )
+    )]
     fn build(
         &self,
         req: &mut impl SignableRequest,
         method: SigningMethod,
         cred: &Credential,
     ) -> Result<SigningContext> {
-        let now = time::now();
+        if cred.is_expired() {
+            return Err(Error::CredentialExpired.into());
+        }
+
+        let now = self.time.unwrap_or_else(|| time::now() + self.time_offset);
         let mut ctx = req.build()?;
 
         let string_to_sign = string_to_sign(&mut ctx, cred, now, method, &self.bucket)?;
@@ -82,10 +118,24 @@ impl Signer {
     }
 
     /// Signing request with header.
-    pub fn sign(&self, req: &mut impl SignableRequest, cred: &Credential) -> Result<()> {
-        let ctx = self.build(req, SigningMethod::Header, cred)?;
+    pub fn sign(&self, req: &mut impl SignableRequest, cred: &Credential) -> crate::Result<()> {
+        let ctx = self
+            .build(req, SigningMethod::Header, cred)
+            .map_err(Error::signing)?;
+        req.apply(ctx).map_err(Error::signing)
+    }
 
-        req.apply(ctx)
+    /// Calculate the headers that will be inserted or changed while signing
+    /// with header, the request will be left untouched.
+    ///
+    /// It's useful to apply the headers to another request like proxies
+    /// that re-emit requests.
+    pub fn sign_to_headers(
+        &self,
+        req: &mut impl SignableRequest,
+        cred: &Credential,
+    ) -> crate::Result<HeaderMap> {
+        sign_to_headers(req, |ctx| self.sign(ctx, cred))
     }
 
     /// Signing request with query.
@@ -94,10 +144,11 @@ impl Signer {
         req: &mut impl SignableRequest,
         expire: Duration,
         cred: &Credential,
-    ) -> Result<()> {
-        let ctx = self.build(req, SigningMethod::Query(expire), cred)?;
-
-        req.apply(ctx)
+    ) -> crate::Result<()> {
+        let ctx = self
+            .build(req, SigningMethod::Query(expire), cred)
+            .map_err(Error::signing)?;
+        req.apply(ctx).map_err(Error::signing)
     }
 }
 
@@ -304,3 +355,76 @@ static SUB_RESOURCES: Lazy<HashSet<&'static str>> = Lazy::new(|| {
         "metaQuery",
     ])
 });
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use chrono::Utc;
+
+    use super::*;
+
+    fn sts_credential() -> Credential {
+        Credential {
+            access_key_id: "test_access_key".to_string(),
+            access_key_secret: "test_secret".to_string(),
+            security_token: Some("sts_token".to_string()),
+            expires_in: None,
+        }
+    }
+
+    #[test]
+    fn test_sign_with_security_token() -> Result<()> {
+        let now = Utc.with_ymd_and_hms(2022, 3, 1, 8, 12, 34).unwrap();
+        let cred = sts_credential();
+
+        let build_req = || {
+            http::Request::get("https://examplebucket.oss-cn-hangzhou.aliyuncs.com/example.txt")
+                .header("x-oss-storage-class", "Standard")
+                .header("x-oss-meta-a", "a")
+                .body("")
+        };
+        let mut ctx = build_req()?.build()?;
+
+        // Security token must be sorted with other OSS headers.
+        let s = string_to_sign(&mut ctx, &cred, now, SigningMethod::Header, "examplebucket")?;
+        assert_eq!(
+            s,
+            "GET\n\n\nTue, 01 Mar 2022 08:12:34 GMT\nx-oss-meta-a:a\nx-oss-security-token:sts_token\nx-oss-storage-class:Standard\n/examplebucket/example.txt"
+        );
+
+        let mut req = build_req()?;
+        Signer::new("examplebucket")
+            .with_time(now)
+            .sign(&mut req, &cred)?;
+
+        assert_eq!(req.headers()["x-oss-security-token"], "sts_token");
+        assert_eq!(
+            req.headers()[AUTHORIZATION],
+            "OSS test_access_key:/HsZ59CsgBOC3uNFNIcQIXl9o5w="
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_query_with_security_token() -> Result<()> {
+        let now = Utc.with_ymd_and_hms(2022, 3, 1, 8, 12, 34).unwrap();
+        let cred = sts_credential();
+
+        let mut req =
+            http::Request::get("https://examplebucket.oss-cn-hangzhou.aliyuncs.com/example.txt")
+                .body("")?;
+
+        Signer::new("examplebucket").with_time(now).sign_query(
+            &mut req,
+            Duration::from_secs(3600),
+            &cred,
+        )?;
+
+        assert!(req.headers().get("x-oss-security-token").is_none());
+        assert_eq!(
+            req.uri().query(),
+            Some("security-token=sts%5Ftoken&OSSAccessKeyId=test_access_key&Expires=1646125954&Signature=Blbb98uJLu80r%2B1LIjaf4T2xcs4%3D")
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+//! AWS S3 bucket naming rules.
+
+use std::net::Ipv4Addr;
+
+use anyhow::anyhow;
+
+/// Prefixes reserved by S3 that bucket names can't start with.
+const RESERVED_PREFIXES: &[&str] = &["xn--", "sthree-", "amzn-s3-demo-"];
+
+/// Suffixes reserved by S3 that bucket names can't end with.
+const RESERVED_SUFFIXES: &[&str] = &["-s3alias", "--ol-s3", ".mrap", "--x-s3", "--table-s3"];
+
+/// Validate bucket name against the naming rules of S3 general purpose
+/// buckets.
+///
+/// Like other AWS items, it's exported with the `aws_` prefix as
+/// `reqsign::aws_validate_bucket_name`, there is no public `reqsign::aws`
+/// module.
+///
+/// - [Bucket naming rules](https://docs.aws.amazon.com/AmazonS3/latest/userguide/bucketnamingrules.html)
+///
+/// Invalid names like `My_Bucket` can't be used in virtual-hosted-style
+/// urls and will fail with confusing DNS or signature errors, check them
+/// before constructing the host:
+///
+/// ```
+/// use reqsign::aws_validate_bucket_name;
+///
+/// assert!(aws_validate_bucket_name("examplebucket").is_ok());
+/// assert!(aws_validate_bucket_name("Example_Bucket").is_err());
+/// ```
+///
+/// Names with dots are valid, but certificates of S3 don't match them in
+/// virtual-hosted-style urls via HTTPS.
+pub fn validate_bucket_name(name: &str) -> crate::Result<()> {
+    if name.len() < 3 || name.len() > 63 {
+        return Err(anyhow!("bucket name {name} must be between 3 and 63 characters long").into());
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| !matches!(c, 'a'..='z' | '0'..='9' | '.' | '-'))
+    {
+        return Err(anyhow!("bucket name {name} contains invalid character {c:?}").into());
+    }
+
+    let is_alphanumeric = |b: u8| b.is_ascii_lowercase() || b.is_ascii_digit();
+    let bs = name.as_bytes();
+    if !is_alphanumeric(bs[0]) || !is_alphanumeric(bs[bs.len() - 1]) {
+        return Err(
+            anyhow!("bucket name {name} must begin and end with a letter or number").into(),
+        );
+    }
+    if name.contains("..") {
+        return Err(anyhow!("bucket name {name} must not contain two adjacent periods").into());
+    }
+    if name.parse::<Ipv4Addr>().is_ok() {
+        return Err(anyhow!("bucket name {name} must not be formatted as an IP address").into());
+    }
+    if let Some(v) = RESERVED_PREFIXES.iter().find(|v| name.starts_with(*v)) {
+        return Err(anyhow!("bucket name {name} must not start with reserved prefix {v}").into());
+    }
+    if let Some(v) = RESERVED_SUFFIXES.iter().find(|v| name.ends_with(*v)) {
+        return Err(anyhow!("bucket name {name} must not end with reserved suffix {v}").into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_bucket_name() {
+        for name in [
+            "abc",
+            "examplebucket",
+            "example-bucket-2023",
+            "docexamplewebsite.com",
+            "1bucket1",
+            &"a".repeat(63),
+        ] {
+            assert!(validate_bucket_name(name).is_ok(), "{name} must be valid");
+        }
+
+        for name in [
+            "ab",
+            &"a".repeat(64),
+            "ExampleBucket",
+            "example_bucket",
+            "example bucket",
+            "-example",
+            "example-",
+            ".example",
+            "example..bucket",
+            "192.168.5.4",
+            "xn--example",
+            "sthree-example",
+            "example-s3alias",
+            "example--ol-s3",
+            "example.mrap",
+        ] {
+            assert!(
+                validate_bucket_name(name).is_err(),
+                "{name} must be invalid"
+            );
+        }
+    }
+}
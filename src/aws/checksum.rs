@@ -0,0 +1,122 @@
+//! AWS S3 additional checksums.
+
+use sha1::Sha1;
+use sha2::Digest;
+use sha2::Sha256;
+
+use crate::hash::base64_encode;
+
+/// ChecksumAlgorithm is the algorithm used by S3 additional checksums.
+///
+/// - [Checking object integrity](https://docs.aws.amazon.com/AmazonS3/latest/userguide/checking-object-integrity.html)
+///
+/// Checksum headers like `x-amz-checksum-crc32c` are always signed by
+/// [`AwsV4Signer`][crate::AwsV4Signer] like other `x-amz-*` headers, so
+/// they must be set before signing:
+///
+/// ```
+/// use reqsign::AwsChecksumAlgorithm;
+///
+/// let body = b"Hello, World!";
+/// let algo = AwsChecksumAlgorithm::Crc32c;
+/// let req = http::Request::put("https://bucket.s3.amazonaws.com/key")
+///     .header(algo.header_name(), algo.checksum(body))
+///     .body(body.to_vec())
+///     .unwrap();
+/// assert_eq!(req.headers()["x-amz-checksum-crc32c"], "TVUQaA==");
+/// ```
+///
+/// Checksums sent as trailers of `aws-chunked` body are not supported yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+    /// CRC32 used by zlib and gzip.
+    Crc32,
+    /// CRC32C with the Castagnoli polynomial.
+    Crc32c,
+    /// SHA1.
+    Sha1,
+    /// SHA256.
+    Sha256,
+}
+
+impl ChecksumAlgorithm {
+    /// The header name of this checksum like `x-amz-checksum-crc32`.
+    pub fn header_name(&self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Crc32 => "x-amz-checksum-crc32",
+            ChecksumAlgorithm::Crc32c => "x-amz-checksum-crc32c",
+            ChecksumAlgorithm::Sha1 => "x-amz-checksum-sha1",
+            ChecksumAlgorithm::Sha256 => "x-amz-checksum-sha256",
+        }
+    }
+
+    /// Calculate the base64 encoded checksum of content.
+    ///
+    /// CRC checksums are encoded in big-endian as S3 expects.
+    pub fn checksum(&self, content: &[u8]) -> String {
+        match self {
+            ChecksumAlgorithm::Crc32 => base64_encode(&crc32(&CRC32_TABLE, content).to_be_bytes()),
+            ChecksumAlgorithm::Crc32c => {
+                base64_encode(&crc32(&CRC32C_TABLE, content).to_be_bytes())
+            }
+            ChecksumAlgorithm::Sha1 => base64_encode(Sha1::digest(content).as_slice()),
+            ChecksumAlgorithm::Sha256 => base64_encode(Sha256::digest(content).as_slice()),
+        }
+    }
+}
+
+const CRC32_TABLE: [u32; 256] = crc32_table(0xEDB8_8320);
+const CRC32C_TABLE: [u32; 256] = crc32_table(0x82F6_3B78);
+
+/// Build the lookup table of reflected CRC32 with given polynomial.
+const fn crc32_table(poly: u32) -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ poly
+            } else {
+                crc >> 1
+            };
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+fn crc32(table: &[u32; 256], content: &[u8]) -> u32 {
+    !content.iter().fold(!0u32, |crc, b| {
+        table[((crc ^ *b as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksum() {
+        // Check values from the CRC catalogue.
+        assert_eq!(crc32(&CRC32_TABLE, b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(&CRC32C_TABLE, b"123456789"), 0xE306_9283);
+
+        for (algo, expected) in [
+            (ChecksumAlgorithm::Crc32, "AAAAAA=="),
+            (ChecksumAlgorithm::Crc32c, "AAAAAA=="),
+            (ChecksumAlgorithm::Sha1, "2jmj7l5rSw0yVb/vlWAYkK/YBwk="),
+            (
+                ChecksumAlgorithm::Sha256,
+                "47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=",
+            ),
+        ] {
+            assert_eq!(algo.checksum(b""), expected, "{algo:?}");
+        }
+        assert_eq!(ChecksumAlgorithm::Crc32.checksum(b"123456789"), "y/Q5Jg==");
+        assert_eq!(ChecksumAlgorithm::Crc32c.checksum(b"123456789"), "4waSgw==");
+    }
+}
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::env;
 use std::fs;
+use std::time::Duration;
 
 use anyhow::anyhow;
 use anyhow::Result;
@@ -9,6 +10,7 @@ use log::debug;
 
 use super::constants::*;
 use crate::dirs::expand_homedir;
+use crate::error::required_together;
 
 /// Config for aws services.
 #[derive(Clone)]
@@ -28,14 +30,23 @@ pub struct Config {
     ///
     /// - this field if it's `is_some`
     /// - env value: [`AWS_PROFILE`]
+    /// - env value: [`AWS_DEFAULT_PROFILE`]
     /// - default to: `default`
-    pub profile: String,
+    ///
+    /// Use [`Config::profile_name`] to get the profile in use.
+    pub profile: Option<String>,
 
     /// `region` will be loaded from:
     ///
     /// - this field if it's `is_some`
     /// - env value: [`AWS_REGION`]
+    /// - env value: [`AWS_DEFAULT_REGION`]
     /// - profile config: `region`
+    ///
+    /// Env values take precedence over profile config no matter whether
+    /// `from_env` is called before or after `from_profile`. Use
+    /// [`AwsLoader::region`][crate::AwsLoader::region] to fallback to
+    /// EC2 instance metadata.
     pub region: Option<String>,
     /// `sts_regional_endpoints` will be loaded from:
     ///
@@ -43,6 +54,38 @@ pub struct Config {
     /// - profile config: `sts_regional_endpoints`
     /// - default to `legacy`
     pub sts_regional_endpoints: String,
+    /// `sts_endpoint` will be loaded from:
+    ///
+    /// - this field if it's `is_some`
+    /// - env value: [`AWS_ENDPOINT_URL_STS`]
+    ///
+    /// If not set, the endpoint will be decided by `region`,
+    /// `sts_regional_endpoints`, `use_fips_endpoint` and
+    /// `use_dualstack_endpoint`. Set this to use custom endpoints like VPC
+    /// endpoints.
+    pub sts_endpoint: Option<String>,
+    /// `use_fips_endpoint` will be loaded from:
+    ///
+    /// - env value: [`AWS_USE_FIPS_ENDPOINT`]
+    /// - profile config: `use_fips_endpoint`
+    /// - default to `false`
+    ///
+    /// If enabled, loaders will use FIPS endpoints of STS and SSO like
+    /// `https://sts-fips.us-east-1.amazonaws.com`, `region` is required.
+    ///
+    /// Signers sign the host in request as-is, so users should send
+    /// requests to FIPS endpoints like `s3-fips.us-gov-west-1.amazonaws.com`
+    /// by themselves.
+    pub use_fips_endpoint: bool,
+    /// `use_dualstack_endpoint` will be loaded from:
+    ///
+    /// - env value: [`AWS_USE_DUALSTACK_ENDPOINT`]
+    /// - profile config: `use_dualstack_endpoint`
+    /// - default to `false`
+    ///
+    /// If enabled, loaders will use dualstack (IPv4 and IPv6) endpoints of
+    /// STS and SSO like `https://sts.us-east-1.api.aws`, `region` is required.
+    pub use_dualstack_endpoint: bool,
     /// `access_key_id` will be loaded from
     ///
     /// - this field if it's `is_some`
@@ -78,29 +121,179 @@ pub struct Config {
     /// - this field if it's `is_some`.
     /// - profile config: `external_id`
     pub external_id: Option<String>,
+    /// `duration_seconds` value will be load from:
+    ///
+    /// - this field if it's `is_some`.
+    /// - profile config: `duration_seconds`
+    ///
+    /// The duration of assumed role session, STS will use `3600` if not set.
+    pub duration_seconds: Option<u32>,
+    /// Inline session policy in JSON passed to `AssumeRole`.
+    ///
+    /// Permissions of assumed role session will be the intersection of role
+    /// policies and this policy.
+    pub policy: Option<String>,
+    /// ARNs of managed policies passed to `AssumeRole` as session policies.
+    pub policy_arns: Vec<String>,
+    /// `mfa_serial` value will be load from:
+    ///
+    /// - this field if it's `is_some`.
+    /// - profile config: `mfa_serial`
+    ///
+    /// MFA token code will be required while assuming role if it's set, see
+    /// [`Loader::with_mfa_token_provider`][crate::AwsLoader::with_mfa_token_provider].
+    pub mfa_serial: Option<String>,
+    /// `source_profile` value will be load from:
+    ///
+    /// - this field if it's `is_some`.
+    /// - profile config: `source_profile`
+    ///
+    /// Credentials of the source profile will be used to assume `role_arn`.
+    /// Source profile could be chained like `prod -> staging -> default`.
+    pub source_profile: Option<String>,
+    /// `credential_source` value will be load from:
+    ///
+    /// - this field if it's `is_some`.
+    /// - profile config: `credential_source`
+    ///
+    /// Credentials from `Environment`, `Ec2InstanceMetadata` or
+    /// `EcsContainer` will be used to assume `role_arn`.
+    pub credential_source: Option<String>,
     /// `web_identity_token_file` value will be loaded from:
     ///
     /// - this field if it's `is_some`
     /// - env value: [`AWS_WEB_IDENTITY_TOKEN_FILE`]
     /// - profile config: `web_identity_token_file`
     pub web_identity_token_file: Option<String>,
+    /// `sso_session` value will be loaded from:
+    ///
+    /// - this field if it's `is_some`
+    /// - profile config: `sso_session`
+    ///
+    /// `sso_start_url` and `sso_region` will be loaded from the
+    /// `[sso-session <name>]` section if `sso_session` is set.
+    pub sso_session: Option<String>,
+    /// `sso_start_url` value will be loaded from:
+    ///
+    /// - this field if it's `is_some`
+    /// - profile config: `sso_start_url`
+    /// - sso session config: `sso_start_url`
+    pub sso_start_url: Option<String>,
+    /// `sso_region` value will be loaded from:
+    ///
+    /// - this field if it's `is_some`
+    /// - profile config: `sso_region`
+    /// - sso session config: `sso_region`
+    pub sso_region: Option<String>,
+    /// `sso_account_id` value will be loaded from:
+    ///
+    /// - this field if it's `is_some`
+    /// - profile config: `sso_account_id`
+    pub sso_account_id: Option<String>,
+    /// `sso_role_name` value will be loaded from:
+    ///
+    /// - this field if it's `is_some`
+    /// - profile config: `sso_role_name`
+    pub sso_role_name: Option<String>,
+    /// `credential_process` value will be loaded from:
+    ///
+    /// - this field if it's `is_some`
+    /// - profile config: `credential_process`
+    ///
+    /// The command will be executed directly without shell, its stdout
+    /// should be the JSON credentials described in
+    /// [Sourcing credentials with an external process](https://docs.aws.amazon.com/cli/latest/userguide/cli-configure-sourcing-external.html).
+    /// Arguments are split by whitespaces, and can be quoted by `"` or `'`.
+    pub credential_process: Option<String>,
+    /// `credential_process_timeout` is the max time to wait for
+    /// `credential_process` to exit, the process will be killed after it.
+    ///
+    /// - default to `60s`
+    pub credential_process_timeout: Duration,
+    /// `container_credentials_relative_uri` value will be loaded from:
+    ///
+    /// - this field if it's `is_some`
+    /// - env value: [`AWS_CONTAINER_CREDENTIALS_RELATIVE_URI`]
+    pub container_credentials_relative_uri: Option<String>,
+    /// `container_credentials_full_uri` value will be loaded from:
+    ///
+    /// - this field if it's `is_some`
+    /// - env value: [`AWS_CONTAINER_CREDENTIALS_FULL_URI`]
+    ///
+    /// Only `https` or loopback/link-local `http` uri is allowed.
+    pub container_credentials_full_uri: Option<String>,
+    /// `container_authorization_token` value will be loaded from:
+    ///
+    /// - this field if it's `is_some`
+    /// - env value: [`AWS_CONTAINER_AUTHORIZATION_TOKEN`]
+    pub container_authorization_token: Option<String>,
+    /// `container_authorization_token_file` value will be loaded from:
+    ///
+    /// - this field if it's `is_some`
+    /// - env value: [`AWS_CONTAINER_AUTHORIZATION_TOKEN_FILE`]
+    ///
+    /// The file will be re-read on every refresh since it could be rotated.
+    /// It takes precedence over `container_authorization_token`.
+    pub container_authorization_token_file: Option<String>,
+    /// `ec2_metadata_service_endpoint` value will be loaded from:
+    ///
+    /// - env value: [`AWS_EC2_METADATA_SERVICE_ENDPOINT`]
+    /// - default to `http://169.254.169.254`
+    pub ec2_metadata_service_endpoint: String,
+
+    /// `max_retries` is the max times to retry transient failures of
+    /// credential endpoints like IMDS and STS.
+    ///
+    /// Network errors, `429` and `5xx` responses are transient failures,
+    /// other errors like `4xx` will be returned directly.
+    ///
+    /// - default to `3`
+    pub max_retries: usize,
+    /// `retry_base_delay` is the delay before the first retry, and will be
+    /// doubled for every following retry.
+    ///
+    /// - default to `100ms`
+    pub retry_base_delay: Duration,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
-            config_file: "`~/.aws/config".to_string(),
+            config_file: "~/.aws/config".to_string(),
             shared_credentials_file: "~/.aws/credentials".to_string(),
-            profile: "default".to_string(),
+            profile: None,
             region: None,
             sts_regional_endpoints: "legacy".to_string(),
+            sts_endpoint: None,
+            use_fips_endpoint: false,
+            use_dualstack_endpoint: false,
             access_key_id: None,
             secret_access_key: None,
             session_token: None,
             role_arn: None,
             role_session_name: "reqsign".to_string(),
             external_id: None,
+            duration_seconds: None,
+            policy: None,
+            policy_arns: Vec::new(),
+            mfa_serial: None,
+            source_profile: None,
+            credential_source: None,
             web_identity_token_file: None,
+            sso_session: None,
+            sso_start_url: None,
+            sso_region: None,
+            sso_account_id: None,
+            sso_role_name: None,
+            credential_process: None,
+            credential_process_timeout: Duration::from_secs(60),
+            container_credentials_relative_uri: None,
+            container_credentials_full_uri: None,
+            container_authorization_token: None,
+            container_authorization_token_file: None,
+            ec2_metadata_service_endpoint: EC2_METADATA_SERVICE_ENDPOINT.to_string(),
+            max_retries: 3,
+            retry_base_delay: Duration::from_millis(100),
         }
     }
 }
@@ -116,15 +309,30 @@ impl Config {
         if let Some(v) = envs.get(AWS_SHARED_CREDENTIALS_FILE) {
             self.shared_credentials_file = v.to_string();
         }
-        if let Some(v) = envs.get(AWS_PROFILE) {
-            self.profile = v.to_string();
+        if self.profile.is_none() {
+            self.profile = envs
+                .get(AWS_PROFILE)
+                .or_else(|| envs.get(AWS_DEFAULT_PROFILE))
+                .cloned();
         }
-        if let Some(v) = envs.get(AWS_REGION) {
+        if let Some(v) = envs
+            .get(AWS_REGION)
+            .or_else(|| envs.get(AWS_DEFAULT_REGION))
+        {
             self.region = Some(v.to_string())
         }
         if let Some(v) = envs.get(AWS_STS_REGIONAL_ENDPOINTS) {
             self.sts_regional_endpoints = v.to_string();
         }
+        if let Some(v) = envs.get(AWS_ENDPOINT_URL_STS) {
+            self.sts_endpoint = Some(v.to_string());
+        }
+        if let Some(v) = envs.get(AWS_USE_FIPS_ENDPOINT) {
+            self.use_fips_endpoint = v.eq_ignore_ascii_case("true");
+        }
+        if let Some(v) = envs.get(AWS_USE_DUALSTACK_ENDPOINT) {
+            self.use_dualstack_endpoint = v.eq_ignore_ascii_case("true");
+        }
         if let Some(v) = envs.get(AWS_ACCESS_KEY_ID) {
             self.access_key_id = Some(v.to_string())
         }
@@ -143,12 +351,63 @@ impl Config {
         if let Some(v) = envs.get(AWS_WEB_IDENTITY_TOKEN_FILE) {
             self.web_identity_token_file = Some(v.to_string());
         }
+        if let Some(v) = envs.get(AWS_CONTAINER_CREDENTIALS_RELATIVE_URI) {
+            self.container_credentials_relative_uri = Some(v.to_string());
+        }
+        if let Some(v) = envs.get(AWS_CONTAINER_CREDENTIALS_FULL_URI) {
+            self.container_credentials_full_uri = Some(v.to_string());
+        }
+        if let Some(v) = envs.get(AWS_CONTAINER_AUTHORIZATION_TOKEN) {
+            self.container_authorization_token = Some(v.to_string());
+        }
+        if let Some(v) = envs.get(AWS_CONTAINER_AUTHORIZATION_TOKEN_FILE) {
+            self.container_authorization_token_file = Some(v.to_string());
+        }
+        if let Some(v) = envs.get(AWS_EC2_METADATA_SERVICE_ENDPOINT) {
+            self.ec2_metadata_service_endpoint = v.trim_end_matches('/').to_string();
+        }
 
         self
     }
 
+    /// Build the config of source profile to load credentials from.
+    ///
+    /// Only files and sts related settings will be inherited, all other
+    /// fields are loaded from the source profile.
+    pub(crate) fn source_profile_config(&self, profile: &str) -> Self {
+        Config {
+            config_file: self.config_file.clone(),
+            shared_credentials_file: self.shared_credentials_file.clone(),
+            profile: Some(profile.to_string()),
+            region: self.region.clone(),
+            sts_regional_endpoints: self.sts_regional_endpoints.clone(),
+            sts_endpoint: self.sts_endpoint.clone(),
+            use_fips_endpoint: self.use_fips_endpoint,
+            use_dualstack_endpoint: self.use_dualstack_endpoint,
+            max_retries: self.max_retries,
+            retry_base_delay: self.retry_base_delay,
+            credential_process_timeout: self.credential_process_timeout,
+            ..Default::default()
+        }
+        .from_profile()
+    }
+
+    /// The name of profile in use, default to `default`.
+    pub fn profile_name(&self) -> &str {
+        self.profile.as_deref().unwrap_or("default")
+    }
+
     /// Load config from profile (and shared profile).
+    ///
+    /// The profile will be resolved from env if `profile` is not set, so
+    /// that it doesn't matter whether `from_env` is called before or after.
     pub fn from_profile(mut self) -> Self {
+        if self.profile.is_none() {
+            self.profile = env::var(AWS_PROFILE)
+                .or_else(|_| env::var(AWS_DEFAULT_PROFILE))
+                .ok();
+        }
+
         // Ignore all errors happened internally.
         let _ = self.load_via_profile_config_file().map_err(|err| {
             debug!("load_via_profile_config_file failed: {err:?}");
@@ -174,8 +433,8 @@ impl Config {
         let conf = Ini::load_from_file(path)?;
 
         let props = conf
-            .section(Some(&self.profile))
-            .ok_or_else(|| anyhow!("section {} is not found", self.profile))?;
+            .section(Some(self.profile_name()))
+            .ok_or_else(|| anyhow!("section {} is not found", self.profile_name()))?;
 
         if let Some(v) = props.get("aws_access_key_id") {
             self.access_key_id = Some(v.to_string())
@@ -198,16 +457,29 @@ impl Config {
 
         let conf = Ini::load_from_file(path)?;
 
+        // Profiles other than `default` are named as `[profile <name>]` in
+        // config file.
+        let section = format!("profile {}", self.profile_name());
         let props = conf
-            .section(Some(&self.profile))
-            .ok_or_else(|| anyhow!("section {} is not found", self.profile))?;
+            .section(Some(section.as_str()))
+            .or_else(|| conf.section(Some(self.profile_name())))
+            .ok_or_else(|| anyhow!("section {} is not found", self.profile_name()))?;
 
-        if let Some(v) = props.get("region") {
-            self.region = Some(v.to_string())
+        // Region from env or set by users takes precedence.
+        if self.region.is_none() {
+            if let Some(v) = props.get("region") {
+                self.region = Some(v.to_string())
+            }
         }
         if let Some(v) = props.get("sts_regional_endpoints") {
             self.sts_regional_endpoints = v.to_string();
         }
+        if let Some(v) = props.get("use_fips_endpoint") {
+            self.use_fips_endpoint = v.eq_ignore_ascii_case("true");
+        }
+        if let Some(v) = props.get("use_dualstack_endpoint") {
+            self.use_dualstack_endpoint = v.eq_ignore_ascii_case("true");
+        }
         if let Some(v) = props.get("aws_access_key_id") {
             self.access_key_id = Some(v.to_string())
         }
@@ -223,10 +495,199 @@ impl Config {
         if let Some(v) = props.get("role_session_name") {
             self.role_session_name = v.to_string()
         }
+        if let Some(v) = props.get("external_id") {
+            self.external_id = Some(v.to_string())
+        }
+        if self.duration_seconds.is_none() {
+            if let Some(v) = props.get("duration_seconds") {
+                self.duration_seconds = v.parse().ok();
+            }
+        }
+        if let Some(v) = props.get("mfa_serial") {
+            self.mfa_serial = Some(v.to_string())
+        }
+        if let Some(v) = props.get("source_profile") {
+            self.source_profile = Some(v.to_string())
+        }
+        if let Some(v) = props.get("credential_source") {
+            self.credential_source = Some(v.to_string())
+        }
         if let Some(v) = props.get("web_identity_token_file") {
             self.web_identity_token_file = Some(v.to_string())
         }
+        if let Some(v) = props.get("sso_session") {
+            self.sso_session = Some(v.to_string())
+        }
+        if let Some(v) = props.get("sso_start_url") {
+            self.sso_start_url = Some(v.to_string())
+        }
+        if let Some(v) = props.get("sso_region") {
+            self.sso_region = Some(v.to_string())
+        }
+        if let Some(v) = props.get("sso_account_id") {
+            self.sso_account_id = Some(v.to_string())
+        }
+        if let Some(v) = props.get("sso_role_name") {
+            self.sso_role_name = Some(v.to_string())
+        }
+        if let Some(v) = props.get("credential_process") {
+            self.credential_process = Some(v.to_string())
+        }
+
+        // Load shared sso settings from `[sso-session <name>]` section.
+        if let Some(session) = &self.sso_session {
+            let section = format!("sso-session {session}");
+            let props = conf
+                .section(Some(section.as_str()))
+                .ok_or_else(|| anyhow!("section {section} is not found"))?;
+
+            if let Some(v) = props.get("sso_start_url") {
+                self.sso_start_url = Some(v.to_string())
+            }
+            if let Some(v) = props.get("sso_region") {
+                self.sso_region = Some(v.to_string())
+            }
+        }
 
         Ok(())
     }
+
+    /// Check that `access_key_id` and `secret_access_key` are set together.
+    ///
+    /// It's called while loading credentials, users can call it eagerly to
+    /// find misconfigurations on startup.
+    pub fn validate(&self) -> crate::Result<()> {
+        required_together(&[
+            ("access_key_id", self.access_key_id.is_some()),
+            ("secret_access_key", self.secret_access_key.is_some()),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_profile_precedence() {
+        let dir = env::temp_dir().join("reqsign_test_profile_precedence");
+        fs::create_dir_all(&dir).expect("create dir must succeed");
+        let config_file = dir.join("config");
+        fs::write(
+            &config_file,
+            "[default]\nregion = default\n\n[profile a]\nregion = a\n\n[profile b]\nregion = b\n",
+        )
+        .expect("write config must succeed");
+
+        let region = |profile: Option<&str>, aws_profile, aws_default_profile| {
+            temp_env::with_vars(
+                vec![
+                    (AWS_CONFIG_FILE, None),
+                    (AWS_SHARED_CREDENTIALS_FILE, None),
+                    (AWS_REGION, None),
+                    (AWS_PROFILE, aws_profile),
+                    (AWS_DEFAULT_PROFILE, aws_default_profile),
+                ],
+                || {
+                    let config = Config {
+                        config_file: config_file.to_string_lossy().to_string(),
+                        shared_credentials_file: "/not/exist".to_string(),
+                        profile: profile.map(|v| v.to_string()),
+                        ..Default::default()
+                    };
+                    // The order of from_env and from_profile doesn't matter.
+                    let x = config.clone().from_env().from_profile();
+                    let y = config.from_profile().from_env();
+                    assert_eq!(x.profile, y.profile);
+                    assert_eq!(x.region, y.region);
+                    (x.profile_name().to_string(), x.region.unwrap_or_default())
+                },
+            )
+        };
+
+        assert_eq!(
+            region(None, None, None),
+            ("default".into(), "default".into())
+        );
+        assert_eq!(region(None, None, Some("b")), ("b".into(), "b".into()));
+        assert_eq!(region(None, Some("a"), None), ("a".into(), "a".into()));
+        assert_eq!(region(None, Some("a"), Some("b")), ("a".into(), "a".into()));
+        assert_eq!(region(Some("b"), Some("a"), None), ("b".into(), "b".into()));
+
+        fs::remove_dir_all(&dir).expect("remove dir must succeed");
+    }
+
+    #[test]
+    fn test_region_precedence() {
+        let dir = env::temp_dir().join("reqsign_test_region_precedence");
+        fs::create_dir_all(&dir).expect("create dir must succeed");
+        let config_file = dir.join("config");
+        fs::write(&config_file, "[default]\nregion = us-west-2\n")
+            .expect("write config must succeed");
+
+        let region = |aws_region, aws_default_region| {
+            temp_env::with_vars(
+                vec![
+                    (AWS_PROFILE, None),
+                    (AWS_DEFAULT_PROFILE, None),
+                    (AWS_REGION, aws_region),
+                    (AWS_DEFAULT_REGION, aws_default_region),
+                ],
+                || {
+                    let config = Config {
+                        config_file: config_file.to_string_lossy().to_string(),
+                        shared_credentials_file: "/not/exist".to_string(),
+                        ..Default::default()
+                    };
+                    let x = config.clone().from_env().from_profile();
+                    let y = config.from_profile().from_env();
+                    assert_eq!(x.region, y.region);
+                    x.region
+                },
+            )
+        };
+
+        // Profile only region should be picked up.
+        assert_eq!(region(None, None).as_deref(), Some("us-west-2"));
+        assert_eq!(
+            region(None, Some("eu-west-1")).as_deref(),
+            Some("eu-west-1")
+        );
+        assert_eq!(
+            region(Some("ap-east-1"), Some("eu-west-1")).as_deref(),
+            Some("ap-east-1")
+        );
+
+        fs::remove_dir_all(&dir).expect("remove dir must succeed");
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = |ak: Option<&str>, sk: Option<&str>| Config {
+            access_key_id: ak.map(|v| v.to_string()),
+            secret_access_key: sk.map(|v| v.to_string()),
+            ..Default::default()
+        };
+
+        assert!(config(Some("ak"), Some("sk")).validate().is_ok());
+        assert!(config(None, None).validate().is_ok());
+
+        for (invalid, msg) in [
+            (
+                config(Some("ak"), None),
+                "secret_access_key is required when access_key_id is set",
+            ),
+            (
+                config(None, Some("sk")),
+                "access_key_id is required when secret_access_key is set",
+            ),
+        ] {
+            let err = invalid.validate().expect_err("config must be invalid");
+            assert!(matches!(err, crate::Error::InvalidConfig(_)), "{err:?}");
+            assert_eq!(err.to_string(), format!("invalid config: {msg}"));
+        }
+    }
 }
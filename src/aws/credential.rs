@@ -1,24 +1,46 @@
 use std::fmt::Debug;
-use std::fmt::Write;
 use std::fs;
+use std::io::Read;
+use std::net::IpAddr;
+use std::net::Ipv6Addr;
+use std::process::Command;
+use std::process::Stdio;
 use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
 
 use anyhow::anyhow;
 use anyhow::Result;
 use async_trait::async_trait;
+use chrono::TimeZone;
+use chrono::Utc;
+use http::header::AUTHORIZATION;
 use http::header::CONTENT_LENGTH;
 use log::debug;
 use quick_xml::de;
-use reqwest::Client;
 use serde::Deserialize;
 
 use super::config::Config;
+use super::constants::AWS_CONTAINER_CREDENTIALS_ENDPOINT;
+use super::constants::EC2_METADATA_TOKEN_TTL;
+use super::constants::SSO_CACHE_DIR;
+use super::v4::Signer;
+use crate::dirs::expand_homedir;
+use crate::hash::hex_sha1;
+use crate::http_send::check_available;
+use crate::http_send::is_unavailable;
+use crate::http_send::HttpSend;
+use crate::request::copy_request;
 use crate::time::now;
 use crate::time::parse_rfc3339;
 use crate::time::DateTime;
+use crate::trace::credential_refreshed;
+use crate::trace::record_cache_hit;
+use crate::Error;
 
 /// Credential that holds the access_key and secret_key.
 #[derive(Default, Clone)]
@@ -35,6 +57,42 @@ pub struct Credential {
 }
 
 impl Credential {
+    /// Create an anonymous credential.
+    ///
+    /// Signers will leave requests untouched while signing with anonymous
+    /// credential, so that public resources like S3 public buckets can
+    /// be accessed in the same code path as private ones:
+    ///
+    /// ```no_run
+    /// use reqsign::AwsConfig;
+    /// use reqsign::AwsCredential;
+    /// use reqsign::AwsLoader;
+    /// use reqsign::AwsV4Signer;
+    /// use reqwest::Client;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> anyhow::Result<()> {
+    /// let config = AwsConfig::default().from_profile().from_env();
+    /// let loader = AwsLoader::new(Client::new(), config).with_allow_anonymous();
+    /// let cred = loader.load().await?.unwrap_or_else(AwsCredential::anonymous);
+    ///
+    /// let mut req = http::Request::get("https://bucket.s3.amazonaws.com/key").body(())?;
+    /// AwsV4Signer::new("s3", "us-east-1").sign(&mut req, &cred)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn anonymous() -> Self {
+        Self::default()
+    }
+
+    /// Whether this credential is anonymous, that is, without access key
+    /// and secret key.
+    pub fn is_anonymous(&self) -> bool {
+        self.access_key_id.is_empty()
+            && self.secret_access_key.is_empty()
+            && self.session_token.is_none()
+    }
+
     /// is current cred is valid?
     pub fn is_valid(&self) -> bool {
         if (self.access_key_id.is_empty() || self.secret_access_key.is_empty())
@@ -52,6 +110,44 @@ impl Credential {
 
         true
     }
+
+    /// The time when this credential will be expired.
+    ///
+    /// Returns `None` for long-lived credentials that never expire.
+    pub fn expires_at(&self) -> Option<DateTime> {
+        self.expires_in
+    }
+
+    /// Whether this credential has been expired.
+    pub fn is_expired(&self) -> bool {
+        self.expires_in.is_some_and(|v| v <= now())
+    }
+
+    /// Whether this credential will be expired within `duration`.
+    ///
+    /// Long-lived credentials that never expire always return `false`.
+    pub fn is_expired_within(&self, duration: std::time::Duration) -> bool {
+        let expires_in = match self.expires_in {
+            Some(v) => v,
+            None => return false,
+        };
+        match chrono::Duration::from_std(duration)
+            .ok()
+            .and_then(|d| now().checked_add_signed(d))
+        {
+            Some(deadline) => expires_in <= deadline,
+            // Duration is too large to represent, the credential must be
+            // expired within it.
+            None => true,
+        }
+    }
+}
+
+#[cfg(feature = "refresher")]
+impl crate::CredentialExpiry for Credential {
+    fn expires_at(&self) -> Option<DateTime> {
+        self.expires_in
+    }
 }
 
 /// Loader trait will try to load credential from different sources.
@@ -62,36 +158,49 @@ pub trait CredentialLoad: 'static + Send + Sync + Debug {
     /// - If succeed, return `Ok(Some(cred))`
     /// - If not found, return `Ok(None)`
     /// - If unexpected errors happened, return `Err(err)`
-    async fn load_credential(&self, client: Client) -> Result<Option<Credential>>;
+    async fn load_credential(&self, client: Arc<dyn HttpSend>) -> Result<Option<Credential>>;
 }
 
 /// CredentialLoader will load credential from different methods.
 #[cfg_attr(test, derive(Debug))]
 pub struct Loader {
-    client: Client,
+    client: Arc<dyn HttpSend>,
     config: Config,
 
     allow_anonymous: bool,
     disable_ec2_metadata: bool,
+    allow_ec2_metadata_v1: bool,
     customed_credential_loader: Option<Box<dyn CredentialLoad>>,
+    mfa_token_provider: Option<MfaTokenProvider>,
 
     loaded: AtomicBool,
     credential: Arc<Mutex<Option<Credential>>>,
+    /// Incremented every time the cached credential is replaced.
+    generation: AtomicU64,
+    refresh_lock: Arc<tokio::sync::Mutex<()>>,
+    ec2_metadata_token: Arc<Mutex<Option<(String, DateTime)>>>,
+    ec2_metadata_region: Arc<Mutex<Option<String>>>,
 }
 
 impl Loader {
     /// Create a new CredentialLoader
-    pub fn new(client: Client, config: Config) -> Self {
+    pub fn new(client: impl HttpSend, config: Config) -> Self {
         Self {
-            client,
+            client: Arc::new(client),
             config,
 
             allow_anonymous: false,
             disable_ec2_metadata: false,
+            allow_ec2_metadata_v1: false,
             customed_credential_loader: None,
+            mfa_token_provider: None,
 
             loaded: AtomicBool::new(false),
             credential: Arc::default(),
+            generation: AtomicU64::new(0),
+            refresh_lock: Arc::default(),
+            ec2_metadata_token: Arc::default(),
+            ec2_metadata_region: Arc::default(),
         }
     }
 
@@ -110,6 +219,16 @@ impl Loader {
         self
     }
 
+    /// Allow falling back to IMDSv1 while loading from ec2 metadata.
+    ///
+    /// By default, we will only use IMDSv2 which requires a session token.
+    /// By enabling this option, requests will be sent without token if
+    /// fetching token failed.
+    pub fn with_allow_ec2_metadata_v1(mut self) -> Self {
+        self.allow_ec2_metadata_v1 = true;
+        self
+    }
+
     /// Set customed credential loader.
     ///
     /// This loader will be used first.
@@ -118,34 +237,180 @@ impl Loader {
         self
     }
 
+    /// Set the provider of MFA token code.
+    ///
+    /// The provider will be called to get the current TOTP code while
+    /// assuming role with `mfa_serial`. Assumed credentials will be cached
+    /// until expired, so users will not be asked for every request.
+    ///
+    /// # Note
+    ///
+    /// The provider is allowed to block, for example, CLI tools can read the
+    /// code from stdin. It's called inside the async `load`, so it will block
+    /// the runtime worker thread until returned.
+    pub fn with_mfa_token_provider(
+        mut self,
+        f: impl FnMut() -> Result<String> + Send + 'static,
+    ) -> Self {
+        self.mfa_token_provider = Some(MfaTokenProvider(Mutex::new(Box::new(f))));
+        self
+    }
+
     /// Load credential.
     ///
     /// Resolution order:
-    /// 1. Environment variables
-    /// 2. Shared config (`~/.aws/config`, `~/.aws/credentials`)
-    /// 3. Web Identity Tokens
-    /// 4. ECS (IAM Roles for Tasks) & General HTTP credentials:
-    /// 5. EC2 IMDSv2
-    pub async fn load(&self) -> Result<Option<Credential>> {
-        // Return cached credential if it has been loaded at least once.
-        if self.loaded.load(Ordering::Relaxed) {
-            match self.credential.lock().expect("lock poisoned").clone() {
-                Some(cred) if cred.is_valid() => return Ok(Some(cred)),
-                None if self.allow_anonymous => return Ok(None),
-                _ => (),
-            }
+    /// 1. Static keys from environment variables or config
+    /// 2. Shared config (`~/.aws/config`, `~/.aws/credentials`), roles with
+    ///    `source_profile` or `credential_source` will be assumed. Profiles
+    ///    whose `source_profile` is themselves assume the role with their
+    ///    static keys instead of returning them directly.
+    /// 3. External process in `credential_process`
+    /// 4. SSO credentials cached by `aws sso login`
+    /// 5. Web Identity Tokens
+    /// 6. ECS (IAM Roles for Tasks) & General HTTP credentials:
+    /// 7. EC2 IMDSv2
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "reqsign::load",
+            level = "debug",
+            skip_all,
+            fields(provider = "aws", cache_hit = tracing::field::Empty)
+        )
+    )]
+    pub async fn load(&self) -> crate::Result<Option<Credential>> {
+        if let Some(cred) = self.cached() {
+            record_cache_hit(true);
+            return Ok(cred);
+        }
+
+        // Only one task loads credential at the same time, others will wait
+        // and reuse its result.
+        let _guard = self.refresh_lock.lock().await;
+        if let Some(cred) = self.cached() {
+            record_cache_hit(true);
+            return Ok(cred);
+        }
+        record_cache_hit(false);
+
+        self.refresh().await
+    }
+
+    /// Return cached credential if it has been loaded at least once.
+    fn cached(&self) -> Option<Option<Credential>> {
+        if !self.loaded.load(Ordering::Relaxed) {
+            return None;
         }
+        match self.credential.lock().expect("lock poisoned").clone() {
+            Some(cred) if cred.is_valid() => Some(Some(cred)),
+            None if self.allow_anonymous => Some(None),
+            _ => None,
+        }
+    }
+
+    /// Load credential from sources, ignoring the cached one.
+    ///
+    /// The cache will be replaced by the newly loaded credential. It's
+    /// useful when the cached credential has been rotated or revoked, for
+    /// example, requests failed with `403 Forbidden`.
+    ///
+    /// Concurrent calls will be merged: callers waiting for an in-flight
+    /// refresh will get its result instead of loading again.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "reqsign::load",
+            level = "debug",
+            skip_all,
+            fields(provider = "aws", cache_hit = false)
+        )
+    )]
+    pub async fn force_refresh(&self) -> crate::Result<Option<Credential>> {
+        let generation = self.generation.load(Ordering::Acquire);
+        let _guard = self.refresh_lock.lock().await;
+
+        // Credential has been refreshed while we are waiting.
+        if self.generation.load(Ordering::Acquire) != generation {
+            return Ok(self.credential.lock().expect("lock poisoned").clone());
+        }
+
+        self.refresh().await
+    }
 
+    async fn refresh(&self) -> crate::Result<Option<Credential>> {
         let cred = self.load_inner().await?;
+        if let Some(cred) = &cred {
+            credential_refreshed("aws", cred.expires_in);
+        }
 
         let mut lock = self.credential.lock().expect("lock poisoned");
         *lock = cred.clone();
+        self.generation.fetch_add(1, Ordering::Release);
         // Set loaded after we have updated the credential.
         self.loaded.store(true, Ordering::Relaxed);
 
         Ok(cred)
     }
 
+    /// Resolve the region of current environment.
+    ///
+    /// Resolution order:
+    /// 1. `region` in config, which is loaded from `AWS_REGION`,
+    ///    `AWS_DEFAULT_REGION` and the `region` of active profile
+    /// 2. EC2 instance metadata `placement/region` unless ec2 metadata is
+    ///    disabled
+    ///
+    /// Returns `Ok(None)` if region can't be resolved. Pass the region to
+    /// signer so that the credential scope matches it:
+    ///
+    /// ```no_run
+    /// use reqsign::AwsConfig;
+    /// use reqsign::AwsLoader;
+    /// use reqsign::AwsV4Signer;
+    /// use reqwest::Client;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> anyhow::Result<()> {
+    /// let config = AwsConfig::default().from_profile().from_env();
+    /// let loader = AwsLoader::new(Client::new(), config);
+    /// let region = loader.region().await?.unwrap_or("us-east-1".to_string());
+    ///
+    /// let mut req = http::Request::get("https://s3.amazonaws.com/bucket").body(())?;
+    /// let cred = loader.load().await?.expect("credential must be loaded");
+    /// AwsV4Signer::new("s3", &region).sign(&mut req, &cred)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn region(&self) -> crate::Result<Option<String>> {
+        if let Some(region) = &self.config.region {
+            return Ok(Some(region.clone()));
+        }
+        if self.disable_ec2_metadata {
+            return Ok(None);
+        }
+        if let Some(region) = self
+            .ec2_metadata_region
+            .lock()
+            .expect("lock poisoned")
+            .clone()
+        {
+            return Ok(Some(region));
+        }
+
+        let region = match self
+            .ec2_metadata_get("/latest/meta-data/placement/region")
+            .await
+        {
+            Ok(region) => region.trim().to_string(),
+            Err(err) => {
+                debug!("load region via ec2 metadata failed: {err:?}");
+                return Ok(None);
+            }
+        };
+        *self.ec2_metadata_region.lock().expect("lock poisoned") = Some(region.clone());
+        Ok(Some(region))
+    }
+
     async fn load_inner(&self) -> Result<Option<Credential>> {
         if let Ok(Some(cred)) = self
             .load_via_customed_credential_load()
@@ -155,9 +420,54 @@ impl Loader {
             return Ok(Some(cred));
         }
 
+        // Errors of other sources are skipped, but misconfigurations should
+        // be reported instead of falling back to them silently.
+        self.config.validate()?;
+
+        // Static keys from env take precedence over roles in profile like
+        // AWS SDKs, except that a profile sourcing itself uses its static
+        // keys to assume the role.
+        let sources_itself = self.config.source_profile.as_deref()
+            == Some(self.config.profile_name())
+            && self.config.role_arn.is_some();
+        if !sources_itself {
+            if let Ok(Some(cred)) = self
+                .load_via_config()
+                .map_err(|err| debug!("load credential via config failed: {err:?}"))
+            {
+                return Ok(Some(cred));
+            }
+        }
+
+        if let Ok(Some(cred)) = self
+            .load_via_source_profile()
+            .await
+            .map_err(|err| debug!("load credential via source_profile failed: {err:?}"))
+        {
+            return Ok(Some(cred));
+        }
+
+        if sources_itself {
+            if let Ok(Some(cred)) = self
+                .load_via_config()
+                .map_err(|err| debug!("load credential via config failed: {err:?}"))
+            {
+                return Ok(Some(cred));
+            }
+        }
+
+        if let Ok(Some(cred)) = self
+            .load_via_credential_process()
+            .await
+            .map_err(|err| debug!("load credential via credential_process failed: {err:?}"))
+        {
+            return Ok(Some(cred));
+        }
+
         if let Ok(Some(cred)) = self
-            .load_via_config()
-            .map_err(|err| debug!("load credential via config failed: {err:?}"))
+            .load_via_sso()
+            .await
+            .map_err(|err| debug!("load credential via sso failed: {err:?}"))
         {
             return Ok(Some(cred));
         }
@@ -180,6 +490,14 @@ impl Loader {
             return Ok(Some(cred));
         }
 
+        if let Ok(Some(cred)) = self
+            .load_via_container()
+            .await
+            .map_err(|err| debug!("load credential via container failed: {err:?}"))
+        {
+            return Ok(Some(cred));
+        }
+
         if let Ok(Some(cred)) = self
             .load_via_imds_v2()
             .await
@@ -214,41 +532,103 @@ impl Loader {
         }
     }
 
-    async fn load_via_imds_v2(&self) -> Result<Option<Credential>> {
-        if self.disable_ec2_metadata {
-            return Ok(None);
+    /// Load credential via the external process in `credential_process`.
+    ///
+    /// Reference: [Sourcing credentials with an external process](https://docs.aws.amazon.com/cli/latest/userguide/cli-configure-sourcing-external.html)
+    async fn load_via_credential_process(&self) -> Result<Option<Credential>> {
+        match &self.config.credential_process {
+            Some(command) => Ok(Some(
+                run_credential_process(command, self.config.credential_process_timeout).await?,
+            )),
+            None => Ok(None),
         }
+    }
 
-        // Get ec2 metadata token
-        let url = "http://169.254.169.254/latest/api/token";
-        let req = self
-            .client
-            .put(url)
-            .header(CONTENT_LENGTH, "0")
-            .header("x-aws-ec2-metadata-token-ttl-seconds", "60");
-        let resp = req.send().await?;
-        if resp.status() != http::StatusCode::OK {
-            let content = resp.text().await?;
-            return Err(anyhow!(
-                "request to AWS EC2 Metadata Services failed: {content}"
-            ));
+    /// Send request to credential endpoints with retry.
+    ///
+    /// Transient failures like network errors, `429` and `5xx` responses
+    /// will be retried with exponential backoff, and returned as
+    /// [`Error::MetadataUnavailable`] if all retries failed. Other responses
+    /// will be returned directly for callers to check.
+    async fn send(&self, req: http::Request<Vec<u8>>) -> Result<http::Response<Vec<u8>>> {
+        let mut retries = 0;
+        loop {
+            let result = self
+                .client
+                .send(copy_request(&req, req.body().clone()))
+                .await;
+            let retryable = match &result {
+                Ok(resp) => is_unavailable(resp.status()),
+                Err(_) => true,
+            };
+            if !retryable || retries >= self.config.max_retries {
+                return check_available(result);
+            }
+
+            let delay = self.config.retry_base_delay * 2u32.saturating_pow(retries as u32);
+            debug!(
+                "request to {} failed, retry after {delay:?}: {:?}",
+                req.uri(),
+                result.map(|resp| resp.status())
+            );
+            tokio::time::sleep(delay).await;
+            retries += 1;
         }
-        let ec2_token = resp.text().await?;
+    }
 
-        // List all credentials that node has.
-        let url = "http://169.254.169.254/latest/meta-data/iam/security-credentials/";
-        let req = self
-            .client
-            .get(url)
-            .header("x-aws-ec2-metadata-token", &ec2_token);
-        let resp = req.send().await?;
+    async fn load_via_container(&self) -> Result<Option<Credential>> {
+        let url = match (
+            &self.config.container_credentials_relative_uri,
+            &self.config.container_credentials_full_uri,
+        ) {
+            (Some(relative_uri), _) => {
+                format!("{AWS_CONTAINER_CREDENTIALS_ENDPOINT}{relative_uri}")
+            }
+            (None, Some(full_uri)) => {
+                check_container_credentials_full_uri(full_uri)?;
+                full_uri.to_string()
+            }
+            (None, None) => return Ok(None),
+        };
+
+        let mut req = http::Request::get(&url);
+        // Token file could be rotated, we need to read it every time.
+        if let Some(token_file) = &self.config.container_authorization_token_file {
+            let token = fs::read_to_string(token_file)?;
+            req = req.header(AUTHORIZATION, token.trim());
+        } else if let Some(token) = &self.config.container_authorization_token {
+            req = req.header(AUTHORIZATION, token);
+        }
+
+        let resp = self.send(req.body(Vec::new())?).await?;
         if resp.status() != http::StatusCode::OK {
-            let content = resp.text().await?;
+            let content = String::from_utf8_lossy(resp.body());
             return Err(anyhow!(
-                "request to AWS EC2 Metadata Services failed: {content}"
+                "request to AWS container credentials endpoint failed: {content}"
             ));
         }
-        let content = resp.text().await?;
+
+        let resp: ContainerCredentials = serde_json::from_slice(resp.body())?;
+
+        let cred = Credential {
+            access_key_id: resp.access_key_id,
+            secret_access_key: resp.secret_access_key,
+            session_token: Some(resp.token),
+            expires_in: Some(parse_rfc3339(&resp.expiration)?),
+        };
+
+        Ok(Some(cred))
+    }
+
+    async fn load_via_imds_v2(&self) -> Result<Option<Credential>> {
+        if self.disable_ec2_metadata {
+            return Ok(None);
+        }
+
+        // List all credentials that node has.
+        let content = self
+            .ec2_metadata_get("/latest/meta-data/iam/security-credentials/")
+            .await?;
         let credential_list: Vec<_> = content.split('\n').collect();
         // credential list is empty, return None directly.
         if credential_list.is_empty() {
@@ -257,21 +637,11 @@ impl Loader {
         let role_name = credential_list[0];
 
         // Get the credentials via role_name.
-        let url =
-            format!("http://169.254.169.254/latest/meta-data/iam/security-credentials/{role_name}");
-        let req = self
-            .client
-            .get(&url)
-            .header("x-aws-ec2-metadata-token", &ec2_token);
-        let resp = req.send().await?;
-        if resp.status() != http::StatusCode::OK {
-            let content = resp.text().await?;
-            return Err(anyhow!(
-                "request to AWS EC2 Metadata Services failed: {content}"
-            ));
-        }
-
-        let content = resp.text().await?;
+        let content = self
+            .ec2_metadata_get(&format!(
+                "/latest/meta-data/iam/security-credentials/{role_name}"
+            ))
+            .await?;
         let resp: Ec2MetadataIamSecurityCredentials = serde_json::from_str(&content)?;
         if resp.code != "Success" {
             return Err(anyhow!(
@@ -289,32 +659,350 @@ impl Loader {
         Ok(Some(cred))
     }
 
+    /// Send a GET request to ec2 metadata service with session token.
+    ///
+    /// Session token will be re-fetched once if it's rejected by the service.
+    async fn ec2_metadata_get(&self, path: &str) -> Result<String> {
+        let url = format!("{}{path}", self.config.ec2_metadata_service_endpoint);
+
+        let mut retried = false;
+        loop {
+            let token = self.ec2_metadata_token(retried).await?;
+
+            let mut req = http::Request::get(&url);
+            if let Some(token) = &token {
+                req = req.header("x-aws-ec2-metadata-token", token);
+            }
+            let resp = self.send(req.body(Vec::new())?).await?;
+
+            if resp.status() == http::StatusCode::UNAUTHORIZED && token.is_some() && !retried {
+                debug!("ec2 metadata token has been rejected, re-fetching");
+                retried = true;
+                continue;
+            }
+            if resp.status() != http::StatusCode::OK {
+                let content = String::from_utf8_lossy(resp.body());
+                return Err(anyhow!(
+                    "request to AWS EC2 Metadata Services failed: {content}"
+                ));
+            }
+
+            return Ok(String::from_utf8(resp.into_body())?);
+        }
+    }
+
+    /// Get ec2 metadata session token.
+    ///
+    /// - Cached token will be used until it's expired unless `force` is true.
+    /// - Returns `Ok(None)` if token can't be fetched and IMDSv1 is allowed.
+    async fn ec2_metadata_token(&self, force: bool) -> Result<Option<String>> {
+        if !force {
+            if let Some((token, expires_in)) = self
+                .ec2_metadata_token
+                .lock()
+                .expect("lock poisoned")
+                .clone()
+            {
+                if expires_in > now() {
+                    return Ok(Some(token));
+                }
+            }
+        }
+
+        match self.fetch_ec2_metadata_token().await {
+            Ok((token, expires_in)) => {
+                *self.ec2_metadata_token.lock().expect("lock poisoned") =
+                    Some((token.clone(), expires_in));
+                Ok(Some(token))
+            }
+            Err(err) if self.allow_ec2_metadata_v1 => {
+                debug!("fetch ec2 metadata token failed, fallback to IMDSv1: {err:?}");
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn fetch_ec2_metadata_token(&self) -> Result<(String, DateTime)> {
+        let url = format!(
+            "{}/latest/api/token",
+            self.config.ec2_metadata_service_endpoint
+        );
+        let req = http::Request::put(url)
+            .header(CONTENT_LENGTH, "0")
+            .header(
+                "x-aws-ec2-metadata-token-ttl-seconds",
+                EC2_METADATA_TOKEN_TTL.as_secs().to_string(),
+            )
+            .body(Vec::new())?;
+        let resp = self.send(req).await?;
+        if resp.status() != http::StatusCode::OK {
+            let content = String::from_utf8_lossy(resp.body());
+            return Err(anyhow!(
+                "request to AWS EC2 Metadata Services failed: {content}"
+            ));
+        }
+        let token = String::from_utf8(resp.into_body())?;
+
+        // Refresh the token a bit earlier to avoid been rejected.
+        let expires_in = now() + chrono::Duration::from_std(EC2_METADATA_TOKEN_TTL)?
+            - chrono::Duration::minutes(1);
+        Ok((token, expires_in))
+    }
+
+    async fn load_via_sso(&self) -> Result<Option<Credential>> {
+        let (account_id, role_name) =
+            match (&self.config.sso_account_id, &self.config.sso_role_name) {
+                (Some(account_id), Some(role_name)) => (account_id, role_name),
+                _ => return Ok(None),
+            };
+        let region = self
+            .config
+            .sso_region
+            .as_ref()
+            .ok_or_else(|| anyhow!("sso_region is required for sso profile"))?;
+
+        // Token cache is keyed by session name, and by start url for
+        // legacy profiles without `sso_session`.
+        let cache_key = match (&self.config.sso_session, &self.config.sso_start_url) {
+            (Some(session), _) => session,
+            (None, Some(start_url)) => start_url,
+            (None, None) => return Err(anyhow!("sso_start_url is required for sso profile")),
+        };
+        let cache_dir =
+            expand_homedir(SSO_CACHE_DIR).ok_or_else(|| anyhow!("expand homedir failed"))?;
+        let token = load_sso_token(&cache_dir, cache_key)?;
+
+        let url = sso_role_credentials_url(
+            &regional_endpoint(
+                "portal.sso",
+                region,
+                self.config.use_fips_endpoint,
+                self.config.use_dualstack_endpoint,
+            ),
+            account_id,
+            role_name,
+        );
+        let req = http::Request::get(&url)
+            .header("x-amz-sso_bearer_token", &token.access_token)
+            .body(Vec::new())?;
+
+        let resp = self.send(req).await?;
+        if resp.status() != http::StatusCode::OK {
+            let content = String::from_utf8_lossy(resp.body());
+            return Err(anyhow!("request to AWS SSO Services failed: {content}"));
+        }
+
+        let resp: SsoGetRoleCredentialsResponse = serde_json::from_slice(resp.body())?;
+        let resp_cred = resp.role_credentials;
+
+        let cred = Credential {
+            access_key_id: resp_cred.access_key_id,
+            secret_access_key: resp_cred.secret_access_key,
+            session_token: Some(resp_cred.session_token),
+            expires_in: Utc.timestamp_millis_opt(resp_cred.expiration).single(),
+        };
+
+        Ok(Some(cred))
+    }
+
     async fn load_via_assume_role(&self) -> Result<Option<Credential>> {
         let role_arn = match &self.config.role_arn {
             Some(role_arn) => role_arn,
             None => return Ok(None),
         };
-        let role_session_name = &self.config.role_session_name;
 
+        let cred = self.assume_role(role_arn, &self.config, None).await?;
+
+        Ok(Some(cred))
+    }
+
+    /// Load credential by assuming role with credentials of source profile.
+    ///
+    /// Profiles will be resolved recursively until a profile that contains
+    /// static credentials or `credential_source` is found:
+    ///
+    /// ```ini
+    /// [profile prod]
+    /// role_arn = arn:aws:iam::123456789012:role/prod
+    /// source_profile = staging
+    ///
+    /// [profile staging]
+    /// role_arn = arn:aws:iam::123456789012:role/staging
+    /// source_profile = default
+    ///
+    /// [default]
+    /// aws_access_key_id = access_key_id
+    /// aws_secret_access_key = secret_access_key
+    /// ```
+    ///
+    /// Roles will be assumed in order of `staging` and `prod`.
+    async fn load_via_source_profile(&self) -> Result<Option<Credential>> {
+        if self.config.role_arn.is_none()
+            || (self.config.source_profile.is_none() && self.config.credential_source.is_none())
+        {
+            return Ok(None);
+        }
+
+        let mut visited = vec![self.config.profile_name().to_string()];
+        let mut roles: Vec<Config> = Vec::new();
+        let mut current = self.config.clone();
+
+        let mut cred = loop {
+            if current.role_arn.is_some() {
+                if let Some(source) = &current.credential_source {
+                    let cred = self.load_via_credential_source(source).await?;
+                    roles.push(current);
+                    break cred;
+                }
+
+                if let Some(source) = current.source_profile.clone() {
+                    // Profile that sources itself uses its static credentials.
+                    if source == current.profile_name() {
+                        let cred = static_credential(&current).ok_or_else(|| {
+                            Error::CredentialNotFound(format!(
+                                "profile {source} doesn't contain static credentials"
+                            ))
+                        })?;
+                        roles.push(current);
+                        break cred;
+                    }
+
+                    if visited.contains(&source) {
+                        visited.push(source);
+                        return Err(anyhow!(
+                            "source_profile cycle detected: {}",
+                            visited.join(" -> ")
+                        ));
+                    }
+                    visited.push(source.clone());
+
+                    let next = current.source_profile_config(&source);
+                    roles.push(current);
+                    current = next;
+                    continue;
+                }
+            }
+
+            if let Some(cred) = static_credential(&current) {
+                break cred;
+            }
+            if let Some(command) = &current.credential_process {
+                break run_credential_process(command, current.credential_process_timeout).await?;
+            }
+            return Err(Error::CredentialNotFound(format!(
+                "source profile {} doesn't contain credentials",
+                current.profile_name()
+            ))
+            .into());
+        };
+
+        for role in roles.iter().rev() {
+            let role_arn = role.role_arn.as_ref().expect("role_arn must be set");
+            debug!(
+                "assuming role {role_arn} of profile {}",
+                role.profile_name()
+            );
+
+            cred = self.assume_role(role_arn, role, Some(&cred)).await?;
+        }
+
+        Ok(Some(cred))
+    }
+
+    /// Load source credentials specified by `credential_source`.
+    async fn load_via_credential_source(&self, source: &str) -> Result<Credential> {
+        let cred = match source {
+            "Environment" => static_credential(&Config::default().from_env()),
+            "Ec2InstanceMetadata" => {
+                let mut loader = Loader::new(self.client.clone(), self.config.clone());
+                loader.allow_ec2_metadata_v1 = self.allow_ec2_metadata_v1;
+                loader.load_via_imds_v2().await?
+            }
+            "EcsContainer" => {
+                Loader::new(self.client.clone(), self.config.clone())
+                    .load_via_container()
+                    .await?
+            }
+            v => return Err(anyhow!("credential_source {v} is not supported")),
+        };
+
+        cred.ok_or_else(|| {
+            Error::CredentialNotFound(format!(
+                "no credential found from credential_source {source}"
+            ))
+            .into()
+        })
+    }
+
+    /// Assume role via AWS STS.
+    ///
+    /// Parameters like `ExternalId` and `DurationSeconds` are taken from the
+    /// config of role. The request will be signed if source credential is
+    /// provided.
+    async fn assume_role(
+        &self,
+        role_arn: &str,
+        role: &Config,
+        source: Option<&Credential>,
+    ) -> Result<Credential> {
         let endpoint = self.sts_endpoint()?;
+        let mfa_serial = role.mfa_serial.as_deref();
+        let token_code = match mfa_serial {
+            Some(serial) => {
+                let provider = self.mfa_token_provider.as_ref().ok_or_else(|| {
+                    anyhow!("mfa_serial {serial} is set but mfa token provider is not specified")
+                })?;
+                Some(provider.token_code()?)
+            }
+            None => None,
+        };
 
         // Construct request to AWS STS Service.
-        let mut url = format!("https://{endpoint}/?Action=AssumeRole&RoleArn={role_arn}&Version=2011-06-15&RoleSessionName={role_session_name}");
-        if let Some(external_id) = &self.config.external_id {
-            write!(url, "&ExternalId={external_id}")?;
+        let mut query = vec![
+            ("Action".to_string(), "AssumeRole"),
+            ("RoleArn".to_string(), role_arn),
+            ("Version".to_string(), "2011-06-15"),
+            ("RoleSessionName".to_string(), &role.role_session_name),
+        ];
+        if let Some(external_id) = &role.external_id {
+            query.push(("ExternalId".to_string(), external_id));
+        }
+        let duration_seconds = role.duration_seconds.map(|v| v.to_string());
+        if let Some(duration_seconds) = &duration_seconds {
+            query.push(("DurationSeconds".to_string(), duration_seconds));
+        }
+        if let Some(policy) = &role.policy {
+            query.push(("Policy".to_string(), policy));
+        }
+        for (i, arn) in role.policy_arns.iter().enumerate() {
+            query.push((format!("PolicyArns.member.{}.arn", i + 1), arn));
+        }
+        if let (Some(serial), Some(code)) = (mfa_serial, &token_code) {
+            query.push(("SerialNumber".to_string(), serial));
+            query.push(("TokenCode".to_string(), code));
+        }
+        let query = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(query)
+            .finish();
+        let mut req = http::Request::get(format!("{endpoint}/?{query}"))
+            .header(
+                http::header::CONTENT_TYPE.as_str(),
+                "application/x-www-form-urlencoded",
+            )
+            .body(Vec::new())?;
+        if let Some(cred) = source {
+            let region = self.config.region.as_deref().unwrap_or("us-east-1");
+            Signer::new("sts", region).sign(&mut req, cred)?;
         }
-        let req = self.client.get(&url).header(
-            http::header::CONTENT_TYPE.as_str(),
-            "application/x-www-form-urlencoded",
-        );
 
-        let resp = req.send().await?;
+        let resp = self.send(req).await?;
         if resp.status() != http::StatusCode::OK {
-            let content = resp.text().await?;
+            let content = String::from_utf8_lossy(resp.body());
             return Err(anyhow!("request to AWS STS Services failed: {content}"));
         }
 
-        let resp: AssumeRoleResponse = de::from_str(&resp.text().await?)?;
+        let resp: AssumeRoleResponse = de::from_reader(resp.body().as_slice())?;
         let resp_cred = resp.result.credentials;
 
         let cred = Credential {
@@ -324,7 +1012,7 @@ impl Loader {
             expires_in: Some(parse_rfc3339(&resp_cred.expiration)?),
         };
 
-        Ok(Some(cred))
+        Ok(cred)
     }
 
     async fn load_via_assume_role_with_web_identity(&self) -> Result<Option<Credential>> {
@@ -334,25 +1022,36 @@ impl Loader {
                 _ => return Ok(None),
             };
 
+        // Token file will be rotated, we need to read it every time.
         let token = fs::read_to_string(token_file)?;
         let role_session_name = &self.config.role_session_name;
 
         let endpoint = self.sts_endpoint()?;
 
         // Construct request to AWS STS Service.
-        let url = format!("https://{endpoint}/?Action=AssumeRoleWithWebIdentity&RoleArn={role_arn}&WebIdentityToken={token}&Version=2011-06-15&RoleSessionName={role_session_name}");
-        let req = self.client.get(&url).header(
-            http::header::CONTENT_TYPE.as_str(),
-            "application/x-www-form-urlencoded",
-        );
-
-        let resp = req.send().await?;
+        let query = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs([
+                ("Action", "AssumeRoleWithWebIdentity"),
+                ("RoleArn", role_arn),
+                ("WebIdentityToken", token.trim()),
+                ("Version", "2011-06-15"),
+                ("RoleSessionName", role_session_name),
+            ])
+            .finish();
+        let req = http::Request::get(format!("{endpoint}/?{query}"))
+            .header(
+                http::header::CONTENT_TYPE.as_str(),
+                "application/x-www-form-urlencoded",
+            )
+            .body(Vec::new())?;
+
+        let resp = self.send(req).await?;
         if resp.status() != http::StatusCode::OK {
-            let content = resp.text().await?;
+            let content = String::from_utf8_lossy(resp.body());
             return Err(anyhow!("request to AWS STS Services failed: {content}"));
         }
 
-        let resp: AssumeRoleWithWebIdentityResponse = de::from_str(&resp.text().await?)?;
+        let resp: AssumeRoleWithWebIdentityResponse = de::from_reader(resp.body().as_slice())?;
         let resp_cred = resp.result.credentials;
 
         let cred = Credential {
@@ -367,7 +1066,7 @@ impl Loader {
 
     /// Get the sts endpoint.
     ///
-    /// The returning format may look like `sts.{region}.amazonaws.com`
+    /// The returning format may look like `https://sts.{region}.amazonaws.com`
     ///
     /// # Notes
     ///
@@ -376,45 +1075,311 @@ impl Loader {
     ///
     /// ref: https://github.com/awslabs/aws-sdk-rust/blob/31cfae2cf23be0c68a47357070dea1aee9227e3a/sdk/sts/src/aws_endpoint.rs
     fn sts_endpoint(&self) -> Result<String> {
+        // use user specified endpoint first, like fips endpoints.
+        if let Some(endpoint) = &self.config.sts_endpoint {
+            return Ok(endpoint.trim_end_matches('/').to_string());
+        }
+
+        // fips and dualstack endpoints are always regional.
+        if self.config.use_fips_endpoint || self.config.use_dualstack_endpoint {
+            let region = self.config.region.as_deref().ok_or_else(|| {
+                anyhow!("use_fips_endpoint or use_dualstack_endpoint is set, but region is not set")
+            })?;
+            return Ok(regional_endpoint(
+                "sts",
+                region,
+                self.config.use_fips_endpoint,
+                self.config.use_dualstack_endpoint,
+            ));
+        }
+
         // use regional sts if sts_regional_endpoints has been set.
         if self.config.sts_regional_endpoints == "regional" {
             let region = self.config.region.clone().ok_or_else(|| {
                 anyhow!("sts_regional_endpoints set to reginal, but region is not set")
             })?;
             if region.starts_with("cn-") {
-                Ok(format!("sts.{region}.amazonaws.com.cn"))
+                Ok(format!("https://sts.{region}.amazonaws.com.cn"))
             } else {
-                Ok(format!("sts.{region}.amazonaws.com"))
+                Ok(format!("https://sts.{region}.amazonaws.com"))
             }
         } else {
             let region = self.config.region.clone().unwrap_or_default();
             if region.starts_with("cn") {
                 // TODO: seems aws china doesn't support global sts?
-                Ok("sts.amazonaws.com.cn".to_string())
+                Ok("https://sts.amazonaws.com.cn".to_string())
             } else {
-                Ok("sts.amazonaws.com".to_string())
+                Ok("https://sts.amazonaws.com".to_string())
             }
         }
     }
 }
 
-#[derive(Default, Debug, Deserialize)]
-#[serde(default, rename_all = "PascalCase")]
-struct AssumeRoleWithWebIdentityResponse {
-    #[serde(rename = "AssumeRoleWithWebIdentityResult")]
-    result: AssumeRoleWithWebIdentityResult,
+#[async_trait]
+impl crate::loader::CredentialLoad<Credential> for Loader {
+    async fn load(&self) -> crate::Result<Option<Credential>> {
+        Loader::load(self).await
+    }
 }
 
-#[derive(Default, Debug, Deserialize)]
-#[serde(default, rename_all = "PascalCase")]
-struct AssumeRoleWithWebIdentityResult {
-    credentials: AssumeRoleWithWebIdentityCredentials,
+/// Callback that provides MFA token code.
+struct MfaTokenProvider(Mutex<Box<dyn FnMut() -> Result<String> + Send>>);
+
+impl Debug for MfaTokenProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("MfaTokenProvider")
+    }
 }
 
-#[derive(Default, Debug, Deserialize)]
-#[serde(default, rename_all = "PascalCase")]
-struct AssumeRoleWithWebIdentityCredentials {
-    access_key_id: String,
+impl MfaTokenProvider {
+    fn token_code(&self) -> Result<String> {
+        let mut f = self.0.lock().expect("lock poisoned");
+        Ok((f)()?.trim().to_string())
+    }
+}
+
+/// Build static credential from access key id and secret access key in config.
+fn static_credential(config: &Config) -> Option<Credential> {
+    match (&config.access_key_id, &config.secret_access_key) {
+        (Some(ak), Some(sk)) => Some(Credential {
+            access_key_id: ak.clone(),
+            secret_access_key: sk.clone(),
+            session_token: config.session_token.clone(),
+            expires_in: None,
+        }),
+        _ => None,
+    }
+}
+
+/// Build the url of SSO `GetRoleCredentials`, role names could contain
+/// characters like `+` and `@` that must be encoded.
+fn sso_role_credentials_url(endpoint: &str, account_id: &str, role_name: &str) -> String {
+    let query = form_urlencoded::Serializer::new(String::new())
+        .extend_pairs([("account_id", account_id), ("role_name", role_name)])
+        .finish();
+    format!("{endpoint}/federation/credentials?{query}")
+}
+
+/// Build the regional endpoint of service like `https://sts.us-east-1.amazonaws.com`.
+///
+/// - FIPS endpoints add `-fips` to the service like `sts-fips`, except that
+///   endpoints in GovCloud are FIPS compliant already.
+/// - Dualstack endpoints use `api.aws` as the dns suffix.
+fn regional_endpoint(service: &str, region: &str, fips: bool, dualstack: bool) -> String {
+    let china = region.starts_with("cn-");
+    let govcloud = region.starts_with("us-gov-");
+
+    let suffix = match (china, dualstack) {
+        (true, true) => "api.amazonwebservices.com.cn",
+        (true, false) => "amazonaws.com.cn",
+        (false, true) => "api.aws",
+        (false, false) => "amazonaws.com",
+    };
+    if fips && (!govcloud || dualstack) {
+        format!("https://{service}-fips.{region}.{suffix}")
+    } else {
+        format!("https://{service}.{region}.{suffix}")
+    }
+}
+
+/// Run `credential_process` and parse credential from its stdout.
+///
+/// The command is executed directly without shell so that no shell
+/// expansion or injection could happen, and it will be killed if it doesn't
+/// exit in `timeout`.
+async fn run_credential_process(command: &str, timeout: Duration) -> Result<Credential> {
+    let args = split_command(command)?;
+    let (program, args) = args
+        .split_first()
+        .ok_or_else(|| anyhow!("credential_process is empty"))?;
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        // Keep stderr so that users can see the prompts like MFA.
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|err| anyhow!("spawn credential_process {program} failed: {err}"))?;
+
+    // Read stdout in another thread so that the process will not be
+    // blocked by a full pipe while we are waiting for it.
+    let mut stdout = child.stdout.take().expect("stdout must be piped");
+    let reader = std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        stdout.read_to_end(&mut buf).map(|_| buf)
+    });
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(anyhow!(
+                "credential_process {program} didn't exit in {timeout:?}"
+            ));
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    };
+    if !status.success() {
+        return Err(anyhow!("credential_process {program} exited with {status}"));
+    }
+
+    let output = reader
+        .join()
+        .map_err(|_| anyhow!("read stdout of credential_process {program} panicked"))??;
+    let resp: ProcessCredentials = serde_json::from_slice(&output)
+        .map_err(|err| anyhow!("parse output of credential_process {program} failed: {err}"))?;
+    if resp.version != 1 {
+        return Err(anyhow!(
+            "credential_process {program} returned unsupported version {}",
+            resp.version
+        ));
+    }
+
+    Ok(Credential {
+        access_key_id: resp.access_key_id,
+        secret_access_key: resp.secret_access_key,
+        session_token: resp.session_token,
+        expires_in: resp.expiration.as_deref().map(parse_rfc3339).transpose()?,
+    })
+}
+
+/// Split command into arguments like shells without any expansion.
+///
+/// Arguments are separated by whitespaces, and can be quoted by `"` or `'`.
+/// `\` escapes the next character outside of single quotes.
+fn split_command(command: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote: Option<char> = None;
+
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('\''), c) => current.get_or_insert_with(String::new).push(c),
+            (_, '\\') => {
+                let c = chars
+                    .next()
+                    .ok_or_else(|| anyhow!("credential_process ends with escape"))?;
+                current.get_or_insert_with(String::new).push(c)
+            }
+            (Some(_), c) => current.get_or_insert_with(String::new).push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => args.extend(current.take()),
+            (None, c) => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(anyhow!("credential_process contains unclosed quote"));
+    }
+    args.extend(current);
+
+    Ok(args)
+}
+
+/// Check if the full uri is allowed to load container credentials from.
+///
+/// To avoid SSRF, only `https` or `http` with loopback/link-local hosts
+/// is allowed, which is the same as AWS SDKs.
+fn check_container_credentials_full_uri(uri: &str) -> Result<()> {
+    let url: http::Uri = uri.parse()?;
+    if url.scheme_str() == Some("https") {
+        return Ok(());
+    }
+    if url.scheme_str() != Some("http") {
+        return Err(anyhow!(
+            "container credentials full uri {uri} has unsupported scheme"
+        ));
+    }
+
+    let host = url.host().unwrap_or_default();
+    let allowed = match host
+        .trim_matches(|c| c == '[' || c == ']')
+        .parse::<IpAddr>()
+    {
+        Ok(IpAddr::V4(ip)) => ip.is_loopback() || ip.is_link_local(),
+        // `fd00:ec2::23` is the endpoint of EKS Pod Identity Agent.
+        Ok(IpAddr::V6(ip)) => {
+            ip.is_loopback()
+                || (ip.segments()[0] & 0xffc0) == 0xfe80
+                || ip == Ipv6Addr::new(0xfd00, 0xec2, 0, 0, 0, 0, 0, 0x23)
+        }
+        Err(_) => host == "localhost",
+    };
+    if !allowed {
+        return Err(anyhow!(
+            "container credentials full uri {uri} must be https or a loopback/link-local http uri"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Load the sso access token cached by `aws sso login`.
+///
+/// The cache file is `<cache_dir>/<hex sha1 of cache_key>.json`.
+fn load_sso_token(cache_dir: &str, cache_key: &str) -> Result<SsoTokenCache> {
+    let path = format!("{cache_dir}/{}.json", hex_sha1(cache_key.as_bytes()));
+    let content = fs::read_to_string(&path)?;
+    let token: SsoTokenCache = serde_json::from_str(&content)?;
+
+    if parse_rfc3339(&token.expires_at)? <= now() {
+        return Err(anyhow!(
+            "sso token in {path} has been expired, please run `aws sso login` to refresh"
+        ));
+    }
+
+    Ok(token)
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct SsoTokenCache {
+    access_token: String,
+    expires_at: String,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct SsoGetRoleCredentialsResponse {
+    role_credentials: SsoRoleCredentials,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct SsoRoleCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: String,
+    /// Expiration time in milliseconds since epoch.
+    expiration: i64,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+struct AssumeRoleWithWebIdentityResponse {
+    #[serde(rename = "AssumeRoleWithWebIdentityResult")]
+    result: AssumeRoleWithWebIdentityResult,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+struct AssumeRoleWithWebIdentityResult {
+    credentials: AssumeRoleWithWebIdentityCredentials,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+struct AssumeRoleWithWebIdentityCredentials {
+    access_key_id: String,
     secret_access_key: String,
     session_token: String,
     expiration: String,
@@ -442,6 +1407,25 @@ struct AssumeRoleCredentials {
     expiration: String,
 }
 
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+struct ProcessCredentials {
+    version: u8,
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+    expiration: Option<String>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+struct ContainerCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    token: String,
+    expiration: String,
+}
+
 #[derive(Default, Debug, Deserialize)]
 #[serde(default, rename_all = "PascalCase")]
 struct Ec2MetadataIamSecurityCredentials {
@@ -457,6 +1441,7 @@ struct Ec2MetadataIamSecurityCredentials {
 mod tests {
     use std::env;
     use std::str::FromStr;
+    use std::sync::atomic::AtomicUsize;
 
     use anyhow::Result;
     use base64::prelude::BASE64_STANDARD;
@@ -465,10 +1450,15 @@ mod tests {
     use once_cell::sync::Lazy;
     use quick_xml::de;
     use reqwest::Client;
+    use tokio::io::AsyncReadExt;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
     use tokio::runtime::Runtime;
 
     use super::*;
     use crate::aws::constants::*;
+    use crate::time::format_rfc3339;
+    use crate::time::set_fake_now;
 
     static RUNTIME: Lazy<Runtime> = Lazy::new(|| {
         tokio::runtime::Builder::new_multi_thread()
@@ -477,6 +1467,40 @@ mod tests {
             .expect("Should create a tokio runtime")
     });
 
+    #[test]
+    fn test_credential_expiry() {
+        let cred = |expires_in| Credential {
+            access_key_id: "access_key_id".to_string(),
+            secret_access_key: "secret_access_key".to_string(),
+            session_token: Some("session_token".to_string()),
+            expires_in,
+        };
+
+        // Long-lived credential never expires.
+        let long_lived = cred(None);
+        assert_eq!(long_lived.expires_at(), None);
+        assert!(!long_lived.is_expired());
+        assert!(!long_lived.is_expired_within(Duration::MAX));
+
+        // Near expiry.
+        let expires_at = now() + chrono::Duration::minutes(1);
+        let near = cred(Some(expires_at));
+        assert_eq!(near.expires_at(), Some(expires_at));
+        assert!(!near.is_expired());
+        assert!(near.is_expired_within(Duration::from_secs(5 * 60)));
+        assert!(!near.is_expired_within(Duration::from_secs(10)));
+
+        // Far from expiry.
+        let far = cred(Some(now() + chrono::Duration::hours(12)));
+        assert!(!far.is_expired());
+        assert!(!far.is_expired_within(Duration::from_secs(5 * 60)));
+        assert!(far.is_expired_within(Duration::MAX));
+
+        let expired = cred(Some(now() - chrono::Duration::minutes(1)));
+        assert!(expired.is_expired());
+        assert!(expired.is_expired_within(Duration::ZERO));
+    }
+
     #[test]
     fn test_credential_env_loader_without_env() {
         let _ = env_logger::builder().is_test(true).try_init();
@@ -695,6 +1719,1008 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_config_from_sso_profile() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        temp_env::with_vars(
+            vec![(
+                AWS_CONFIG_FILE,
+                Some(format!(
+                    "{}/testdata/services/aws/sso_config",
+                    env::current_dir()
+                        .expect("current_dir must exist")
+                        .to_string_lossy()
+                )),
+            )],
+            || {
+                let config = Config::default().from_env().from_profile();
+                assert_eq!(config.sso_session.as_deref(), Some("my-sso"));
+                assert_eq!(config.sso_account_id.as_deref(), Some("123456789011"));
+                assert_eq!(config.sso_role_name.as_deref(), Some("readOnly"));
+                assert_eq!(config.sso_region.as_deref(), Some("us-west-2"));
+                assert_eq!(
+                    config.sso_start_url.as_deref(),
+                    Some("https://my-sso-portal.awsapps.com/start")
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn test_load_sso_token() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let dir = env::temp_dir().join("reqsign_test_load_sso_token");
+        fs::create_dir_all(&dir)?;
+        let cache_dir = dir.to_string_lossy().to_string();
+
+        let write_token = |key: &str, expires_at: &str| -> Result<()> {
+            let path = dir.join(format!("{}.json", hex_sha1(key.as_bytes())));
+            fs::write(
+                path,
+                format!(
+                    r#"{{"startUrl": "https://my-sso-portal.awsapps.com/start", "region": "us-west-2", "accessToken": "token_{key}", "expiresAt": "{expires_at}"}}"#
+                ),
+            )?;
+            Ok(())
+        };
+
+        write_token("my-sso", "2099-01-01T00:00:00Z")?;
+        let token = load_sso_token(&cache_dir, "my-sso")?;
+        assert_eq!(token.access_token, "token_my-sso");
+
+        write_token("expired-sso", "2019-11-14T04:05:45Z")?;
+        assert!(load_sso_token(&cache_dir, "expired-sso").is_err());
+
+        assert!(load_sso_token(&cache_dir, "not-exist").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sso_role_credentials_url() {
+        assert_eq!(
+            sso_role_credentials_url(
+                "https://portal.sso.us-east-1.amazonaws.com",
+                "123456789012",
+                "a+b@c"
+            ),
+            "https://portal.sso.us-east-1.amazonaws.com/federation/credentials?account_id=123456789012&role_name=a%2Bb%40c"
+        );
+    }
+
+    #[test]
+    fn test_parse_sso_get_role_credentials_response() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let content = r#"{
+  "roleCredentials": {
+    "accessKeyId": "access_key_id",
+    "secretAccessKey": "secret_access_key",
+    "sessionToken": "session_token",
+    "expiration": 1653479117000
+  }
+}"#;
+
+        let resp: SsoGetRoleCredentialsResponse =
+            serde_json::from_str(content).expect("json deserialize must success");
+
+        assert_eq!(&resp.role_credentials.access_key_id, "access_key_id");
+        assert_eq!(
+            &resp.role_credentials.secret_access_key,
+            "secret_access_key"
+        );
+        assert_eq!(&resp.role_credentials.session_token, "session_token");
+        assert_eq!(resp.role_credentials.expiration, 1653479117000);
+
+        Ok(())
+    }
+
+    /// Serve container credentials on a local port, returns the address and
+    /// all received `Authorization` headers.
+    async fn serve_container_credentials(
+        expiration: DateTime,
+    ) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let tokens = Arc::new(Mutex::new(Vec::new()));
+
+        let received = tokens.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0; 4096];
+                let n = stream.read(&mut buf).await.unwrap();
+                let req = String::from_utf8_lossy(&buf[..n]).to_string();
+                let token = req
+                    .lines()
+                    .find_map(|l| l.strip_prefix("authorization: "))
+                    .unwrap_or_default()
+                    .to_string();
+                received.lock().unwrap().push(token);
+
+                let body = format!(
+                    r#"{{"AccessKeyId":"access_key_id","SecretAccessKey":"secret_access_key","Token":"session_token","Expiration":"{}"}}"#,
+                    format_rfc3339(expiration)
+                );
+                let resp = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                stream.write_all(resp.as_bytes()).await.unwrap();
+            }
+        });
+
+        (addr.to_string(), tokens)
+    }
+
+    #[test]
+    fn test_credential_container_loader_with_full_uri() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        // Expiration is within the 120s buffer, every load will refresh.
+        let (addr, tokens) = RUNTIME.block_on(serve_container_credentials(
+            now() + chrono::Duration::seconds(60),
+        ));
+
+        let token_file = env::temp_dir().join("reqsign_test_container_token");
+        fs::write(&token_file, "token_0\n")?;
+
+        temp_env::with_vars(
+            vec![
+                (AWS_ACCESS_KEY_ID, None),
+                (AWS_SECRET_ACCESS_KEY, None),
+                (
+                    AWS_CONTAINER_CREDENTIALS_FULL_URI,
+                    Some(format!("http://{addr}/v1/credentials")),
+                ),
+                (
+                    AWS_CONTAINER_AUTHORIZATION_TOKEN_FILE,
+                    Some(token_file.to_string_lossy().to_string()),
+                ),
+            ],
+            || {
+                RUNTIME.block_on(async {
+                    let l = Loader::new(Client::new(), Config::default().from_env())
+                        .with_disable_ec2_metadata();
+                    let x = l.load().await.unwrap().unwrap();
+                    assert_eq!("access_key_id", x.access_key_id);
+                    assert_eq!("secret_access_key", x.secret_access_key);
+                    assert_eq!(Some("session_token".to_string()), x.session_token);
+
+                    // Token file rotated.
+                    fs::write(&token_file, "token_1\n").unwrap();
+                    l.load().await.unwrap().unwrap();
+                })
+            },
+        );
+
+        assert_eq!(*tokens.lock().unwrap(), vec!["token_0", "token_1"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_container_credentials_full_uri() {
+        for uri in [
+            "https://example.com/credentials",
+            "http://127.0.0.1:8080/credentials",
+            "http://localhost/credentials",
+            "http://169.254.170.23/v1/credentials",
+            "http://[::1]/credentials",
+            "http://[fd00:ec2::23]/v1/credentials",
+        ] {
+            assert!(
+                check_container_credentials_full_uri(uri).is_ok(),
+                "{uri} should be allowed"
+            );
+        }
+
+        for uri in [
+            "http://example.com/credentials",
+            "http://10.0.0.1/credentials",
+            "ftp://127.0.0.1/credentials",
+            "not a uri",
+        ] {
+            assert!(
+                check_container_credentials_full_uri(uri).is_err(),
+                "{uri} should be rejected"
+            );
+        }
+    }
+
+    #[derive(Default)]
+    struct MockImds {
+        /// Whether IMDSv2 session token is supported.
+        token_supported: bool,
+        /// Token that accepted by mock imds, requests without it will be rejected.
+        valid_token: Mutex<Option<String>>,
+        issued_tokens: AtomicUsize,
+    }
+
+    /// Serve a mock ec2 metadata service which rejects unauthenticated
+    /// requests if `token_supported` is true.
+    async fn serve_ec2_metadata(token_supported: bool) -> (String, Arc<MockImds>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let imds = Arc::new(MockImds {
+            token_supported,
+            ..Default::default()
+        });
+
+        let state = imds.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0; 4096];
+                let n = stream.read(&mut buf).await.unwrap();
+                let req = String::from_utf8_lossy(&buf[..n]).to_string();
+                let token = req
+                    .lines()
+                    .find_map(|l| l.strip_prefix("x-aws-ec2-metadata-token: "))
+                    .map(|v| v.to_string());
+
+                let (status, body) = if req.starts_with("PUT /latest/api/token") {
+                    if state.token_supported {
+                        assert!(req.contains("x-aws-ec2-metadata-token-ttl-seconds: 21600"));
+                        let idx = state.issued_tokens.fetch_add(1, Ordering::SeqCst);
+                        let token = format!("token_{idx}");
+                        *state.valid_token.lock().unwrap() = Some(token.clone());
+                        ("200 OK", token)
+                    } else {
+                        ("403 Forbidden", String::new())
+                    }
+                } else if state.token_supported
+                    && (token.is_none() || token != *state.valid_token.lock().unwrap())
+                {
+                    ("401 Unauthorized", String::new())
+                } else if req.starts_with("GET /latest/meta-data/placement/region ") {
+                    ("200 OK", "ap-northeast-1".to_string())
+                } else if req.starts_with("GET /latest/meta-data/iam/security-credentials/ ") {
+                    ("200 OK", "test_role".to_string())
+                } else if req
+                    .starts_with("GET /latest/meta-data/iam/security-credentials/test_role ")
+                {
+                    (
+                        "200 OK",
+                        r#"{"Code":"Success","AccessKeyId":"access_key_id","SecretAccessKey":"secret_access_key","Token":"session_token","Expiration":"2099-01-01T00:00:00Z"}"#.to_string(),
+                    )
+                } else {
+                    ("404 Not Found", String::new())
+                };
+
+                let resp = format!(
+                    "HTTP/1.1 {status}\r\ncontent-length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                stream.write_all(resp.as_bytes()).await.unwrap();
+            }
+        });
+
+        (format!("http://{addr}"), imds)
+    }
+
+    fn ec2_metadata_loader(endpoint: String) -> Loader {
+        Loader::new(
+            Client::new(),
+            Config {
+                ec2_metadata_service_endpoint: endpoint,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_region_via_imds() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        RUNTIME.block_on(async {
+            let (endpoint, imds) = serve_ec2_metadata(true).await;
+
+            // Region in config takes precedence.
+            let l = Loader::new(
+                Client::new(),
+                Config {
+                    region: Some("us-west-2".to_string()),
+                    ec2_metadata_service_endpoint: endpoint.clone(),
+                    ..Default::default()
+                },
+            );
+            assert_eq!(l.region().await.unwrap().as_deref(), Some("us-west-2"));
+            assert_eq!(imds.issued_tokens.load(Ordering::SeqCst), 0);
+
+            let l = ec2_metadata_loader(endpoint.clone());
+            assert_eq!(l.region().await.unwrap().as_deref(), Some("ap-northeast-1"));
+            // Resolved region should be cached.
+            l.region().await.unwrap();
+            assert_eq!(imds.issued_tokens.load(Ordering::SeqCst), 1);
+
+            let l = ec2_metadata_loader(endpoint).with_disable_ec2_metadata();
+            assert_eq!(l.region().await.unwrap(), None);
+        });
+    }
+
+    #[test]
+    fn test_credential_imds_v2_loader() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        RUNTIME.block_on(async {
+            let (endpoint, imds) = serve_ec2_metadata(true).await;
+            let l = ec2_metadata_loader(endpoint);
+
+            let x = l.load_via_imds_v2().await.unwrap().unwrap();
+            assert_eq!("access_key_id", x.access_key_id);
+            assert_eq!("secret_access_key", x.secret_access_key);
+            assert_eq!(Some("session_token".to_string()), x.session_token);
+
+            // Session token should be cached.
+            l.load_via_imds_v2().await.unwrap().unwrap();
+            assert_eq!(imds.issued_tokens.load(Ordering::SeqCst), 1);
+
+            // Session token rejected, should be re-fetched.
+            *imds.valid_token.lock().unwrap() = None;
+            let x = l.load_via_imds_v2().await.unwrap().unwrap();
+            assert_eq!("access_key_id", x.access_key_id);
+            assert_eq!(imds.issued_tokens.load(Ordering::SeqCst), 2);
+        });
+    }
+
+    #[test]
+    fn test_credential_imds_v1_fallback() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        RUNTIME.block_on(async {
+            let (endpoint, _) = serve_ec2_metadata(false).await;
+
+            // IMDSv1 is not allowed by default.
+            let l = ec2_metadata_loader(endpoint.clone());
+            assert!(l.load_via_imds_v2().await.is_err());
+
+            let l = ec2_metadata_loader(endpoint).with_allow_ec2_metadata_v1();
+            let x = l.load_via_imds_v2().await.unwrap().unwrap();
+            assert_eq!("access_key_id", x.access_key_id);
+        });
+    }
+
+    /// Serve a mock sts which records all received web identity tokens.
+    async fn serve_sts(expiration: DateTime) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let tokens = Arc::new(Mutex::new(Vec::new()));
+
+        let received = tokens.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0; 4096];
+                let n = stream.read(&mut buf).await.unwrap();
+                let req = String::from_utf8_lossy(&buf[..n]).to_string();
+                let path = req.split(' ').nth(1).unwrap().to_string();
+                let query = path.split_once('?').unwrap().1;
+                let params: Vec<(String, String)> = form_urlencoded::parse(query.as_bytes())
+                    .into_owned()
+                    .collect();
+                assert!(params.contains(&(
+                    "Action".to_string(),
+                    "AssumeRoleWithWebIdentity".to_string()
+                )));
+                let token = params
+                    .iter()
+                    .find(|(k, _)| k == "WebIdentityToken")
+                    .map(|(_, v)| v.clone())
+                    .unwrap();
+                received.lock().unwrap().push(token);
+
+                let body = format!(
+                    r#"<AssumeRoleWithWebIdentityResponse xmlns="https://sts.amazonaws.com/doc/2011-06-15/">
+  <AssumeRoleWithWebIdentityResult>
+    <Credentials>
+      <AccessKeyId>access_key_id</AccessKeyId>
+      <SecretAccessKey>secret_access_key</SecretAccessKey>
+      <SessionToken>session_token</SessionToken>
+      <Expiration>{}</Expiration>
+    </Credentials>
+  </AssumeRoleWithWebIdentityResult>
+</AssumeRoleWithWebIdentityResponse>"#,
+                    format_rfc3339(expiration)
+                );
+                let resp = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: text/xml\r\ncontent-length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                stream.write_all(resp.as_bytes()).await.unwrap();
+            }
+        });
+
+        (format!("http://{addr}"), tokens)
+    }
+
+    #[test]
+    fn test_credential_web_identity_loader() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let start = Utc.with_ymd_and_hms(2022, 3, 1, 8, 0, 0).unwrap();
+        let expiration = start + chrono::Duration::hours(1);
+        let (endpoint, tokens) = RUNTIME.block_on(serve_sts(expiration));
+
+        let token_file = env::temp_dir().join("reqsign_test_web_identity_token");
+        fs::write(&token_file, "token+0/==\n")?;
+
+        temp_env::with_vars(
+            vec![
+                (AWS_ACCESS_KEY_ID, None),
+                (AWS_SECRET_ACCESS_KEY, None),
+                (AWS_ROLE_ARN, Some("arn:aws:iam::123456789012:role/reqsign")),
+                (
+                    AWS_WEB_IDENTITY_TOKEN_FILE,
+                    Some(token_file.to_string_lossy().as_ref()),
+                ),
+                (AWS_ENDPOINT_URL_STS, Some(endpoint.as_str())),
+            ],
+            || {
+                RUNTIME.block_on(async {
+                    let l = Loader::new(Client::new(), Config::default().from_env())
+                        .with_disable_ec2_metadata();
+
+                    set_fake_now(Some(start));
+                    let x = l.load().await.unwrap().unwrap();
+                    assert_eq!("access_key_id", x.access_key_id);
+                    assert_eq!(Some("session_token".to_string()), x.session_token);
+
+                    // Token file rotated, but credential is still cached.
+                    fs::write(&token_file, "token+1/==\n").unwrap();
+                    set_fake_now(Some(expiration - chrono::Duration::minutes(30)));
+                    l.load().await.unwrap().unwrap();
+                    assert_eq!(tokens.lock().unwrap().len(), 1);
+
+                    // Credential is about to expire, should be refreshed with new token.
+                    set_fake_now(Some(expiration - chrono::Duration::seconds(60)));
+                    l.load().await.unwrap().unwrap();
+
+                    set_fake_now(None);
+                })
+            },
+        );
+
+        assert_eq!(*tokens.lock().unwrap(), vec!["token+0/==", "token+1/=="]);
+        Ok(())
+    }
+
+    /// Mock http client that responds with fixed body and records requests.
+    #[derive(Debug, Default)]
+    struct MockHttpSend {
+        body: String,
+        uris: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl HttpSend for MockHttpSend {
+        async fn send(&self, req: http::Request<Vec<u8>>) -> Result<http::Response<Vec<u8>>> {
+            self.uris.lock().unwrap().push(req.uri().to_string());
+            Ok(http::Response::builder()
+                .status(http::StatusCode::OK)
+                .body(self.body.clone().into_bytes())?)
+        }
+    }
+
+    #[test]
+    fn test_credential_assume_role_with_http_send() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let client = Arc::new(MockHttpSend {
+            body: r#"<AssumeRoleResponse xmlns="https://sts.amazonaws.com/doc/2011-06-15/">
+  <AssumeRoleResult>
+    <Credentials>
+      <AccessKeyId>access_key_id</AccessKeyId>
+      <SecretAccessKey>secret_access_key</SecretAccessKey>
+      <SessionToken>session_token</SessionToken>
+      <Expiration>2099-01-01T00:00:00Z</Expiration>
+    </Credentials>
+  </AssumeRoleResult>
+</AssumeRoleResponse>"#
+                .to_string(),
+            ..Default::default()
+        });
+
+        let l = Loader::new(
+            client.clone(),
+            Config {
+                region: Some("us-west-2".to_string()),
+                sts_regional_endpoints: "regional".to_string(),
+                role_arn: Some("arn:aws:iam::123456789012:role/reqsign".to_string()),
+                external_id: Some("external id".to_string()),
+                ..Default::default()
+            },
+        );
+        let x = RUNTIME.block_on(l.load_via_assume_role())?.unwrap();
+        assert_eq!("access_key_id", x.access_key_id);
+        assert_eq!("secret_access_key", x.secret_access_key);
+        assert_eq!(Some("session_token".to_string()), x.session_token);
+
+        assert_eq!(
+            *client.uris.lock().unwrap(),
+            vec!["https://sts.us-west-2.amazonaws.com/?Action=AssumeRole&RoleArn=arn%3Aaws%3Aiam%3A%3A123456789012%3Arole%2Freqsign&Version=2011-06-15&RoleSessionName=reqsign&ExternalId=external+id"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sts_endpoint_fips_dualstack() -> Result<()> {
+        let endpoint = |region: Option<&str>, fips, dualstack| {
+            Loader::new(
+                Arc::new(MockHttpSend::default()),
+                Config {
+                    region: region.map(|v| v.to_string()),
+                    use_fips_endpoint: fips,
+                    use_dualstack_endpoint: dualstack,
+                    ..Default::default()
+                },
+            )
+            .sts_endpoint()
+        };
+
+        for (region, fips, dualstack, expected) in [
+            (
+                "us-east-1",
+                true,
+                false,
+                "https://sts-fips.us-east-1.amazonaws.com",
+            ),
+            ("us-east-1", false, true, "https://sts.us-east-1.api.aws"),
+            (
+                "us-east-1",
+                true,
+                true,
+                "https://sts-fips.us-east-1.api.aws",
+            ),
+            (
+                "us-gov-west-1",
+                true,
+                false,
+                "https://sts.us-gov-west-1.amazonaws.com",
+            ),
+            (
+                "us-gov-west-1",
+                true,
+                true,
+                "https://sts-fips.us-gov-west-1.api.aws",
+            ),
+            (
+                "cn-north-1",
+                false,
+                true,
+                "https://sts.cn-north-1.api.amazonwebservices.com.cn",
+            ),
+        ] {
+            assert_eq!(endpoint(Some(region), fips, dualstack)?, expected);
+        }
+
+        // Region is required by fips endpoints.
+        assert!(endpoint(None, true, false).is_err());
+        // Legacy global endpoint is still used if not enabled.
+        assert_eq!(
+            endpoint(Some("us-east-1"), false, false)?,
+            "https://sts.amazonaws.com"
+        );
+
+        Ok(())
+    }
+
+    /// Mock http client that fails with given statuses before succeeding,
+    /// `None` means a network error.
+    #[derive(Debug, Default)]
+    struct MockFlaky {
+        failures: Mutex<Vec<Option<u16>>>,
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl HttpSend for MockFlaky {
+        async fn send(&self, _: http::Request<Vec<u8>>) -> Result<http::Response<Vec<u8>>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let mut failures = self.failures.lock().unwrap();
+            if failures.is_empty() {
+                return Ok(http::Response::builder().status(200).body(
+                    br#"{"AccessKeyId":"access_key_id","SecretAccessKey":"secret_access_key","Token":"session_token","Expiration":"2099-01-01T00:00:00Z"}"#.to_vec(),
+                )?);
+            }
+            match failures.remove(0) {
+                Some(status) => Ok(http::Response::builder()
+                    .status(status)
+                    .body(b"failed".to_vec())?),
+                None => Err(anyhow!("connection reset")),
+            }
+        }
+    }
+
+    /// Mock container endpoint that issues a new credential for every call.
+    #[derive(Debug, Default)]
+    struct MockRotating {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl HttpSend for MockRotating {
+        async fn send(&self, _: http::Request<Vec<u8>>) -> Result<http::Response<Vec<u8>>> {
+            let n = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            // Make concurrent refreshes overlap.
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            Ok(http::Response::builder().status(200).body(
+                format!(r#"{{"AccessKeyId":"access_key_id_{n}","SecretAccessKey":"secret_access_key","Token":"session_token","Expiration":"2099-01-01T00:00:00Z"}}"#).into_bytes(),
+            )?)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_force_refresh() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let client = Arc::new(MockRotating::default());
+        let l = Loader::new(
+            client.clone(),
+            Config {
+                container_credentials_full_uri: Some("http://127.0.0.1/creds".to_string()),
+                ..Default::default()
+            },
+        );
+
+        let x = l.load().await?.unwrap();
+        assert_eq!("access_key_id_1", x.access_key_id);
+        let x = l.load().await?.unwrap();
+        assert_eq!("access_key_id_1", x.access_key_id);
+        assert_eq!(client.calls.load(Ordering::SeqCst), 1);
+
+        // Cached credential should be replaced.
+        let x = l.force_refresh().await?.unwrap();
+        assert_eq!("access_key_id_2", x.access_key_id);
+        let x = l.load().await?.unwrap();
+        assert_eq!("access_key_id_2", x.access_key_id);
+        assert_eq!(client.calls.load(Ordering::SeqCst), 2);
+
+        // Concurrent refreshes should only hit the endpoint once.
+        let (a, b, c) = tokio::join!(l.force_refresh(), l.force_refresh(), l.force_refresh());
+        for x in [a?, b?, c?] {
+            assert_eq!("access_key_id_3", x.unwrap().access_key_id);
+        }
+        assert_eq!(client.calls.load(Ordering::SeqCst), 3);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_load_single_flight() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let client = Arc::new(MockRotating::default());
+        let l = Arc::new(Loader::new(
+            client.clone(),
+            Config {
+                container_credentials_full_uri: Some("http://127.0.0.1/creds".to_string()),
+                ..Default::default()
+            },
+        ));
+
+        let tasks: Vec<_> = (0..100)
+            .map(|_| {
+                let l = l.clone();
+                tokio::spawn(async move { l.load().await })
+            })
+            .collect();
+        for task in tasks {
+            let x = task.await??.unwrap();
+            assert_eq!("access_key_id_1", x.access_key_id);
+        }
+        assert_eq!(client.calls.load(Ordering::SeqCst), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_credential_retry() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let loader = |failures: Vec<Option<u16>>, max_retries| {
+            let client = Arc::new(MockFlaky {
+                failures: Mutex::new(failures),
+                ..Default::default()
+            });
+            let l = Loader::new(
+                client.clone(),
+                Config {
+                    container_credentials_full_uri: Some("http://127.0.0.1/creds".to_string()),
+                    max_retries,
+                    retry_base_delay: std::time::Duration::from_millis(1),
+                    ..Default::default()
+                },
+            );
+            (l, client)
+        };
+
+        // Transient failures should be recovered.
+        let (l, client) = loader(vec![Some(503), None], 3);
+        let x = l.load_via_container().await?.unwrap();
+        assert_eq!("access_key_id", x.access_key_id);
+        assert_eq!(client.calls.load(Ordering::SeqCst), 3);
+
+        // Auth errors should fail fast.
+        let (l, client) = loader(vec![Some(403)], 3);
+        let err = Error::from(l.load_via_container().await.unwrap_err());
+        assert!(matches!(err, Error::Unexpected(_)), "{err:?}");
+        assert_eq!(client.calls.load(Ordering::SeqCst), 1);
+
+        // Give up after max retries.
+        let (l, client) = loader(vec![Some(429), Some(500), Some(500)], 1);
+        let err = Error::from(l.load_via_container().await.unwrap_err());
+        assert!(matches!(err, Error::MetadataUnavailable(_)), "{err:?}");
+        assert_eq!(client.calls.load(Ordering::SeqCst), 2);
+
+        let (l, _) = loader(vec![None, None], 1);
+        let err = Error::from(l.load_via_container().await.unwrap_err());
+        assert!(matches!(err, Error::MetadataUnavailable(_)), "{err:?}");
+
+        Ok(())
+    }
+
+    /// Mock sts that issues credentials named after the assumed role and
+    /// records the access key used to sign every request.
+    #[derive(Debug, Default)]
+    struct MockSts {
+        requests: Mutex<Vec<(String, String)>>,
+        uris: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl HttpSend for MockSts {
+        async fn send(&self, req: http::Request<Vec<u8>>) -> Result<http::Response<Vec<u8>>> {
+            let role = form_urlencoded::parse(req.uri().query().unwrap_or_default().as_bytes())
+                .find(|(k, _)| k == "RoleArn")
+                .map(|(_, v)| v.rsplit('/').next().unwrap_or_default().to_string())
+                .expect("RoleArn must be set");
+            let signed_by = req
+                .headers()
+                .get(AUTHORIZATION)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.split("Credential=").nth(1))
+                .and_then(|v| v.split('/').next())
+                .unwrap_or_default()
+                .to_string();
+            self.requests
+                .lock()
+                .unwrap()
+                .push((role.clone(), signed_by));
+            self.uris.lock().unwrap().push(req.uri().to_string());
+
+            let body = format!(
+                r#"<AssumeRoleResponse xmlns="https://sts.amazonaws.com/doc/2011-06-15/">
+  <AssumeRoleResult>
+    <Credentials>
+      <AccessKeyId>{role}_access_key_id</AccessKeyId>
+      <SecretAccessKey>{role}_secret_access_key</SecretAccessKey>
+      <SessionToken>{role}_session_token</SessionToken>
+      <Expiration>2099-01-01T00:00:00Z</Expiration>
+    </Credentials>
+  </AssumeRoleResult>
+</AssumeRoleResponse>"#
+            );
+            Ok(http::Response::builder()
+                .status(http::StatusCode::OK)
+                .body(body.into_bytes())?)
+        }
+    }
+
+    fn source_profile_config(profile: &str) -> Result<Config> {
+        let config_file =
+            env::temp_dir().join(format!("reqsign_test_source_profile_config_{profile}"));
+        fs::write(
+            &config_file,
+            r#"[profile prod]
+role_arn = arn:aws:iam::123456789012:role/prod
+source_profile = staging
+external_id = prod_external_id
+duration_seconds = 1800
+
+[profile staging]
+role_arn = arn:aws:iam::123456789012:role/staging
+source_profile = default
+
+[default]
+aws_access_key_id = default_access_key_id
+aws_secret_access_key = default_secret_access_key
+
+[profile self]
+role_arn = arn:aws:iam::123456789012:role/self
+source_profile = self
+aws_access_key_id = self_static_access_key_id
+aws_secret_access_key = self_static_secret_access_key
+
+[profile mfa]
+role_arn = arn:aws:iam::123456789012:role/mfa
+source_profile = default
+mfa_serial = arn:aws:iam::123456789012:mfa/user
+
+[profile loop_a]
+role_arn = arn:aws:iam::123456789012:role/loop_a
+source_profile = loop_b
+
+[profile loop_b]
+role_arn = arn:aws:iam::123456789012:role/loop_b
+source_profile = loop_a
+"#,
+        )?;
+
+        Ok(Config {
+            config_file: config_file.to_string_lossy().to_string(),
+            shared_credentials_file: "/not_exist".to_string(),
+            profile: Some(profile.to_string()),
+            ..Default::default()
+        }
+        .from_profile())
+    }
+
+    #[tokio::test]
+    async fn test_credential_source_profile_chain() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let config = source_profile_config("prod")?;
+        assert_eq!(config.source_profile.as_deref(), Some("staging"));
+        assert_eq!(config.external_id.as_deref(), Some("prod_external_id"));
+        assert_eq!(config.duration_seconds, Some(1800));
+
+        let client = Arc::new(MockSts::default());
+        let cred = Loader::new(client.clone(), config)
+            .load_via_source_profile()
+            .await?
+            .expect("credential must be loaded");
+        assert_eq!(cred.access_key_id, "prod_access_key_id");
+        assert_eq!(cred.session_token.as_deref(), Some("prod_session_token"));
+        // Roles must be assumed from the root of chain.
+        assert_eq!(
+            *client.requests.lock().unwrap(),
+            vec![
+                ("staging".to_string(), "default_access_key_id".to_string()),
+                ("prod".to_string(), "staging_access_key_id".to_string()),
+            ]
+        );
+
+        let client = Arc::new(MockSts::default());
+        let cred = Loader::new(client.clone(), source_profile_config("self")?)
+            .load()
+            .await?
+            .expect("credential must be loaded");
+        assert_eq!(cred.access_key_id, "self_access_key_id");
+        assert_eq!(
+            *client.requests.lock().unwrap(),
+            vec![("self".to_string(), "self_static_access_key_id".to_string())]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_credential_env_before_source_profile() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        // Keys set via env must not be ignored by roles in profile.
+        let config = Config {
+            access_key_id: Some("env_access_key_id".to_string()),
+            secret_access_key: Some("env_secret_access_key".to_string()),
+            ..source_profile_config("prod")?
+        };
+        let client = Arc::new(MockSts::default());
+        let cred = Loader::new(client.clone(), config)
+            .load()
+            .await?
+            .expect("credential must be loaded");
+        assert_eq!(cred.access_key_id, "env_access_key_id");
+        assert!(client.requests.lock().unwrap().is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_credential_assume_role_with_mfa() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let config = source_profile_config("mfa")?;
+        assert_eq!(
+            config.mfa_serial.as_deref(),
+            Some("arn:aws:iam::123456789012:mfa/user")
+        );
+
+        // Token provider is required for profile with mfa_serial.
+        let client = Arc::new(MockSts::default());
+        let l = Loader::new(client.clone(), config.clone());
+        assert!(l.load_via_source_profile().await.is_err());
+        assert!(client.uris.lock().unwrap().is_empty());
+
+        let called = Arc::new(AtomicUsize::new(0));
+        let l = Loader::new(client.clone(), config).with_mfa_token_provider({
+            let called = called.clone();
+            move || {
+                called.fetch_add(1, Ordering::SeqCst);
+                Ok("123456\n".to_string())
+            }
+        });
+        let cred = l.load().await?.expect("credential must be loaded");
+        assert_eq!(cred.access_key_id, "mfa_access_key_id");
+        // Assumed credential should be cached until expired.
+        let cred = l.load().await?.expect("credential must be loaded");
+        assert_eq!(cred.access_key_id, "mfa_access_key_id");
+        assert_eq!(called.load(Ordering::SeqCst), 1);
+
+        assert_eq!(
+            *client.uris.lock().unwrap(),
+            vec!["https://sts.amazonaws.com/?Action=AssumeRole&RoleArn=arn%3Aaws%3Aiam%3A%3A123456789012%3Arole%2Fmfa&RoleSessionName=reqsign&SerialNumber=arn%3Aaws%3Aiam%3A%3A123456789012%3Amfa%2Fuser&TokenCode=123456&Version=2011-06-15"]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_credential_assume_role_with_parameters() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let client = Arc::new(MockSts::default());
+        let config = Config {
+            role_arn: Some("arn:aws:iam::123456789012:role/partner".to_string()),
+            external_id: Some("partner_external_id".to_string()),
+            duration_seconds: Some(900),
+            policy: Some(r#"{"Version":"2012-10-17"}"#.to_string()),
+            policy_arns: vec!["arn:aws:iam::aws:policy/ReadOnlyAccess".to_string()],
+            ..Default::default()
+        };
+        let cred = Loader::new(client.clone(), config)
+            .load_via_assume_role()
+            .await?
+            .expect("credential must be loaded");
+        assert_eq!(cred.access_key_id, "partner_access_key_id");
+
+        let uris = client.uris.lock().unwrap();
+        let uri: http::Uri = uris[0].parse()?;
+        let query: Vec<(String, String)> =
+            form_urlencoded::parse(uri.query().unwrap_or_default().as_bytes())
+                .into_owned()
+                .collect();
+        for (k, v) in [
+            ("ExternalId", "partner_external_id"),
+            ("DurationSeconds", "900"),
+            ("Policy", r#"{"Version":"2012-10-17"}"#),
+            (
+                "PolicyArns.member.1.arn",
+                "arn:aws:iam::aws:policy/ReadOnlyAccess",
+            ),
+        ] {
+            assert!(
+                query.contains(&(k.to_string(), v.to_string())),
+                "{k} must be set in {uri}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_credential_source_profile_cycle() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let client = Arc::new(MockSts::default());
+        let err = Loader::new(client.clone(), source_profile_config("loop_a")?)
+            .load_via_source_profile()
+            .await
+            .expect_err("cycle must be detected");
+        assert_eq!(
+            err.to_string(),
+            "source_profile cycle detected: loop_a -> loop_b -> loop_a"
+        );
+        assert!(client.requests.lock().unwrap().is_empty());
+
+        Ok(())
+    }
+
     #[test]
     fn test_parse_assume_role_with_web_identity_response() -> Result<()> {
         let _ = env_logger::builder().is_test(true).try_init();
@@ -786,4 +2812,89 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_split_command() -> Result<()> {
+        assert_eq!(
+            split_command(r#"aws-vault export --format=json "my profile" 'a "b"' c\ d"#)?,
+            vec![
+                "aws-vault",
+                "export",
+                "--format=json",
+                "my profile",
+                r#"a "b""#,
+                "c d"
+            ]
+        );
+        assert_eq!(split_command(r#"cmd """#)?, vec!["cmd", ""]);
+        assert!(split_command("  ")?.is_empty());
+        assert!(split_command(r#"cmd "unclosed"#).is_err());
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_load_via_credential_process() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let dir = env::temp_dir().join("reqsign_test_credential_process");
+        fs::create_dir_all(&dir)?;
+        let write_script = |name: &str, content: &str| -> Result<String> {
+            let path = dir.join(name);
+            fs::write(&path, format!("#!/bin/sh\n{content}\n"))?;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+            Ok(path.to_string_lossy().to_string())
+        };
+
+        // The first argument is printed as access key id to make sure that
+        // arguments are passed as-is without shell expansion.
+        let script = write_script(
+            "credential_process.sh",
+            r#"printf '{"Version": 1, "AccessKeyId": "%s", "SecretAccessKey": "secret_access_key", "SessionToken": "session_token", "Expiration": "2099-01-01T00:00:00Z"}' "$1""#,
+        )?;
+        let load = |command: String, timeout: Duration| {
+            let l = Loader::new(
+                Client::new(),
+                Config {
+                    credential_process: Some(command),
+                    credential_process_timeout: timeout,
+                    ..Default::default()
+                },
+            );
+            RUNTIME.block_on(l.load_via_credential_process())
+        };
+
+        let cred = load(
+            format!(r#"{script} '$(touch injected); echo'"#),
+            Duration::from_secs(10),
+        )?
+        .expect("credential must be loaded");
+        assert_eq!(cred.access_key_id, "$(touch injected); echo");
+        assert_eq!(cred.secret_access_key, "secret_access_key");
+        assert_eq!(cred.session_token.as_deref(), Some("session_token"));
+        assert_eq!(
+            cred.expires_in,
+            Some(Utc.with_ymd_and_hms(2099, 1, 1, 0, 0, 0).unwrap())
+        );
+
+        let script = write_script("credential_process_slow.sh", "sleep 10")?;
+        let start = Instant::now();
+        let err = load(script, Duration::from_millis(200)).expect_err("must timeout");
+        assert!(err.to_string().contains("didn't exit"), "{err}");
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let script = write_script("credential_process_failed.sh", "exit 1")?;
+        assert!(load(script, Duration::from_secs(10)).is_err());
+
+        let script = write_script(
+            "credential_process_version.sh",
+            r#"echo '{"Version": 2, "AccessKeyId": "ak", "SecretAccessKey": "sk"}'"#,
+        )?;
+        assert!(load(script, Duration::from_secs(10)).is_err());
+
+        Ok(())
+    }
 }
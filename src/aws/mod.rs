@@ -1,6 +1,13 @@
 //! AWS service signer
 //!
-//! Only sigv4 has been supported.
+//! sigv4 and sigv4a have been supported, sigv2 is supported for legacy
+//! S3-compatible services.
+mod bucket;
+pub use bucket::validate_bucket_name as aws_validate_bucket_name;
+
+mod checksum;
+pub use checksum::ChecksumAlgorithm as AwsChecksumAlgorithm;
+
 mod config;
 pub use config::Config as AwsConfig;
 
@@ -9,7 +16,17 @@ pub use credential::Credential as AwsCredential;
 pub use credential::CredentialLoad as AwsCredentialLoad;
 pub use credential::Loader as AwsLoader;
 
+mod v2;
+pub use v2::Signer as AwsV2Signer;
+
 mod v4;
+pub use v4::ChunkSigner as AwsV4ChunkSigner;
+pub use v4::DateHeader as AwsV4DateHeader;
+pub use v4::PayloadMode as AwsV4PayloadMode;
+pub use v4::PostFormFields as AwsV4PostFormFields;
 pub use v4::Signer as AwsV4Signer;
 
+mod v4a;
+pub use v4a::Signer as AwsV4aSigner;
+
 mod constants;
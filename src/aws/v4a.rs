@@ -0,0 +1,536 @@
+//! AWS service sigv4a signer
+
+use std::fmt::Debug;
+use std::fmt::Write;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use http::HeaderMap;
+use http::HeaderValue;
+use log::debug;
+use p256::ecdsa::signature::Signer as _;
+use p256::ecdsa::Signature;
+use p256::ecdsa::SigningKey;
+use percent_encoding::utf8_percent_encode;
+
+use super::constants::AWS_QUERY_ENCODE_SET;
+use super::constants::MAX_PRESIGN_EXPIRE;
+use super::constants::X_AMZ_REGION_SET;
+use super::credential::Credential;
+use super::v4::canonical_request_string;
+use super::v4::canonicalize_header;
+use super::v4::default_double_uri_encode;
+use super::v4::default_normalize_path;
+use super::v4::default_payload_hash;
+use super::v4::signed_header_names;
+use super::v4::DateHeader;
+use super::v4::HeaderFilter;
+use crate::ctx::SigningContext;
+use crate::ctx::SigningMethod;
+use crate::hash::hex_sha256;
+use crate::hash::hmac_sha256;
+use crate::request::sign_to_headers;
+use crate::request::SignableRequest;
+use crate::time::format_date;
+use crate::time::format_iso8601;
+use crate::time::now;
+use crate::time::DateTime;
+use crate::Error;
+
+const ALGORITHM: &str = "AWS4-ECDSA-P256-SHA256";
+
+/// The order of P-256 minus two, in big-endian.
+const P256_N_MINUS_TWO: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0xbc, 0xe6, 0xfa, 0xad, 0xa7, 0x17, 0x9e, 0x84, 0xf3, 0xb9, 0xca, 0xc2, 0xfc, 0x63, 0x25, 0x4f,
+];
+
+/// Singer that implement AWS SigV4a.
+///
+/// SigV4a signs requests with an ECDSA P-256 key derived from the secret
+/// access key, so that one signature can be valid in a set of regions.
+/// It's required by services like S3 Multi-Region Access Points.
+#[derive(Debug)]
+pub struct Signer {
+    service: String,
+    region_set: String,
+
+    time: Option<DateTime>,
+    time_offset: chrono::Duration,
+}
+
+impl Signer {
+    /// Create a builder.
+    ///
+    /// `region_set` is a comma separated list of regions that this
+    /// signature will be valid in, for example `us-east-1,us-west-2`
+    /// or `*` for all regions.
+    pub fn new(service: &str, region_set: &str) -> Self {
+        Self {
+            service: service.to_string(),
+            region_set: region_set.to_string(),
+            time: None,
+            time_offset: chrono::Duration::zero(),
+        }
+    }
+
+    /// Specify the signing time.
+    ///
+    /// Current time will be used if not set. Fixed time is useful to
+    /// generate reproducible signatures.
+    pub fn with_time(mut self, time: DateTime) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    /// Specify the clock skew offset, see [`skew_from_response`](crate::time::skew_from_response).
+    pub fn with_time_offset(mut self, offset: chrono::Duration) -> Self {
+        self.time_offset = offset;
+        self
+    }
+
+    /// Get the region set of this signer.
+    pub fn region_set(&self) -> &str {
+        &self.region_set
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "reqsign::sign",
+            level = "debug",
+            skip_all,
+            fields(provider = "aws")
+        )
+    )]
+    fn build(
+        &self,
+        req: &mut impl SignableRequest,
+        method: SigningMethod,
+        cred: &Credential,
+    ) -> Result<SigningContext> {
+        if cred.is_expired() {
+            return Err(Error::CredentialExpired.into());
+        }
+
+        let now = self.time.unwrap_or_else(|| now() + self.time_offset);
+        let mut ctx = req.build()?;
+
+        let creq = self.canonical_request(&mut ctx, method, cred, now)?;
+        let string_to_sign = self.string_to_sign(&creq, now)?;
+
+        let signing_key = generate_signing_key(&cred.access_key_id, &cred.secret_access_key)?;
+        let signature: Signature = signing_key.sign(string_to_sign.as_bytes());
+        let signature = hex::encode(signature.to_der().as_bytes());
+
+        match method {
+            SigningMethod::Header => {
+                let mut authorization = HeaderValue::from_str(&format!(
+                    "{ALGORITHM} Credential={}/{}, SignedHeaders={}, Signature={}",
+                    cred.access_key_id,
+                    self.scope(now),
+                    signed_header_names(&ctx, &HeaderFilter::default()).join(";"),
+                    signature
+                ))?;
+                authorization.set_sensitive(true);
+
+                ctx.headers
+                    .insert(http::header::AUTHORIZATION, authorization);
+            }
+            SigningMethod::Query(_) => {
+                ctx.query.push(("X-Amz-Signature".into(), signature));
+            }
+        }
+
+        Ok(ctx)
+    }
+
+    /// Scope: "20220313/<service>/aws4_request"
+    ///
+    /// Unlike sigv4, region is not part of the scope.
+    fn scope(&self, now: DateTime) -> String {
+        format!("{}/{}/aws4_request", format_date(now), self.service)
+    }
+
+    /// Canonicalize the context and build the canonical request.
+    fn canonical_request(
+        &self,
+        ctx: &mut SigningContext,
+        method: SigningMethod,
+        cred: &Credential,
+        now: DateTime,
+    ) -> Result<String> {
+        canonicalize_header(ctx, method, cred, now, DateHeader::default())?;
+        if method == SigningMethod::Header {
+            ctx.headers
+                .insert(X_AMZ_REGION_SET, HeaderValue::from_str(&self.region_set)?);
+        }
+        canonicalize_query(ctx, method, cred, now, &self.scope(now), &self.region_set)?;
+
+        canonical_request_string(
+            ctx,
+            default_double_uri_encode(&self.service),
+            default_normalize_path(&self.service),
+            &default_payload_hash(&self.service),
+            &HeaderFilter::default(),
+        )
+    }
+
+    /// Build the string to sign from canonical request.
+    ///
+    /// ```text
+    /// AWS4-ECDSA-P256-SHA256
+    /// 20220313T072004Z
+    /// 20220313/<service>/aws4_request
+    /// <hashed_canonical_request>
+    /// ```
+    fn string_to_sign(&self, creq: &str, now: DateTime) -> Result<String> {
+        let encoded_req = hex_sha256(creq.as_bytes());
+
+        let scope = self.scope(now);
+        debug!("calculated scope: {scope}");
+
+        let mut f = String::new();
+        writeln!(f, "{ALGORITHM}")?;
+        writeln!(f, "{}", format_iso8601(now))?;
+        writeln!(f, "{}", &scope)?;
+        write!(f, "{}", &encoded_req)?;
+        debug!("calculated string to sign: {f}");
+
+        Ok(f)
+    }
+
+    /// Signing request with header.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use anyhow::Result;
+    /// use reqsign::AwsConfig;
+    /// use reqsign::AwsLoader;
+    /// use reqsign::AwsV4aSigner;
+    /// use reqwest::Client;
+    /// use reqwest::Request;
+    /// use reqwest::Url;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let client = Client::new();
+    ///     let config = AwsConfig::default().from_profile().from_env();
+    ///     let loader = AwsLoader::new(client.clone(), config);
+    ///     let signer = AwsV4aSigner::new("s3", "*");
+    ///     // Construct request
+    ///     let url = Url::parse("https://mfzwi23gnjvgw.mrap.accesspoint.s3-global.amazonaws.com/object")?;
+    ///     let mut req = reqwest::Request::new(http::Method::GET, url);
+    ///     // Signing request with Signer
+    ///     let credential = loader.load().await?.unwrap();
+    ///     signer.sign(&mut req, &credential)?;
+    ///     // Sending already signed request.
+    ///     let resp = client.execute(req).await?;
+    ///     println!("resp got status: {}", resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn sign(&self, req: &mut impl SignableRequest, cred: &Credential) -> crate::Result<()> {
+        if cred.is_anonymous() {
+            return Ok(());
+        }
+
+        let ctx = self
+            .build(req, SigningMethod::Header, cred)
+            .map_err(Error::signing)?;
+        req.apply(ctx).map_err(Error::signing)
+    }
+
+    /// Calculate the headers that will be inserted or changed while signing
+    /// with header, the request will be left untouched.
+    ///
+    /// It's useful to apply the headers to another request like proxies
+    /// that re-emit requests.
+    pub fn sign_to_headers(
+        &self,
+        req: &mut impl SignableRequest,
+        cred: &Credential,
+    ) -> crate::Result<HeaderMap> {
+        sign_to_headers(req, |ctx| self.sign(ctx, cred))
+    }
+
+    /// Signing request with query.
+    pub fn sign_query(
+        &self,
+        req: &mut impl SignableRequest,
+        expire: Duration,
+        cred: &Credential,
+    ) -> crate::Result<()> {
+        if cred.is_anonymous() {
+            return Ok(());
+        }
+
+        let ctx = self
+            .build(req, SigningMethod::Query(expire), cred)
+            .map_err(Error::signing)?;
+        req.apply(ctx).map_err(Error::signing)
+    }
+}
+
+fn canonicalize_query(
+    ctx: &mut SigningContext,
+    method: SigningMethod,
+    cred: &Credential,
+    now: DateTime,
+    scope: &str,
+    region_set: &str,
+) -> Result<()> {
+    if let SigningMethod::Query(expire) = method {
+        // S3 will reject presigned url that expires after 7 days.
+        if expire > MAX_PRESIGN_EXPIRE {
+            return Err(anyhow!(
+                "presign expiry must be <= 7 days for SigV4a, but got {}s",
+                expire.as_secs()
+            ));
+        }
+
+        ctx.query.push(("X-Amz-Algorithm".into(), ALGORITHM.into()));
+        ctx.query.push((
+            "X-Amz-Credential".into(),
+            format!("{}/{}", cred.access_key_id, scope),
+        ));
+        ctx.query.push(("X-Amz-Date".into(), format_iso8601(now)));
+        ctx.query
+            .push(("X-Amz-Expires".into(), expire.as_secs().to_string()));
+        ctx.query
+            .push(("X-Amz-Region-Set".into(), region_set.to_string()));
+        ctx.query.push((
+            "X-Amz-SignedHeaders".into(),
+            signed_header_names(ctx, &HeaderFilter::default()).join(";"),
+        ));
+
+        if let Some(token) = &cred.session_token {
+            ctx.query
+                .push(("X-Amz-Security-Token".into(), token.into()));
+        }
+    }
+
+    // Return if query is empty.
+    if ctx.query.is_empty() {
+        return Ok(());
+    }
+
+    // Sort by param name
+    ctx.query.sort();
+
+    ctx.query = ctx
+        .query
+        .iter()
+        .map(|(k, v)| {
+            (
+                utf8_percent_encode(k, &AWS_QUERY_ENCODE_SET).to_string(),
+                utf8_percent_encode(v, &AWS_QUERY_ENCODE_SET).to_string(),
+            )
+        })
+        .collect();
+
+    Ok(())
+}
+
+/// Derive the ECDSA P-256 signing key from access key pair.
+///
+/// The derivation is HMAC-SHA256 in counter mode (NIST SP 800-108) with
+/// `AWS4A{secret}` as key. The output is rejected and regenerated with a
+/// larger counter until it's no greater than `n - 2`, and the private key
+/// is `output + 1`.
+fn generate_signing_key(access_key_id: &str, secret: &str) -> Result<SigningKey> {
+    let input_key = format!("AWS4A{secret}");
+
+    for counter in 1..=254u8 {
+        let mut fixed_input = Vec::with_capacity(64);
+        // Iteration of the counter mode, always 1 for 256 bits output.
+        fixed_input.extend_from_slice(&1u32.to_be_bytes());
+        fixed_input.extend_from_slice(ALGORITHM.as_bytes());
+        fixed_input.push(0x00);
+        fixed_input.extend_from_slice(access_key_id.as_bytes());
+        fixed_input.push(counter);
+        // Length of output in bits.
+        fixed_input.extend_from_slice(&256u32.to_be_bytes());
+
+        let mut k = hmac_sha256(input_key.as_bytes(), &fixed_input);
+        if k.as_slice() > P256_N_MINUS_TWO.as_slice() {
+            continue;
+        }
+
+        // k + 1 will never overflow since k <= n - 2.
+        for b in k.iter_mut().rev() {
+            let (v, overflow) = b.overflowing_add(1);
+            *b = v;
+            if !overflow {
+                break;
+            }
+        }
+
+        return Ok(SigningKey::from_slice(&k)?);
+    }
+
+    Err(anyhow!("failed to derive sigv4a signing key"))
+}
+
+#[cfg(test)]
+mod tests {
+    use p256::ecdsa::signature::Verifier;
+    use p256::ecdsa::VerifyingKey;
+
+    use super::*;
+
+    fn test_credential() -> Credential {
+        Credential {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            session_token: None,
+            expires_in: None,
+        }
+    }
+
+    fn test_time() -> DateTime {
+        chrono::DateTime::parse_from_rfc2822("Sun, 30 Aug 2015 12:36:00 GMT")
+            .unwrap()
+            .with_timezone(&chrono::Utc)
+    }
+
+    fn test_request() -> http::Request<&'static str> {
+        let mut req = http::Request::new("");
+        *req.uri_mut() = "https://example.amazonaws.com/".parse().unwrap();
+        req
+    }
+
+    #[test]
+    fn test_generate_signing_key() -> Result<()> {
+        let cred = test_credential();
+        let key = generate_signing_key(&cred.access_key_id, &cred.secret_access_key)?;
+
+        // Public key from the sigv4a test suite in aws-c-auth.
+        let point = key.verifying_key().to_encoded_point(false);
+        assert_eq!(
+            hex::encode(point.x().unwrap()),
+            "b6618f6a65740a99e650b33b6b4b5bd0d43b176d721a3edfea7e7d2d56d936b1"
+        );
+        assert_eq!(
+            hex::encode(point.y().unwrap()),
+            "865ed22a7eadc9c5cb9d2cbaca1b3699139fedc5043dc6661864218330c8e518"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_get_vanilla() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let signer = Signer::new("service", "us-east-1");
+
+        // `get-vanilla` from the sigv4a test suite in aws-c-auth, which
+        // doesn't sign `x-amz-content-sha256`.
+        let mut req = test_request();
+        req.headers_mut()
+            .insert(http::header::HOST, "example.amazonaws.com".parse()?);
+        req.headers_mut()
+            .insert("x-amz-date", "20150830T123600Z".parse()?);
+        req.headers_mut()
+            .insert(X_AMZ_REGION_SET, "us-east-1".parse()?);
+        let mut ctx = req.build()?;
+        let creq = canonical_request_string(
+            &mut ctx,
+            default_double_uri_encode(&signer.service),
+            default_normalize_path(&signer.service),
+            &default_payload_hash(&signer.service),
+            &HeaderFilter::default(),
+        )?;
+        assert_eq!(
+            creq,
+            "GET\n/\n\nhost:example.amazonaws.com\nx-amz-date:20150830T123600Z\nx-amz-region-set:us-east-1\n\nhost;x-amz-date;x-amz-region-set\ne3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            signer.string_to_sign(&creq, test_time())?,
+            "AWS4-ECDSA-P256-SHA256\n20150830T123600Z\n20150830/service/aws4_request\ncf59db423e841c8b7e3444158185aa261b724a5c27cbe762676f3eed19f4dc02"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let cred = test_credential();
+        let signer = Signer::new("service", "us-east-1").with_time(test_time());
+
+        let mut req = test_request();
+        signer.sign(&mut req, &cred)?;
+
+        assert_eq!(req.headers()["x-amz-region-set"], "us-east-1");
+        assert_eq!(req.headers()["x-amz-date"], "20150830T123600Z");
+
+        let authorization = req.headers()["authorization"].to_str()?;
+        let prefix = "AWS4-ECDSA-P256-SHA256 Credential=AKIDEXAMPLE/20150830/service/aws4_request, SignedHeaders=host;x-amz-content-sha256;x-amz-date;x-amz-region-set, Signature=";
+        assert!(authorization.starts_with(prefix), "{authorization}");
+        let signature = &authorization[prefix.len()..];
+
+        // Same as `get-vanilla` except `x-amz-content-sha256` that the
+        // signer always inserts.
+        let mut ctx = test_request().build()?;
+        let creq = signer.canonical_request(&mut ctx, SigningMethod::Header, &cred, test_time())?;
+        assert_eq!(
+            creq,
+            "GET\n/\n\nhost:example.amazonaws.com\nx-amz-content-sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855\nx-amz-date:20150830T123600Z\nx-amz-region-set:us-east-1\n\nhost;x-amz-content-sha256;x-amz-date;x-amz-region-set\ne3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        let string_to_sign = "AWS4-ECDSA-P256-SHA256\n20150830T123600Z\n20150830/service/aws4_request\nf2031703ad366b66876a01b1166e0aafdeaf4a7c0bfaf947e250ed9a164a3b72";
+        assert_eq!(signer.string_to_sign(&creq, test_time())?, string_to_sign);
+
+        // ECDSA signatures are not stable, verify it with the public key instead.
+        let key = generate_signing_key(&cred.access_key_id, &cred.secret_access_key)?;
+        let verifier = VerifyingKey::from(&key);
+        let signature = Signature::from_der(&hex::decode(signature)?)?;
+        verifier.verify(string_to_sign.as_bytes(), &signature)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_query() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let mut cred = test_credential();
+        cred.session_token = Some("security_token".to_string());
+        let signer = Signer::new("s3", "us-east-1,us-west-2").with_time(test_time());
+
+        let mut req = test_request();
+        signer.sign_query(&mut req, Duration::from_secs(3600), &cred)?;
+
+        let query = req.uri().query().unwrap();
+        assert!(query.contains("X-Amz-Algorithm=AWS4-ECDSA-P256-SHA256"));
+        assert!(query.contains("X-Amz-Credential=AKIDEXAMPLE%2F20150830%2Fs3%2Faws4_request"));
+        assert!(query.contains("X-Amz-Region-Set=us-east-1%2Cus-west-2"));
+        assert!(query.contains("X-Amz-Security-Token=security_token"));
+        assert!(query.contains("X-Amz-Signature="));
+        assert!(req.headers().get("authorization").is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_query_with_expire_exceeds_7_days() {
+        let cred = test_credential();
+        let signer = Signer::new("s3", "*").with_time(test_time());
+
+        let mut req = test_request();
+        let err = signer
+            .sign_query(&mut req, Duration::from_secs(8 * 24 * 60 * 60), &cred)
+            .expect_err("must fail for 8 days");
+        assert!(err.to_string().contains("must be <= 7 days"), "{err}");
+
+        let mut req = test_request();
+        signer
+            .sign_query(&mut req, Duration::from_secs(7 * 24 * 60 * 60), &cred)
+            .expect("must succeed for 7 days");
+        assert!(req.uri().query().unwrap().contains("X-Amz-Expires=604800"));
+    }
+}
@@ -5,3 +5,19 @@ pub const CONTENT_MD5: &str = "content-md5";
 
 // Env values used in azure services.
 pub const AZURE_VERSION: &str = "2019-12-12";
+pub const AZURE_IMDS_ENDPOINT: &str = "AZURE_IMDS_ENDPOINT";
+pub const AZURE_STORAGE_ACCOUNT_NAME: &str = "AZURE_STORAGE_ACCOUNT_NAME";
+pub const AZURE_STORAGE_ACCOUNT_KEY: &str = "AZURE_STORAGE_ACCOUNT_KEY";
+pub const AZURE_STORAGE_SAS_TOKEN: &str = "AZURE_STORAGE_SAS_TOKEN";
+pub const AZURE_STORAGE_CONNECTION_STRING: &str = "AZURE_STORAGE_CONNECTION_STRING";
+
+// Well-known credentials of Azurite, the local storage emulator.
+pub const AZURITE_ACCOUNT_NAME: &str = "devstoreaccount1";
+pub const AZURITE_ACCOUNT_KEY: &str =
+    "Eby8vdM02xNOcqFlqUwJPLlmEtlCDXJ1OUzFT50uSRZ6IFsuFq2UVErCz4I6tq/K1SZFPTOtr/KBHBeksoGMGw==";
+
+// Values used in azure instance metadata service.
+pub const AZURE_IMDS_DEFAULT_ENDPOINT: &str =
+    "http://169.254.169.254/metadata/identity/oauth2/token";
+pub const AZURE_IMDS_API_VERSION: &str = "2018-02-01";
+pub const AZURE_STORAGE_RESOURCE: &str = "https://storage.azure.com/";
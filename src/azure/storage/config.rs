@@ -1,3 +1,13 @@
+use std::collections::HashMap;
+use std::env;
+use std::time::Duration;
+
+use log::debug;
+
+use super::super::constants::*;
+use crate::Error;
+use crate::Result;
+
 /// Config carries all the configuration for Azure Storage services.
 #[derive(Clone, Default)]
 #[cfg_attr(test, derive(Debug))]
@@ -5,13 +15,293 @@ pub struct Config {
     /// `account_name` will be loaded from
     ///
     /// - this field if it's `is_some`
+    /// - env value: [`AZURE_STORAGE_ACCOUNT_NAME`]
+    /// - env value: [`AZURE_STORAGE_CONNECTION_STRING`]
     pub account_name: Option<String>,
     /// `account_key` will be loaded from
     ///
     /// - this field if it's `is_some`
+    /// - env value: [`AZURE_STORAGE_ACCOUNT_KEY`]
+    /// - env value: [`AZURE_STORAGE_CONNECTION_STRING`]
     pub account_key: Option<String>,
     /// `sas_token` will be loaded from
     ///
     /// - this field if it's `is_some`
+    /// - env value: [`AZURE_STORAGE_SAS_TOKEN`]
+    /// - env value: [`AZURE_STORAGE_CONNECTION_STRING`]
     pub sas_token: Option<String>,
+    /// `client_id` of the user-assigned managed identity will be loaded from
+    ///
+    /// - this field if it's `is_some`
+    ///
+    /// Leave it as `None` to use the system-assigned managed identity.
+    pub client_id: Option<String>,
+    /// `refresh_skew` controls how long before expiry a cached credential
+    /// will be refreshed, will be loaded from
+    ///
+    /// - this field if it's `is_some`
+    /// - default to `120s`
+    pub refresh_skew: Option<Duration>,
+}
+
+impl Config {
+    /// Load config from env.
+    ///
+    /// Fields that are already set will be kept. The connection string in
+    /// [`AZURE_STORAGE_CONNECTION_STRING`] is only used if none of
+    /// `account_name`, `account_key` and `sas_token` is set, so that
+    /// credentials from different sources will never be mixed. Invalid
+    /// connection string will be ignored.
+    pub fn from_env(mut self) -> Self {
+        let envs = env::vars().collect::<HashMap<_, _>>();
+
+        if let Some(v) = envs.get(AZURE_STORAGE_ACCOUNT_NAME) {
+            self.account_name.get_or_insert(v.clone());
+        }
+        if let Some(v) = envs.get(AZURE_STORAGE_ACCOUNT_KEY) {
+            self.account_key.get_or_insert(v.clone());
+        }
+        if let Some(v) = envs.get(AZURE_STORAGE_SAS_TOKEN) {
+            self.sas_token.get_or_insert(v.clone());
+        }
+
+        if self.account_name.is_none() && self.account_key.is_none() && self.sas_token.is_none() {
+            if let Some(v) = envs.get(AZURE_STORAGE_CONNECTION_STRING) {
+                match Config::from_connection_string(v) {
+                    Ok(config) => {
+                        self.account_name = config.account_name;
+                        self.account_key = config.account_key;
+                        self.sas_token = config.sas_token;
+                    }
+                    Err(err) => debug!("load config from connection string failed: {err:?}"),
+                }
+            }
+        }
+
+        self
+    }
+
+    /// Parse config from connection string like
+    /// `AccountName=account;AccountKey=key;EndpointSuffix=core.windows.net`.
+    ///
+    /// - `AccountName` and `AccountKey` will be parsed into `account_name`
+    ///   and `account_key`.
+    /// - `SharedAccessSignature` will be parsed into `sas_token`.
+    /// - `UseDevelopmentStorage=true` will use the well-known Azurite
+    ///   credentials.
+    ///
+    /// Other keys will be ignored.
+    ///
+    /// Reference: [Configure Azure Storage connection strings](https://learn.microsoft.com/en-us/azure/storage/common/storage-configure-connection-string)
+    pub fn from_connection_string(conn: &str) -> Result<Self> {
+        let mut config = Config::default();
+
+        for kv in conn.split(';').map(|v| v.trim()).filter(|v| !v.is_empty()) {
+            // Value could contain `=` like base64 encoded key, only split at the first one.
+            let (k, v) = kv.split_once('=').ok_or_else(|| {
+                Error::InvalidConfig(format!("invalid connection string segment: {kv}"))
+            })?;
+
+            match k {
+                "AccountName" => config.account_name = Some(v.to_string()),
+                "AccountKey" => config.account_key = Some(v.to_string()),
+                "SharedAccessSignature" => config.sas_token = Some(v.to_string()),
+                "UseDevelopmentStorage" if v.eq_ignore_ascii_case("true") => {
+                    config.account_name = Some(AZURITE_ACCOUNT_NAME.to_string());
+                    config.account_key = Some(AZURITE_ACCOUNT_KEY.to_string());
+                }
+                _ => (),
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Check whether fields in config conflict with each other.
+    ///
+    /// Only one way of authorization could be configured:
+    ///
+    /// - shared key: both `account_name` and `account_key`
+    /// - SAS: `sas_token`
+    /// - bearer token via managed identity: none of above, and optional
+    ///   `client_id`
+    ///
+    /// It's called while loading credentials, users can call it eagerly to
+    /// find misconfigurations on startup.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |msg: &str| Err(Error::InvalidConfig(msg.to_string()));
+
+        if self.sas_token.is_some() && self.account_key.is_some() {
+            return invalid("only one of sas_token and account_key can be set");
+        }
+        if self.account_key.is_some() && self.account_name.is_none() {
+            return invalid("account_name is required when account_key is set");
+        }
+        if self.account_name.is_some() && self.account_key.is_none() && self.sas_token.is_none() {
+            return invalid("account_key is required when not using SAS");
+        }
+        if self.client_id.is_some() && (self.account_key.is_some() || self.sas_token.is_some()) {
+            return invalid(
+                "client_id is only used by managed identity, it can't be set together with account_key or sas_token",
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_connection_string() {
+        let config = Config::from_connection_string(
+            "DefaultEndpointsProtocol=https;AccountName=account;AccountKey=a2V5==;EndpointSuffix=core.windows.net",
+        )
+        .expect("connection string must be valid");
+        assert_eq!(config.account_name.as_deref(), Some("account"));
+        assert_eq!(config.account_key.as_deref(), Some("a2V5=="));
+        assert!(config.sas_token.is_none());
+
+        let config = Config::from_connection_string(
+            "BlobEndpoint=https://account.blob.core.windows.net/;SharedAccessSignature=sv=2021-01-01&sig=abc%3D",
+        )
+        .expect("connection string must be valid");
+        assert!(config.account_name.is_none());
+        assert_eq!(
+            config.sas_token.as_deref(),
+            Some("sv=2021-01-01&sig=abc%3D")
+        );
+
+        let config = Config::from_connection_string("UseDevelopmentStorage=true;")
+            .expect("connection string must be valid");
+        assert_eq!(config.account_name.as_deref(), Some(AZURITE_ACCOUNT_NAME));
+        assert_eq!(config.account_key.as_deref(), Some(AZURITE_ACCOUNT_KEY));
+
+        assert!(Config::from_connection_string("AccountName").is_err());
+    }
+
+    #[test]
+    fn test_from_env() {
+        let envs = [
+            AZURE_STORAGE_ACCOUNT_NAME,
+            AZURE_STORAGE_ACCOUNT_KEY,
+            AZURE_STORAGE_SAS_TOKEN,
+            AZURE_STORAGE_CONNECTION_STRING,
+        ];
+
+        temp_env::with_vars(
+            [
+                (AZURE_STORAGE_ACCOUNT_NAME, Some("account")),
+                (AZURE_STORAGE_ACCOUNT_KEY, Some("a2V5")),
+                (AZURE_STORAGE_SAS_TOKEN, None),
+                (
+                    AZURE_STORAGE_CONNECTION_STRING,
+                    Some("AccountName=other;AccountKey=b3RoZXI="),
+                ),
+            ],
+            || {
+                let config = Config::default().from_env();
+                assert_eq!(config.account_name.as_deref(), Some("account"));
+                assert_eq!(config.account_key.as_deref(), Some("a2V5"));
+                assert!(config.sas_token.is_none());
+
+                // Fields in config take precedence over env.
+                let config = Config {
+                    account_name: Some("config".to_string()),
+                    ..Default::default()
+                }
+                .from_env();
+                assert_eq!(config.account_name.as_deref(), Some("config"));
+                assert_eq!(config.account_key.as_deref(), Some("a2V5"));
+            },
+        );
+
+        temp_env::with_vars(
+            envs.map(|k| match k {
+                AZURE_STORAGE_CONNECTION_STRING => {
+                    (k, Some("SharedAccessSignature=sv=2021-01-01&sig=abc"))
+                }
+                _ => (k, None),
+            }),
+            || {
+                let config = Config::default().from_env();
+                assert!(config.account_name.is_none());
+                assert_eq!(config.sas_token.as_deref(), Some("sv=2021-01-01&sig=abc"));
+
+                // Connection string is ignored if credentials are set.
+                let config = Config {
+                    account_name: Some("account".to_string()),
+                    account_key: Some("a2V5".to_string()),
+                    ..Default::default()
+                }
+                .from_env();
+                assert!(config.sas_token.is_none());
+            },
+        );
+
+        temp_env::with_vars(
+            envs.map(|k| match k {
+                AZURE_STORAGE_CONNECTION_STRING => (k, Some("AccountName")),
+                _ => (k, None),
+            }),
+            || {
+                let config = Config::default().from_env();
+                assert!(config.account_name.is_none());
+                assert!(config.sas_token.is_none());
+            },
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = |account_name: Option<&str>,
+                      account_key: Option<&str>,
+                      sas_token: Option<&str>,
+                      client_id: Option<&str>| Config {
+            account_name: account_name.map(|v| v.to_string()),
+            account_key: account_key.map(|v| v.to_string()),
+            sas_token: sas_token.map(|v| v.to_string()),
+            client_id: client_id.map(|v| v.to_string()),
+            ..Default::default()
+        };
+
+        for valid in [
+            config(Some("account"), Some("key"), None, None),
+            config(None, None, Some("sas"), None),
+            config(Some("account"), None, Some("sas"), None),
+            config(None, None, None, None),
+            config(None, None, None, Some("client_id")),
+        ] {
+            assert!(valid.validate().is_ok(), "{valid:?}");
+        }
+
+        for (invalid, msg) in [
+            (
+                config(Some("account"), Some("key"), Some("sas"), None),
+                "only one of sas_token and account_key can be set",
+            ),
+            (
+                config(None, Some("key"), None, None),
+                "account_name is required when account_key is set",
+            ),
+            (
+                config(Some("account"), None, None, None),
+                "account_key is required when not using SAS",
+            ),
+            (
+                config(Some("account"), Some("key"), None, Some("client_id")),
+                "client_id is only used by managed identity",
+            ),
+            (
+                config(None, None, Some("sas"), Some("client_id")),
+                "client_id is only used by managed identity",
+            ),
+        ] {
+            let err = invalid.validate().expect_err("config must be invalid");
+            assert!(matches!(err, Error::InvalidConfig(_)), "{err:?}");
+            assert!(err.to_string().contains(msg), "{err}");
+        }
+    }
 }
@@ -10,4 +10,8 @@ pub enum Credential {
     ///
     /// Refer to <https://learn.microsoft.com/en-us/rest/api/storageservices/create-account-sas>
     SharedAccessSignature(String),
+    /// Credential via OAuth2 bearer token
+    ///
+    /// Refer to <https://learn.microsoft.com/en-us/rest/api/storageservices/authorize-with-azure-active-directory>
+    BearerToken(String),
 }
@@ -1,63 +1,389 @@
+use std::env;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Duration;
 
+use anyhow::anyhow;
 use anyhow::Result;
+use async_trait::async_trait;
+use chrono::TimeZone;
+use chrono::Utc;
+use log::debug;
+use serde::Deserialize;
 
+use super::super::constants::*;
 use super::config::Config;
 use super::credential::Credential;
+use crate::http_send::check_available;
+use crate::http_send::HttpSend;
+use crate::loader::CredentialLoad;
+use crate::time::now;
+use crate::time::DateTime;
+use crate::trace::credential_refreshed;
+use crate::trace::record_cache_hit;
 
 /// Loader will load credential from different methods.
 #[cfg_attr(test, derive(Debug))]
 pub struct Loader {
+    client: Arc<dyn HttpSend>,
     config: Config,
 
-    credential: Arc<Mutex<Option<Credential>>>,
+    credential: Arc<Mutex<Option<(Credential, DateTime)>>>,
+    refresh_lock: Arc<tokio::sync::Mutex<()>>,
 }
 
 impl Loader {
-    /// Create a new loader via config.
-    pub fn new(config: Config) -> Self {
+    /// Create a new loader via client and config.
+    pub fn new(client: impl HttpSend, config: Config) -> Self {
         Self {
+            client: Arc::new(client),
             config,
 
             credential: Arc::default(),
+            refresh_lock: Arc::default(),
         }
     }
 
     /// Load credential.
-    pub async fn load(&self) -> Result<Option<Credential>> {
-        // Return cached credential if it's valid.
-        if let Some(cred) = self.credential.lock().expect("lock poisoned").clone() {
+    ///
+    /// Resolution order:
+    /// 1. SAS token in config
+    /// 2. Account name and key in config
+    /// 3. Managed identity via Azure Instance Metadata Service (IMDS)
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "reqsign::load",
+            level = "debug",
+            skip_all,
+            fields(provider = "azure", cache_hit = tracing::field::Empty)
+        )
+    )]
+    pub async fn load(&self) -> crate::Result<Option<Credential>> {
+        let skew = chrono::Duration::from_std(
+            self.config
+                .refresh_skew
+                .unwrap_or_else(|| Duration::from_secs(120)),
+        )
+        .map_err(anyhow::Error::from)?;
+
+        if let Some(cred) = self.cached(skew) {
+            record_cache_hit(true);
+            return Ok(Some(cred));
+        }
+
+        // Only one task loads credential at the same time, others will wait
+        // and reuse its result.
+        let _guard = self.refresh_lock.lock().await;
+        if let Some(cred) = self.cached(skew) {
+            record_cache_hit(true);
             return Ok(Some(cred));
         }
+        record_cache_hit(false);
 
-        let cred = self.load_inner().await?;
+        let (cred, expires_on) = if let Some(v) = self.load_inner().await? {
+            v
+        } else {
+            return Ok(None);
+        };
+        credential_refreshed("azure", Some(expires_on));
 
         let mut lock = self.credential.lock().expect("lock poisoned");
-        *lock = cred.clone();
+        *lock = Some((cred.clone(), expires_on));
 
-        Ok(cred)
+        Ok(Some(cred))
     }
 
-    async fn load_inner(&self) -> Result<Option<Credential>> {
+    /// Return cached credential if it will not expire within `skew`.
+    fn cached(&self, skew: chrono::Duration) -> Option<Credential> {
+        match self.credential.lock().expect("lock poisoned").clone() {
+            Some((cred, expires_on)) if now() < expires_on - skew => Some(cred),
+            _ => None,
+        }
+    }
+
+    async fn load_inner(&self) -> Result<Option<(Credential, DateTime)>> {
         if let Some(cred) = self.load_via_config()? {
             return Ok(Some(cred));
         }
 
+        if let Ok(Some(cred)) = self
+            .load_via_imds()
+            .await
+            .map_err(|err| debug!("load credential via imds failed: {err:?}"))
+        {
+            return Ok(Some(cred));
+        }
+
         Ok(None)
     }
 
-    fn load_via_config(&self) -> Result<Option<Credential>> {
+    fn load_via_config(&self) -> Result<Option<(Credential, DateTime)>> {
+        self.config.validate()?;
+
+        // Credentials from config never expire.
+        let expires_on = DateTime::MAX_UTC;
+
         if let Some(token) = &self.config.sas_token {
             let cred = Credential::SharedAccessSignature(token.clone());
-            return Ok(Some(cred));
+            return Ok(Some((cred, expires_on)));
         }
 
         if let (Some(ak), Some(sk)) = (&self.config.account_name, &self.config.account_key) {
             let cred = Credential::SharedKey(ak.clone(), sk.clone());
-            return Ok(Some(cred));
+            return Ok(Some((cred, expires_on)));
         }
 
         Ok(None)
     }
+
+    /// Load bearer token via Azure Instance Metadata Service.
+    ///
+    /// The endpoint can be overridden by env [`AZURE_IMDS_ENDPOINT`].
+    ///
+    /// Reference: [How to use managed identities for Azure resources on an Azure VM to acquire an access token](https://learn.microsoft.com/en-us/azure/active-directory/managed-identities-azure-resources/how-to-use-vm-token)
+    async fn load_via_imds(&self) -> Result<Option<(Credential, DateTime)>> {
+        let endpoint =
+            env::var(AZURE_IMDS_ENDPOINT).unwrap_or_else(|_| AZURE_IMDS_DEFAULT_ENDPOINT.into());
+
+        let query = {
+            let mut query = form_urlencoded::Serializer::new(String::new());
+            query.append_pair("api-version", AZURE_IMDS_API_VERSION);
+            query.append_pair("resource", AZURE_STORAGE_RESOURCE);
+            if let Some(client_id) = &self.config.client_id {
+                query.append_pair("client_id", client_id);
+            }
+            query.finish()
+        };
+
+        let req = http::Request::get(format!("{endpoint}?{query}"))
+            .header("Metadata", "true")
+            .body(Vec::new())?;
+
+        let resp = check_available(self.client.send(req).await)?;
+        if resp.status() != http::StatusCode::OK {
+            let content = String::from_utf8_lossy(resp.body());
+            return Err(anyhow!(
+                "request to Azure Instance Metadata Service failed: {content}"
+            ));
+        }
+
+        let resp: ImdsTokenResponse = serde_json::from_slice(resp.body())?;
+        let expires_on = Utc
+            .timestamp_opt(resp.expires_on.parse()?, 0)
+            .single()
+            .ok_or_else(|| anyhow!("invalid expires_on: {}", resp.expires_on))?;
+
+        Ok(Some((
+            Credential::BearerToken(resp.access_token),
+            expires_on,
+        )))
+    }
+}
+
+#[async_trait]
+impl CredentialLoad<Credential> for Loader {
+    async fn load(&self) -> crate::Result<Option<Credential>> {
+        Loader::load(self).await
+    }
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+struct ImdsTokenResponse {
+    access_token: String,
+    expires_on: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    use once_cell::sync::Lazy;
+    use reqwest::Client;
+    use tokio::io::AsyncReadExt;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+    use tokio::runtime::Runtime;
+
+    use super::*;
+    use crate::time::set_fake_now;
+
+    static RUNTIME: Lazy<Runtime> = Lazy::new(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .expect("Should create a tokio runtime")
+    });
+
+    /// Serve a fake IMDS that returns `token_{n}` for the n-th request.
+    ///
+    /// Returns the endpoint and the counter of served requests.
+    async fn serve_imds(expires_on: DateTime) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let count = Arc::new(AtomicUsize::new(0));
+
+        let served = count.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0; 4096];
+                let n = stream.read(&mut buf).await.unwrap();
+                let req = String::from_utf8_lossy(&buf[..n]).to_string();
+                assert!(req.contains("metadata: true"));
+                assert!(req.contains("resource=https%3A%2F%2Fstorage.azure.com%2F"));
+
+                let idx = served.fetch_add(1, Ordering::SeqCst);
+                let body = format!(
+                    r#"{{"access_token":"token_{idx}","expires_in":"3599","expires_on":"{}","resource":"https://storage.azure.com/","token_type":"Bearer"}}"#,
+                    expires_on.timestamp()
+                );
+                let resp = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                stream.write_all(resp.as_bytes()).await.unwrap();
+            }
+        });
+
+        (
+            format!("http://{addr}/metadata/identity/oauth2/token"),
+            count,
+        )
+    }
+
+    fn assert_bearer_token(cred: Credential, expected: &str) {
+        match cred {
+            Credential::BearerToken(token) => assert_eq!(token, expected),
+            _ => panic!("credential must be bearer token"),
+        }
+    }
+
+    #[test]
+    fn test_credential_env_loader() {
+        temp_env::with_vars(
+            vec![
+                (AZURE_STORAGE_ACCOUNT_NAME, Some("account")),
+                (AZURE_STORAGE_ACCOUNT_KEY, Some("a2V5")),
+                (AZURE_STORAGE_SAS_TOKEN, None),
+            ],
+            || {
+                RUNTIME.block_on(async {
+                    let l = Loader::new(Client::new(), Config::default().from_env());
+                    match l.load().await.expect("load must succeed").unwrap() {
+                        Credential::SharedKey(name, key) => {
+                            assert_eq!(name, "account");
+                            assert_eq!(key, "a2V5");
+                        }
+                        _ => panic!("credential must be shared key"),
+                    }
+                })
+            },
+        );
+
+        temp_env::with_vars(
+            vec![
+                (AZURE_STORAGE_ACCOUNT_NAME, None),
+                (AZURE_STORAGE_ACCOUNT_KEY, None),
+                (AZURE_STORAGE_SAS_TOKEN, None),
+                (
+                    AZURE_STORAGE_CONNECTION_STRING,
+                    Some("BlobEndpoint=https://account.blob.core.windows.net/;SharedAccessSignature=sv=2021-01-01&sig=abc"),
+                ),
+            ],
+            || {
+                RUNTIME.block_on(async {
+                    let l = Loader::new(Client::new(), Config::default().from_env());
+                    match l.load().await.expect("load must succeed").unwrap() {
+                        Credential::SharedAccessSignature(token) => {
+                            assert_eq!(token, "sv=2021-01-01&sig=abc")
+                        }
+                        _ => panic!("credential must be sas token"),
+                    }
+                })
+            },
+        );
+    }
+
+    #[test]
+    fn test_credential_imds_loader() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let (endpoint, _) = RUNTIME.block_on(serve_imds(now() + chrono::Duration::hours(1)));
+
+        temp_env::with_vars(vec![(AZURE_IMDS_ENDPOINT, Some(endpoint))], || {
+            RUNTIME.block_on(async {
+                let l = Loader::new(Client::new(), Config::default());
+                let x = l.load().await.expect("load must succeed").unwrap();
+
+                assert_bearer_token(x, "token_0");
+            })
+        });
+    }
+
+    #[test]
+    fn test_credential_imds_loader_cached() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let start = Utc.with_ymd_and_hms(2022, 3, 1, 8, 0, 0).unwrap();
+        let expires_on = start + chrono::Duration::hours(1);
+        let (endpoint, count) = RUNTIME.block_on(serve_imds(expires_on));
+
+        temp_env::with_vars(vec![(AZURE_IMDS_ENDPOINT, Some(endpoint))], || {
+            RUNTIME.block_on(async {
+                let l = Loader::new(Client::new(), Config::default());
+
+                set_fake_now(Some(start));
+                let x = l.load().await.expect("load must succeed").unwrap();
+                assert_bearer_token(x, "token_0");
+                assert_eq!(count.load(Ordering::SeqCst), 1);
+
+                // Still outside the refresh window, cached token should be used.
+                set_fake_now(Some(expires_on - chrono::Duration::minutes(30)));
+                let x = l.load().await.expect("load must succeed").unwrap();
+                assert_bearer_token(x, "token_0");
+                assert_eq!(count.load(Ordering::SeqCst), 1);
+
+                // Within 120s of expiry, token should be refreshed.
+                set_fake_now(Some(expires_on - chrono::Duration::seconds(60)));
+                let x = l.load().await.expect("load must succeed").unwrap();
+                assert_bearer_token(x, "token_1");
+                assert_eq!(count.load(Ordering::SeqCst), 2);
+
+                set_fake_now(None);
+            })
+        });
+    }
+
+    #[test]
+    fn test_credential_imds_loader_with_refresh_skew() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let start = Utc.with_ymd_and_hms(2022, 3, 1, 8, 0, 0).unwrap();
+        let expires_on = start + chrono::Duration::hours(1);
+        let (endpoint, count) = RUNTIME.block_on(serve_imds(expires_on));
+
+        temp_env::with_vars(vec![(AZURE_IMDS_ENDPOINT, Some(endpoint))], || {
+            RUNTIME.block_on(async {
+                let l = Loader::new(
+                    Client::new(),
+                    Config {
+                        refresh_skew: Some(Duration::from_secs(3600)),
+                        ..Default::default()
+                    },
+                );
+
+                set_fake_now(Some(start + chrono::Duration::minutes(1)));
+                l.load().await.expect("load must succeed").unwrap();
+                l.load().await.expect("load must succeed").unwrap();
+                // Token is always within the skew, every load should refresh.
+                assert_eq!(count.load(Ordering::SeqCst), 2);
+
+                set_fake_now(None);
+            })
+        });
+    }
 }
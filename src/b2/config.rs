@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+use std::env;
+
+use super::constants::*;
+use crate::error::required_together;
+
+/// Config carries all the configuration for Backblaze B2 services.
+#[derive(Clone)]
+#[cfg_attr(test, derive(Debug))]
+pub struct Config {
+    /// `key_id` will be loaded from:
+    ///
+    /// - this field if it's `is_some`
+    /// - env value: [`B2_APPLICATION_KEY_ID`]
+    pub key_id: Option<String>,
+    /// `application_key` will be loaded from:
+    ///
+    /// - this field if it's `is_some`
+    /// - env value: [`B2_APPLICATION_KEY`]
+    pub application_key: Option<String>,
+    /// `endpoint` that `b2_authorize_account` will be sent to.
+    ///
+    /// Default to `https://api.backblazeb2.com`, requests to B2 native API
+    /// will be sent to the `apiUrl` returned by it.
+    pub endpoint: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            key_id: None,
+            application_key: None,
+            endpoint: B2_DEFAULT_ENDPOINT.to_string(),
+        }
+    }
+}
+
+impl Config {
+    /// Load config from env.
+    pub fn from_env(mut self) -> Self {
+        let envs = env::vars().collect::<HashMap<_, _>>();
+
+        if let Some(v) = envs.get(B2_APPLICATION_KEY_ID) {
+            self.key_id.get_or_insert(v.clone());
+        }
+        if let Some(v) = envs.get(B2_APPLICATION_KEY) {
+            self.application_key.get_or_insert(v.clone());
+        }
+
+        self
+    }
+
+    /// Check that `key_id` and `application_key` are set together.
+    ///
+    /// It's called while loading credentials, users can call it eagerly to
+    /// find misconfigurations on startup.
+    pub fn validate(&self) -> crate::Result<()> {
+        required_together(&[
+            ("key_id", self.key_id.is_some()),
+            ("application_key", self.application_key.is_some()),
+        ])
+    }
+}
@@ -0,0 +1,18 @@
+use percent_encoding::AsciiSet;
+use percent_encoding::NON_ALPHANUMERIC;
+
+// Env values used in backblaze b2 services.
+pub const B2_APPLICATION_KEY_ID: &str = "B2_APPLICATION_KEY_ID";
+pub const B2_APPLICATION_KEY: &str = "B2_APPLICATION_KEY";
+
+/// The endpoint that all accounts are authorized against.
+pub const B2_DEFAULT_ENDPOINT: &str = "https://api.backblazeb2.com";
+
+/// AsciiSet for encoding query back after signing.
+///
+/// - URI encode every byte except the unreserved characters: 'A'-'Z', 'a'-'z', '0'-'9', '-', '.', '_', and '~'.
+pub static B2_QUERY_ENCODE_SET: AsciiSet = NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
@@ -0,0 +1,358 @@
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use async_trait::async_trait;
+use http::header::AUTHORIZATION;
+use http::HeaderValue;
+use serde::Deserialize;
+
+use super::config::Config;
+use crate::hash::base64_encode;
+use crate::http_send::check_available;
+use crate::http_send::HttpSend;
+use crate::loader::CredentialLoad;
+use crate::time::now;
+use crate::time::DateTime;
+use crate::trace::credential_refreshed;
+use crate::trace::record_cache_hit;
+use crate::Error;
+
+/// Credential returned by `b2_authorize_account`.
+#[derive(Default, Clone)]
+#[cfg_attr(test, derive(Debug))]
+pub struct Credential {
+    /// The identifier of the account.
+    pub account_id: String,
+    /// Authorization token used in `Authorization` header of B2 native API.
+    pub authorization_token: String,
+    /// The base URL that B2 native API except downloading is sent to.
+    pub api_url: String,
+    /// The base URL that downloading files is sent to.
+    pub download_url: String,
+    /// expires in for credential.
+    pub expires_in: Option<DateTime>,
+}
+
+impl Credential {
+    /// is current cred is valid?
+    pub fn is_valid(&self) -> bool {
+        if self.authorization_token.is_empty() {
+            return false;
+        }
+        // Take 120s as buffer to avoid edge cases.
+        if let Some(valid) = self
+            .expires_in
+            .map(|v| v > now() + chrono::Duration::minutes(2))
+        {
+            return valid;
+        }
+
+        true
+    }
+
+    /// The time when this credential will be expired.
+    pub fn expires_at(&self) -> Option<DateTime> {
+        self.expires_in
+    }
+
+    /// Whether this credential has been expired.
+    pub fn is_expired(&self) -> bool {
+        self.expires_in.is_some_and(|v| v <= now())
+    }
+}
+
+#[cfg(feature = "refresher")]
+impl crate::CredentialExpiry for Credential {
+    fn expires_at(&self) -> Option<DateTime> {
+        self.expires_in
+    }
+}
+
+/// Loader will load credential via `b2_authorize_account`.
+#[cfg_attr(test, derive(Debug))]
+pub struct Loader {
+    client: Arc<dyn HttpSend>,
+    config: Config,
+
+    credential: Arc<Mutex<Option<Credential>>>,
+    refresh_lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl Loader {
+    /// Create a new loader via client and config.
+    pub fn new(client: impl HttpSend, config: Config) -> Self {
+        Self {
+            client: Arc::new(client),
+            config,
+
+            credential: Arc::default(),
+            refresh_lock: Arc::default(),
+        }
+    }
+
+    /// Load credential.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "reqsign::load",
+            level = "debug",
+            skip_all,
+            fields(provider = "b2", cache_hit = tracing::field::Empty)
+        )
+    )]
+    pub async fn load(&self) -> crate::Result<Option<Credential>> {
+        if let Some(cred) = self.cached() {
+            record_cache_hit(true);
+            return Ok(Some(cred));
+        }
+
+        // Only one task loads credential at the same time, others will wait
+        // and reuse its result.
+        let _guard = self.refresh_lock.lock().await;
+        if let Some(cred) = self.cached() {
+            record_cache_hit(true);
+            return Ok(Some(cred));
+        }
+        record_cache_hit(false);
+
+        let cred = if let Some(cred) = self.load_inner().await? {
+            cred
+        } else {
+            return Ok(None);
+        };
+        credential_refreshed("b2", cred.expires_in);
+
+        let mut lock = self.credential.lock().expect("lock poisoned");
+        *lock = Some(cred.clone());
+
+        Ok(Some(cred))
+    }
+
+    /// Return cached credential if it's valid.
+    fn cached(&self) -> Option<Credential> {
+        self.credential
+            .lock()
+            .expect("lock poisoned")
+            .clone()
+            .filter(|cred| cred.is_valid())
+    }
+
+    async fn load_inner(&self) -> Result<Option<Credential>> {
+        self.load_via_authorize_account().await
+    }
+
+    /// Authorize account via application key.
+    ///
+    /// Reference: [b2_authorize_account](https://www.backblaze.com/apidocs/b2-authorize-account)
+    async fn load_via_authorize_account(&self) -> Result<Option<Credential>> {
+        self.config.validate()?;
+
+        let (key_id, application_key) = match (&self.config.key_id, &self.config.application_key) {
+            (Some(key_id), Some(application_key)) => (key_id, application_key),
+            _ => return Ok(None),
+        };
+
+        let url = format!(
+            "{}/b2api/v2/b2_authorize_account",
+            self.config.endpoint.trim_end_matches('/')
+        );
+        let req = http::Request::get(&url)
+            .header(AUTHORIZATION, {
+                let mut value: HeaderValue = format!(
+                    "Basic {}",
+                    base64_encode(format!("{key_id}:{application_key}").as_bytes())
+                )
+                .parse()?;
+                value.set_sensitive(true);
+
+                value
+            })
+            .body(Vec::new())?;
+
+        let resp = check_available(self.client.send(req).await)?;
+        if resp.status() != http::StatusCode::OK {
+            return Err(authorize_account_error(&resp));
+        }
+
+        let resp: AuthorizeAccountResponse = serde_json::from_slice(resp.body())?;
+        let cred = Credential {
+            account_id: resp.account_id,
+            authorization_token: resp.authorization_token,
+            api_url: resp.api_url,
+            download_url: resp.download_url,
+            // Authorization token is valid for at most 24 hours.
+            expires_in: Some(now() + chrono::Duration::hours(24)),
+        };
+
+        Ok(Some(cred))
+    }
+}
+
+#[async_trait]
+impl CredentialLoad<Credential> for Loader {
+    async fn load(&self) -> crate::Result<Option<Credential>> {
+        Loader::load(self).await
+    }
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct AuthorizeAccountResponse {
+    account_id: String,
+    authorization_token: String,
+    api_url: String,
+    download_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    code: String,
+    message: Option<String>,
+}
+
+/// Convert the failed response of `b2_authorize_account` into error.
+///
+/// Responses in B2 error format like `bad_auth_token` will be
+/// [`Error::CredentialRejected`], others will be kept as unexpected errors.
+fn authorize_account_error(resp: &http::Response<Vec<u8>>) -> anyhow::Error {
+    match serde_json::from_slice::<ErrorResponse>(resp.body()) {
+        Ok(err) => Error::CredentialRejected {
+            status: resp.status(),
+            error: err.code,
+            description: err.message,
+        }
+        .into(),
+        Err(_) => anyhow!(
+            "b2_authorize_account failed with {}: {}",
+            resp.status(),
+            String::from_utf8_lossy(resp.body())
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct MockAuthorize {
+        requests: Mutex<Vec<http::Request<Vec<u8>>>>,
+    }
+
+    #[async_trait]
+    impl HttpSend for MockAuthorize {
+        async fn send(&self, req: http::Request<Vec<u8>>) -> Result<http::Response<Vec<u8>>> {
+            let authorized = req.headers()[AUTHORIZATION]
+                == format!("Basic {}", base64_encode(b"key_id:application_key"));
+            self.requests.lock().unwrap().push(req);
+
+            let resp = if authorized {
+                http::Response::builder().status(StatusCode::OK).body(
+                    br#"{"accountId":"account_id","authorizationToken":"auth_token","apiUrl":"https://api001.backblazeb2.com","downloadUrl":"https://f001.backblazeb2.com","recommendedPartSize":100000000}"#.to_vec(),
+                )?
+            } else {
+                http::Response::builder()
+                    .status(StatusCode::UNAUTHORIZED)
+                    .body(
+                        br#"{"code":"bad_auth_token","message":"Invalid authorization token","status":401}"#
+                            .to_vec(),
+                    )?
+            };
+            Ok(resp)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_load_via_authorize_account() -> Result<()> {
+        let client = Arc::new(MockAuthorize {
+            requests: Mutex::default(),
+        });
+        let loader = Loader::new(
+            client.clone(),
+            Config {
+                key_id: Some("key_id".to_string()),
+                application_key: Some("application_key".to_string()),
+                endpoint: "http://127.0.0.1:8080/".to_string(),
+            },
+        );
+
+        let cred = loader.load().await?.expect("credential must be loaded");
+        assert_eq!(cred.account_id, "account_id");
+        assert_eq!(cred.authorization_token, "auth_token");
+        assert_eq!(cred.api_url, "https://api001.backblazeb2.com");
+        assert_eq!(cred.download_url, "https://f001.backblazeb2.com");
+        assert!(cred.is_valid());
+
+        // Credential must be cached.
+        loader.load().await?.expect("credential must be loaded");
+        let requests = client.requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(
+            requests[0].uri(),
+            "http://127.0.0.1:8080/b2api/v2/b2_authorize_account"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_load_rejected() -> Result<()> {
+        let loader = Loader::new(
+            MockAuthorize {
+                requests: Mutex::default(),
+            },
+            Config {
+                key_id: Some("key_id".to_string()),
+                application_key: Some("wrong_key".to_string()),
+                ..Default::default()
+            },
+        );
+
+        let err = loader.load().await.expect_err("load must fail");
+        match err {
+            crate::Error::CredentialRejected { status, error, .. } => {
+                assert_eq!(status, StatusCode::UNAUTHORIZED);
+                assert_eq!(error, "bad_auth_token");
+            }
+            err => panic!("unexpected error: {err:?}"),
+        }
+
+        let loader = Loader::new(
+            MockAuthorize {
+                requests: Mutex::default(),
+            },
+            Config::default(),
+        );
+        assert!(loader.load().await?.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_load_with_partial_config() -> Result<()> {
+        let client = Arc::new(MockAuthorize {
+            requests: Mutex::default(),
+        });
+        let loader = Loader::new(
+            client.clone(),
+            Config {
+                key_id: Some("key_id".to_string()),
+                ..Default::default()
+            },
+        );
+
+        let err = loader.load().await.expect_err("load must fail");
+        assert!(matches!(err, crate::Error::InvalidConfig(_)), "{err:?}");
+        assert_eq!(
+            err.to_string(),
+            "invalid config: application_key is required when key_id is set"
+        );
+        assert!(client.requests.lock().unwrap().is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,17 @@
+//! Backblaze B2 service signer
+//!
+//! Use [`B2Signer`][crate::B2Signer] to sign requests for the B2 native API.
+//! The S3 compatible API of B2 should be signed by
+//! [`AwsV4Signer`][crate::AwsV4Signer] instead.
+
+mod config;
+pub use config::Config as B2Config;
+
+mod credential;
+pub use credential::Credential as B2Credential;
+pub use credential::Loader as B2Loader;
+
+mod signer;
+pub use signer::Signer as B2Signer;
+
+mod constants;
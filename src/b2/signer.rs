@@ -0,0 +1,164 @@
+//! Backblaze B2 native API signer
+
+use anyhow::Result;
+use http::header::AUTHORIZATION;
+use http::HeaderMap;
+use http::HeaderValue;
+use percent_encoding::utf8_percent_encode;
+
+use super::constants::B2_QUERY_ENCODE_SET;
+use super::credential::Credential;
+use crate::ctx::SigningContext;
+use crate::request::sign_to_headers;
+use crate::request::SignableRequest;
+use crate::Error;
+
+/// Signer that implement Backblaze B2 native API authorization.
+///
+/// - [Native API](https://www.backblaze.com/apidocs/introduction-to-the-b2-native-api)
+///
+/// B2 doesn't sign requests, the `authorizationToken` returned by
+/// `b2_authorize_account` is sent in `Authorization` header as is. Requests
+/// should be sent to the `api_url` or `download_url` of the credential.
+#[derive(Debug, Default)]
+pub struct Signer {}
+
+impl Signer {
+    /// Create a new signer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "reqsign::sign",
+            level = "debug",
+            skip_all,
+            fields(provider = "b2")
+        )
+    )]
+    fn build(&self, req: &mut impl SignableRequest, cred: &Credential) -> Result<SigningContext> {
+        if cred.is_expired() {
+            return Err(Error::CredentialExpired.into());
+        }
+
+        let mut ctx = req.build()?;
+
+        // Query in context has been decoded, encode them back so that the
+        // request we sent is not changed.
+        ctx.query = ctx
+            .query
+            .iter()
+            .map(|(k, v)| {
+                (
+                    utf8_percent_encode(k, &B2_QUERY_ENCODE_SET).to_string(),
+                    utf8_percent_encode(v, &B2_QUERY_ENCODE_SET).to_string(),
+                )
+            })
+            .collect();
+
+        ctx.headers.insert(AUTHORIZATION, {
+            let mut value: HeaderValue = cred.authorization_token.parse()?;
+            value.set_sensitive(true);
+
+            value
+        });
+
+        Ok(ctx)
+    }
+
+    /// Signing request with header.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use anyhow::Result;
+    /// use reqsign::B2Config;
+    /// use reqsign::B2Loader;
+    /// use reqsign::B2Signer;
+    /// use reqwest::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let client = Client::new();
+    ///     let loader = B2Loader::new(client.clone(), B2Config::default().from_env());
+    ///     let signer = B2Signer::new();
+    ///
+    ///     let cred = loader.load().await?.unwrap();
+    ///     let url = format!("{}/b2api/v2/b2_list_buckets?accountId={}", cred.api_url, cred.account_id);
+    ///     let mut req = http::Request::get(url).body(Vec::new())?;
+    ///     signer.sign(&mut req, &cred)?;
+    ///
+    ///     let resp = client.execute(req.try_into()?).await?;
+    ///     println!("resp got status: {}", resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn sign(&self, req: &mut impl SignableRequest, cred: &Credential) -> crate::Result<()> {
+        let ctx = self.build(req, cred).map_err(Error::signing)?;
+        req.apply(ctx).map_err(Error::signing)
+    }
+
+    /// Calculate the headers that will be inserted or changed while signing
+    /// with header, the request will be left untouched.
+    ///
+    /// It's useful to apply the headers to another request like proxies
+    /// that re-emit requests.
+    pub fn sign_to_headers(
+        &self,
+        req: &mut impl SignableRequest,
+        cred: &Credential,
+    ) -> crate::Result<HeaderMap> {
+        sign_to_headers(req, |ctx| self.sign(ctx, cred))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::now;
+
+    fn credential() -> Credential {
+        Credential {
+            account_id: "account_id".to_string(),
+            authorization_token: "auth_token".to_string(),
+            api_url: "https://api001.backblazeb2.com".to_string(),
+            download_url: "https://f001.backblazeb2.com".to_string(),
+            expires_in: Some(now() + chrono::Duration::hours(24)),
+        }
+    }
+
+    #[test]
+    fn test_sign() -> Result<()> {
+        let mut req = http::Request::get(
+            "https://api001.backblazeb2.com/b2api/v2/b2_list_file_names?bucketId=bucket&prefix=a%20b%2Fc",
+        )
+        .body(())?;
+        Signer::new().sign(&mut req, &credential())?;
+
+        assert_eq!(req.headers()[AUTHORIZATION], "auth_token");
+        assert!(req.headers()[AUTHORIZATION].is_sensitive());
+        assert_eq!(
+            req.uri(),
+            "https://api001.backblazeb2.com/b2api/v2/b2_list_file_names?bucketId=bucket&prefix=a%20b%2Fc"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_expired() {
+        let cred = Credential {
+            expires_in: Some(now() - chrono::Duration::minutes(1)),
+            ..credential()
+        };
+        let mut req = http::Request::get("https://api001.backblazeb2.com/b2api/v2/b2_list_buckets")
+            .body(())
+            .expect("request must be valid");
+
+        let err = Signer::new()
+            .sign(&mut req, &cred)
+            .expect_err("sign must fail");
+        assert!(matches!(err, Error::CredentialExpired), "{err:?}");
+    }
+}
@@ -9,20 +9,73 @@ use http::HeaderMap;
 use http::HeaderValue;
 use http::Method;
 
+/// SigningContext is the parts of request that signers read and change.
+///
+/// It's built by [`SignableRequest::build`][crate::SignableRequest::build]
+/// and written back by [`SignableRequest::apply`][crate::SignableRequest::apply],
+/// custom signers can reuse the helpers here to canonicalize requests:
+///
+/// ```
+/// use reqsign::SignableRequest;
+/// use reqsign::SigningContext;
+///
+/// # fn main() -> anyhow::Result<()> {
+/// let mut req = http::Request::get("https://example.com/path?b=2&a=1")
+///     .header("x-custom-meta", "value")
+///     .body(())?;
+///
+/// let mut ctx = req.build()?;
+/// let canonical_query = SigningContext::query_to_string(ctx.query.clone(), "=", "&");
+/// assert_eq!(canonical_query, "a=1&b=2");
+/// let canonical_headers =
+///     SigningContext::header_to_string(ctx.header_to_vec_with_prefix("x-custom-"), ":", "\n");
+/// assert_eq!(canonical_headers, "x-custom-meta:value");
+///
+/// ctx.headers.insert("authorization", "signature".parse()?);
+/// req.apply(ctx)?;
+/// assert_eq!(req.headers()["authorization"], "signature");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
 pub struct SigningContext {
+    /// HTTP method of request.
     pub method: Method,
+    /// Scheme of request, default to `http` if not set.
     pub scheme: Scheme,
+    /// Authority to sign, userinfo and the default port of scheme have been
+    /// stripped.
+    ///
+    /// Scheme and authority are only used for signing, the request keeps
+    /// its original ones while applying.
     pub authority: Authority,
+    /// Path of request as it's in the uri, which is percent encoded.
     pub path: String,
+    /// Query pairs of request.
+    ///
+    /// Pairs built from requests are percent **decoded**, while pairs are
+    /// written back into uri verbatim while applying. Signers must encode
+    /// all pairs before applying the context.
     pub query: Vec<(String, String)>,
+    /// Headers of request.
+    ///
+    /// Headers are moved out of the request while building, so the context
+    /// must be applied back even if signing failed.
     pub headers: HeaderMap,
+    /// Whether the `Host` header is absent in request while building, the
+    /// one inserted by signers will be removed from HTTP/2 and later
+    /// requests while applying.
+    pub host_absent: bool,
 }
 
 impl SigningContext {
-    pub fn path_percent_decoded(&self) -> Cow<str> {
+    /// Get the percent decoded path, invalid UTF-8 will be replaced.
+    pub fn path_percent_decoded(&self) -> Cow<'_, str> {
         percent_encoding::percent_decode_str(&self.path).decode_utf8_lossy()
     }
 
+    /// Get the length of all query keys and values, used to reserve
+    /// capacity while building the query string.
     #[inline]
     pub fn query_size(&self) -> usize {
         self.query
@@ -43,6 +96,7 @@ impl SigningContext {
         self.query.push((query.to_string(), "".to_string()));
     }
 
+    /// Clone query pairs whose key matches `filter`.
     pub fn query_to_vec_with_filter(&self, filter: impl Fn(&str) -> bool) -> Vec<(String, String)> {
         self.query
             .iter()
@@ -55,6 +109,9 @@ impl SigningContext {
 
     /// Convert sorted query to string.
     ///
+    /// Pairs are sorted by key then value and joined as-is, so callers
+    /// should encode them first if needed. `sep` is omitted for empty values.
+    ///
     /// ```shell
     /// [(a, b), (c, d)] => "a:b\nc:d"
     /// ```
@@ -81,6 +138,9 @@ impl SigningContext {
 
     /// Convert sorted query to percent decoded string.
     ///
+    /// The same as [`SigningContext::query_to_string`] except that values
+    /// are percent decoded.
+    ///
     /// ```shell
     /// [(a, b), (c, d)] => "a:b\nc:d"
     /// ```
@@ -109,6 +169,9 @@ impl SigningContext {
         s
     }
 
+    /// Get the value of header, returns an empty string if it's not set.
+    ///
+    /// Returns error if the value is not visible ASCII.
     #[inline]
     pub fn header_get_or_default(&self, key: &HeaderName) -> Result<&str> {
         match self.headers.get(key) {
@@ -117,6 +180,7 @@ impl SigningContext {
         }
     }
 
+    /// Trim leading and trailing spaces of header value.
     pub fn header_value_normalize(v: &mut HeaderValue) {
         let bs = v.as_bytes();
 
@@ -129,6 +193,17 @@ impl SigningContext {
             .expect("invalid header value")
     }
 
+    /// Fold header value by trimming leading and trailing whitespaces and
+    /// replacing every run of whitespaces inside with a single space.
+    ///
+    /// ```shell
+    /// " a \t b  c " => "a b c"
+    /// ```
+    pub fn header_value_fold(v: &str) -> String {
+        v.split_ascii_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    /// Get all header names in lowercase sorted order.
     pub fn header_name_to_vec_sorted(&self) -> Vec<&str> {
         let mut h = self
             .headers
@@ -140,16 +215,39 @@ impl SigningContext {
         h
     }
 
-    pub fn header_to_vec_with_prefix(&self, prefix: &str) -> Vec<(String, String)> {
+    /// Get all values of header joined with `,` in the order they were added.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any value is not visible ASCII.
+    ///
+    /// ```shell
+    /// [(a, b), (a, c)] => "b,c"
+    /// ```
+    pub fn header_value_joined(&self, key: &str) -> String {
         self.headers
+            .get_all(key)
             .iter()
+            .map(|v| v.to_str().expect("must be valid header"))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Convert headers that starts with prefix to vec.
+    ///
+    /// `prefix` should be lowercase since header names are stored in
+    /// lowercase. Multiple values of the same header will be combined into
+    /// one, see [`SigningContext::header_value_joined`].
+    pub fn header_to_vec_with_prefix(&self, prefix: &str) -> Vec<(String, String)> {
+        self.headers
+            .keys()
             // Filter all header that starts with prefix
-            .filter(|(k, _)| k.as_str().starts_with(prefix))
+            .filter(|k| k.as_str().starts_with(prefix))
             // Convert all header name to lowercase
-            .map(|(k, v)| {
+            .map(|k| {
                 (
                     k.as_str().to_lowercase(),
-                    v.to_str().expect("must be valid header").to_string(),
+                    self.header_value_joined(k.as_str()),
                 )
             })
             .collect()
@@ -157,6 +255,9 @@ impl SigningContext {
 
     /// Convert sorted headers to string.
     ///
+    /// Headers are sorted by name then value and joined as-is, so callers
+    /// should lowercase names and normalize values first if needed.
+    ///
     /// ```shell
     /// [(a, b), (c, d)] => "a:b\nc:d"
     /// ```
@@ -181,7 +282,7 @@ impl SigningContext {
 }
 
 /// SigningMethod is the method that used in signing.
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum SigningMethod {
     /// Signing with header.
     Header,
@@ -9,6 +9,17 @@ pub fn expand_homedir(path: &str) -> Option<String> {
     if !path.starts_with("~/") && !path.starts_with("~\\") {
         Some(path.to_string())
     } else {
-        home::home_dir().map(|home| path.replace('~', &home.to_string_lossy()))
+        home_dir().map(|home| path.replace('~', &home))
     }
 }
+
+#[cfg(not(target_arch = "wasm32"))]
+fn home_dir() -> Option<String> {
+    home::home_dir().map(|home| home.to_string_lossy().to_string())
+}
+
+/// There is no home dir in wasm runtimes like browsers.
+#[cfg(target_arch = "wasm32")]
+fn home_dir() -> Option<String> {
+    None
+}
@@ -0,0 +1,158 @@
+//! Errors returned by reqsign.
+
+/// Result that returned by reqsign's public APIs.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Error that returned by reqsign's public APIs.
+///
+/// Callers can match on the variants to decide whether to refresh
+/// credentials, retry later or fail directly.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// Credential is required but not found in any source.
+    #[error("credential not found: {0}")]
+    CredentialNotFound(String),
+    /// Credential has been expired, callers should load a new one.
+    #[error("credential has been expired")]
+    CredentialExpired,
+    /// Credential endpoints like metadata services or STS are unavailable.
+    ///
+    /// It's usually caused by network errors or server errors, and could
+    /// be recovered by retrying later.
+    #[error("credential endpoint is unavailable: {0}")]
+    MetadataUnavailable(#[source] anyhow::Error),
+    /// Credential endpoints like OAuth2 token endpoints rejected the request,
+    /// for example, with `invalid_grant` for revoked keys or clock skew.
+    ///
+    /// It's permanent and retrying will not help until the credential or
+    /// config is fixed.
+    #[error(
+        "credential is rejected by endpoint with {status}: {error}{}",
+        description.as_deref().map(|v| format!(": {v}")).unwrap_or_default()
+    )]
+    CredentialRejected {
+        /// HTTP status returned by endpoint.
+        status: http::StatusCode,
+        /// Error code returned by endpoint like `invalid_grant`.
+        error: String,
+        /// Human readable description of error if returned.
+        description: Option<String>,
+    },
+    /// Config is invalid, for example, fields that conflict with each other
+    /// or are required together are set partially.
+    ///
+    /// It's permanent and the message names the field at fault.
+    #[error("invalid config: {0}")]
+    InvalidConfig(String),
+    /// Failed to sign the request, for example, the request or the private
+    /// key is invalid.
+    #[error("failed to sign request: {0}")]
+    Signing(#[source] anyhow::Error),
+    /// IO errors like failing to read config files.
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    /// Other unexpected errors like malformed configs or responses.
+    #[error(transparent)]
+    Unexpected(anyhow::Error),
+}
+
+/// reqsign uses `anyhow` internally, errors will be converted while
+/// returning from public APIs.
+///
+/// Errors raised as [`Error`] or [`std::io::Error`] will be recovered,
+/// others will be [`Error::Unexpected`].
+impl From<anyhow::Error> for Error {
+    fn from(err: anyhow::Error) -> Self {
+        let err = match err.downcast::<Error>() {
+            Ok(err) => return err,
+            Err(err) => err,
+        };
+        match err.downcast::<std::io::Error>() {
+            Ok(err) => Error::Io(err),
+            Err(err) => Error::Unexpected(err),
+        }
+    }
+}
+
+impl Error {
+    /// Convert internal errors happened while signing.
+    pub(crate) fn signing(err: anyhow::Error) -> Self {
+        match Error::from(err) {
+            Error::Unexpected(err) => Error::Signing(err),
+            err => err,
+        }
+    }
+}
+
+/// Check that fields like access key id and secret access key are set
+/// together, and name the first missing one if they are set partially.
+pub(crate) fn required_together(fields: &[(&str, bool)]) -> Result<()> {
+    let (set, missing): (Vec<_>, Vec<_>) = fields.iter().partition(|(_, is_set)| *is_set);
+    let (Some((name, _)), Some(((last, _), rest))) = (missing.first(), set.split_last()) else {
+        return Ok(());
+    };
+
+    let set = match rest {
+        [] => format!("{last} is"),
+        _ => {
+            let rest = rest.iter().map(|(v, _)| *v).collect::<Vec<_>>();
+            format!("{} and {last} are", rest.join(", "))
+        }
+    };
+    Err(Error::InvalidConfig(format!(
+        "{name} is required when {set} set"
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+
+    use super::*;
+
+    #[test]
+    fn test_from_anyhow() {
+        let err = Error::from(anyhow::Error::from(Error::CredentialExpired));
+        assert!(matches!(err, Error::CredentialExpired));
+
+        let err = Error::from(anyhow::Error::from(std::io::Error::from(
+            std::io::ErrorKind::NotFound,
+        )));
+        assert!(matches!(err, Error::Io(_)));
+
+        let err = Error::from(anyhow!("unexpected"));
+        assert!(matches!(err, Error::Unexpected(_)));
+        assert_eq!(err.to_string(), "unexpected");
+
+        let err = Error::signing(anyhow!("invalid key"));
+        assert!(matches!(err, Error::Signing(_)));
+        assert_eq!(err.to_string(), "failed to sign request: invalid key");
+    }
+
+    #[test]
+    fn test_required_together() {
+        assert!(required_together(&[("a", true), ("b", true)]).is_ok());
+        assert!(required_together(&[("a", false), ("b", false)]).is_ok());
+
+        let err = required_together(&[("a", true), ("b", false)]).unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(_)));
+        assert_eq!(
+            err.to_string(),
+            "invalid config: b is required when a is set"
+        );
+
+        let err = required_together(&[("a", true), ("b", false), ("c", true)]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid config: b is required when a and c are set"
+        );
+
+        let err =
+            required_together(&[("a", true), ("b", true), ("c", true), ("d", false)]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid config: d is required when a, b and c are set"
+        );
+    }
+}
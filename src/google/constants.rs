@@ -4,6 +4,10 @@ use percent_encoding::NON_ALPHANUMERIC;
 // Env values used in google services.
 pub const GOOGLE_APPLICATION_CREDENTIALS: &str = "GOOGLE_APPLICATION_CREDENTIALS";
 
+// Scope and endpoint used by service account impersonation.
+pub const GOOGLE_CLOUD_PLATFORM_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+pub const GOOGLE_IAM_CREDENTIALS_ENDPOINT: &str = "https://iamcredentials.googleapis.com";
+
 /// AsciiSet for [Google UriEncode](https://cloud.google.com/storage/docs/authentication/canonical-requests)
 ///
 /// - URI encode every byte except the unreserved characters: 'A'-'Z', 'a'-'z', '0'-'9', '-', '.', '_', and '~'.
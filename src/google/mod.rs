@@ -5,6 +5,9 @@ mod constants;
 mod credential;
 pub use credential::Credential as GoogleCredential;
 pub use credential::CredentialLoader as GoogleCredentialLoader;
+pub use credential::CredentialSource as GoogleCredentialSource;
+pub use credential::CredentialSourceFormat as GoogleCredentialSourceFormat;
+pub use credential::ExternalAccount as GoogleExternalAccount;
 
 mod token;
 pub use token::Token as GoogleToken;
@@ -2,23 +2,32 @@ use std::fmt::Debug;
 use std::fmt::Formatter;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Duration;
 
 use anyhow::anyhow;
 use anyhow::Result;
 use async_trait::async_trait;
 use http::header;
 use http::StatusCode;
-use jsonwebtoken::Algorithm;
-use jsonwebtoken::EncodingKey;
-use jsonwebtoken::Header;
 use log::error;
-use reqwest::Client;
 use serde::Deserialize;
 use serde::Serialize;
 
+use super::constants::GOOGLE_CLOUD_PLATFORM_SCOPE;
+use super::constants::GOOGLE_IAM_CREDENTIALS_ENDPOINT;
 use super::credential::Credential;
+use super::credential::CredentialSource;
+use super::credential::ExternalAccount;
+use crate::http_send::check_available;
+use crate::http_send::HttpSend;
+use crate::jwt::sign_jwt_rs256;
+use crate::loader::CredentialLoad;
 use crate::time::now;
+use crate::time::parse_rfc3339;
 use crate::time::DateTime;
+use crate::trace::credential_refreshed;
+use crate::trace::record_cache_hit;
+use crate::Error;
 
 /// Token is the authentication methods used by google services.
 ///
@@ -102,6 +111,20 @@ impl Claims {
     }
 }
 
+#[derive(Serialize)]
+struct GenerateAccessTokenRequest {
+    delegates: Vec<String>,
+    scope: Vec<String>,
+    lifetime: String,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct GenerateAccessTokenResponse {
+    access_token: String,
+    expire_time: String,
+}
+
 /// Loader trait will try to load credential from different sources.
 #[async_trait]
 pub trait TokenLoad: 'static + Send + Sync + Debug {
@@ -110,21 +133,25 @@ pub trait TokenLoad: 'static + Send + Sync + Debug {
     /// - If succeed, return `Ok(Some(cred))`
     /// - If not found, return `Ok(None)`
     /// - If unexpected errors happened, return `Err(err)`
-    async fn load(&self, clinet: Client) -> Result<Option<Token>>;
+    async fn load(&self, client: Arc<dyn HttpSend>) -> Result<Option<Token>>;
 }
 
 /// TokenLoader will load token from different methods.
 #[cfg_attr(test, derive(Debug))]
 pub struct TokenLoader {
     scope: String,
-    client: Client,
+    client: Arc<dyn HttpSend>,
 
     credentials: Option<Credential>,
     disable_vm_metadata: bool,
     service_account: Option<String>,
     customed_token_loader: Option<Box<dyn TokenLoad>>,
+    impersonate_service_account: Option<String>,
+    impersonate_delegates: Vec<String>,
+    impersonate_lifetime: Duration,
 
     token: Arc<Mutex<Option<(Token, DateTime)>>>,
+    refresh_lock: Arc<tokio::sync::Mutex<()>>,
 }
 
 impl TokenLoader {
@@ -139,17 +166,21 @@ impl TokenLoader {
     /// - full-control: `https://www.googleapis.com/auth/devstorage.full_control`
     ///
     /// Reference: [Cloud Storage authentication](https://cloud.google.com/storage/docs/authentication)
-    pub fn new(scope: &str, client: Client) -> Self {
+    pub fn new(scope: &str, client: impl HttpSend) -> Self {
         Self {
             scope: scope.to_string(),
-            client,
+            client: Arc::new(client),
 
             credentials: None,
             disable_vm_metadata: false,
             service_account: None,
             customed_token_loader: None,
+            impersonate_service_account: None,
+            impersonate_delegates: Vec::new(),
+            impersonate_lifetime: Duration::from_secs(3600),
 
             token: Arc::default(),
+            refresh_lock: Arc::default(),
         }
     }
 
@@ -177,14 +208,59 @@ impl TokenLoader {
         self
     }
 
+    /// Impersonate the target service account.
+    ///
+    /// The token loaded from other sources will be used to call IAM
+    /// Credentials API `generateAccessToken` for the target service account,
+    /// which must grant `roles/iam.serviceAccountTokenCreator` to the caller.
+    ///
+    /// Reference: [Create a short-lived access token](https://cloud.google.com/iam/docs/create-short-lived-credentials-direct#sa-credentials-oauth)
+    pub fn with_impersonate_service_account(mut self, service_account: &str) -> Self {
+        self.impersonate_service_account = Some(service_account.to_string());
+        self
+    }
+
+    /// Set the delegation chain for impersonation.
+    ///
+    /// Each service account in chain must grant `roles/iam.serviceAccountTokenCreator`
+    /// to the previous one, and the last one must be granted by the target.
+    pub fn with_impersonate_delegates(mut self, delegates: &[&str]) -> Self {
+        self.impersonate_delegates = delegates.iter().map(|v| v.to_string()).collect();
+        self
+    }
+
+    /// Set the lifetime of impersonated token.
+    ///
+    /// Default to 1 hour, and can't be longer than 12 hours.
+    pub fn with_impersonate_lifetime(mut self, lifetime: Duration) -> Self {
+        self.impersonate_lifetime = lifetime;
+        self
+    }
+
     /// Load token from different sources.
-    pub async fn load(&self) -> Result<Option<Token>> {
-        match self.token.lock().expect("lock poisoned").clone() {
-            Some((token, expire_in)) if now() < expire_in - chrono::Duration::seconds(2 * 60) => {
-                return Ok(Some(token))
-            }
-            _ => (),
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "reqsign::load",
+            level = "debug",
+            skip_all,
+            fields(provider = "google", cache_hit = tracing::field::Empty)
+        )
+    )]
+    pub async fn load(&self) -> crate::Result<Option<Token>> {
+        if let Some(token) = self.cached() {
+            record_cache_hit(true);
+            return Ok(Some(token));
+        }
+
+        // Only one task loads token at the same time, others will wait
+        // and reuse its result.
+        let _guard = self.refresh_lock.lock().await;
+        if let Some(token) = self.cached() {
+            record_cache_hit(true);
+            return Ok(Some(token));
         }
+        record_cache_hit(false);
 
         let token = if let Some(token) = self.load_inner().await? {
             token
@@ -193,6 +269,7 @@ impl TokenLoader {
         };
 
         let expire_in = now() + chrono::Duration::seconds(token.expires_in() as i64);
+        credential_refreshed("google", Some(expire_in));
 
         let mut lock = self.token.lock().expect("lock poisoned");
         *lock = Some((token.clone(), expire_in));
@@ -200,16 +277,41 @@ impl TokenLoader {
         Ok(Some(token))
     }
 
+    /// Return cached token if it will not expire in 2 minutes.
+    fn cached(&self) -> Option<Token> {
+        match self.token.lock().expect("lock poisoned").clone() {
+            Some((token, expire_in)) if now() < expire_in - chrono::Duration::seconds(2 * 60) => {
+                Some(token)
+            }
+            _ => None,
+        }
+    }
+
     async fn load_inner(&self) -> Result<Option<Token>> {
+        let target = match &self.impersonate_service_account {
+            Some(target) => target,
+            None => return self.load_base_token(&self.scope).await,
+        };
+
+        // IAM Credentials API requires `cloud-platform` scope.
+        let token = match self.load_base_token(GOOGLE_CLOUD_PLATFORM_SCOPE).await? {
+            Some(token) => token,
+            None => return Ok(None),
+        };
+
+        self.load_via_impersonation(&token, target).await
+    }
+
+    async fn load_base_token(&self, scope: &str) -> Result<Option<Token>> {
         if let Some(token) = self.load_via_customed_token_loader().await? {
             return Ok(Some(token));
         }
 
-        if let Some(token) = self.load_via_credential().await? {
+        if let Some(token) = self.load_via_credential(scope).await? {
             return Ok(Some(token));
         }
 
-        if let Some(token) = self.load_via_vm_metadata().await? {
+        if let Some(token) = self.load_via_vm_metadata(scope).await? {
             return Ok(Some(token));
         }
 
@@ -226,41 +328,48 @@ impl TokenLoader {
     /// Exchange token via Google OAuth2 Service.
     ///
     /// Reference: [Using OAuth 2.0 for Server to Server Applications](https://developers.google.com/identity/protocols/oauth2/service-account#authorizingrequests)
-    async fn load_via_credential(&self) -> Result<Option<Token>> {
+    async fn load_via_credential(&self, scope: &str) -> Result<Option<Token>> {
         let cred = if let Some(cred) = &self.credentials {
             cred
         } else {
             return Ok(None);
         };
 
-        let jwt = jsonwebtoken::encode(
-            &Header::new(Algorithm::RS256),
-            &Claims::new(&cred.client_email, &self.scope),
-            &EncodingKey::from_rsa_pem(cred.private_key.as_bytes())?,
+        if let Some(external_account) = &cred.external_account {
+            return self
+                .load_via_external_account(external_account, scope)
+                .await;
+        }
+
+        let jwt = sign_jwt_rs256(
+            &serde_json::json!({"alg": "RS256", "typ": "JWT"}),
+            &Claims::new(&cred.client_email, scope),
+            &cred.private_key,
         )?;
 
-        let resp = self
-            .client
-            .post("https://oauth2.googleapis.com/token")
+        let body = form_urlencoded::Serializer::new(String::new())
+            .append_pair("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer")
+            .append_pair("assertion", &jwt)
+            .finish();
+        let req = http::Request::post("https://oauth2.googleapis.com/token")
             .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
-            .form(&[
-                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
-                ("assertion", &jwt),
-            ])
-            .send()
-            .await?;
+            .body(body.into_bytes())?;
 
+        let resp = check_available(self.client.send(req).await)?;
         if resp.status() != StatusCode::OK {
-            error!("exchange token got unexpected response: {:?}", resp);
-            return Err(anyhow!("exchange token failed: {}", resp.text().await?));
+            error!(
+                "exchange token got unexpected response: {:?}",
+                resp.status()
+            );
+            return Err(token_endpoint_error("exchange token", &resp));
         }
 
-        let token: Token = serde_json::from_slice(&resp.bytes().await?)?;
+        let token: Token = serde_json::from_slice(resp.body())?;
         Ok(Some(token))
     }
 
     /// Exchange token via vm metadata
-    async fn load_via_vm_metadata(&self) -> Result<Option<Token>> {
+    async fn load_via_vm_metadata(&self, scope: &str) -> Result<Option<Token>> {
         if self.disable_vm_metadata {
             return Ok(None);
         }
@@ -268,16 +377,518 @@ impl TokenLoader {
         // Use `default` if service account not set by user.
         let service_account = self.service_account.as_deref().unwrap_or("default");
 
-        let url = format!("http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/{service_account}/token?scopes={}", self.scope);
+        let url = format!("http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/{service_account}/token?scopes={scope}");
 
-        let resp = self
-            .client
-            .get(&url)
+        let req = http::Request::get(&url)
             .header("Metadata-Flavor", "Google")
-            .send()
-            .await?;
+            .body(Vec::new())?;
+        let resp = check_available(self.client.send(req).await)?;
 
-        let token: Token = serde_json::from_slice(&resp.bytes().await?)?;
+        let token: Token = serde_json::from_slice(resp.body())?;
         Ok(Some(token))
     }
+
+    /// Exchange token of external account via Google STS.
+    ///
+    /// Reference: [Workload identity federation](https://cloud.google.com/iam/docs/workload-identity-federation)
+    async fn load_via_external_account(
+        &self,
+        external_account: &ExternalAccount,
+        scope: &str,
+    ) -> Result<Option<Token>> {
+        let subject_token = self
+            .load_subject_token(&external_account.credential_source)
+            .await?;
+
+        // IAM Credentials API requires `cloud-platform` scope.
+        let sts_scope = if external_account.service_account_impersonation_url.is_some() {
+            GOOGLE_CLOUD_PLATFORM_SCOPE
+        } else {
+            scope
+        };
+
+        let body = form_urlencoded::Serializer::new(String::new())
+            .append_pair(
+                "grant_type",
+                "urn:ietf:params:oauth:grant-type:token-exchange",
+            )
+            .append_pair(
+                "requested_token_type",
+                "urn:ietf:params:oauth:token-type:access_token",
+            )
+            .append_pair("audience", &external_account.audience)
+            .append_pair("scope", sts_scope)
+            .append_pair("subject_token", &subject_token)
+            .append_pair("subject_token_type", &external_account.subject_token_type)
+            .finish();
+        let req = http::Request::post(&external_account.token_url)
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(body.into_bytes())?;
+
+        let resp = check_available(self.client.send(req).await)?;
+        if resp.status() != StatusCode::OK {
+            error!(
+                "exchange external account token got unexpected response: {:?}",
+                resp.status()
+            );
+            return Err(token_endpoint_error(
+                "exchange external account token",
+                &resp,
+            ));
+        }
+
+        let token: Token = serde_json::from_slice(resp.body())?;
+        match &external_account.service_account_impersonation_url {
+            Some(url) => {
+                self.generate_access_token(&token, url, &[], scope, Duration::from_secs(3600))
+                    .await
+            }
+            None => Ok(Some(token)),
+        }
+    }
+
+    /// Load subject token of external account from credential source.
+    async fn load_subject_token(&self, source: &CredentialSource) -> Result<String> {
+        let content = if let Some(path) = &source.file {
+            std::fs::read(path)
+                .map_err(|err| anyhow!("read subject token from {path} failed: {err:?}"))?
+        } else if let Some(url) = &source.url {
+            let mut req = http::Request::get(url);
+            for (k, v) in &source.headers {
+                req = req.header(k, v);
+            }
+
+            let resp = check_available(self.client.send(req.body(Vec::new())?).await)?;
+            if resp.status() != StatusCode::OK {
+                return Err(anyhow!(
+                    "request subject token from {url} failed: {}",
+                    String::from_utf8_lossy(resp.body())
+                ));
+            }
+            resp.into_body()
+        } else if let Some(id) = &source.environment_id {
+            return Err(anyhow!(
+                "credential source of environment {id} is not supported"
+            ));
+        } else {
+            return Err(anyhow!("credential source of external account is empty"));
+        };
+
+        match &source.format {
+            Some(format) if format.format_type == "json" => {
+                let field = format
+                    .subject_token_field_name
+                    .as_deref()
+                    .ok_or_else(|| anyhow!("subject_token_field_name is required for json"))?;
+                let value: serde_json::Value = serde_json::from_slice(&content)?;
+                value
+                    .get(field)
+                    .and_then(|v| v.as_str())
+                    .map(|v| v.to_string())
+                    .ok_or_else(|| anyhow!("subject token field {field} not found"))
+            }
+            _ => Ok(String::from_utf8(content)?.trim().to_string()),
+        }
+    }
+
+    /// Generate access token for target service account via IAM Credentials API.
+    async fn load_via_impersonation(&self, token: &Token, target: &str) -> Result<Option<Token>> {
+        let url = format!("{GOOGLE_IAM_CREDENTIALS_ENDPOINT}/v1/projects/-/serviceAccounts/{target}:generateAccessToken");
+        self.generate_access_token(
+            token,
+            &url,
+            &self.impersonate_delegates,
+            &self.scope,
+            self.impersonate_lifetime,
+        )
+        .await
+    }
+
+    async fn generate_access_token(
+        &self,
+        token: &Token,
+        url: &str,
+        delegates: &[String],
+        scope: &str,
+        lifetime: Duration,
+    ) -> Result<Option<Token>> {
+        let body = GenerateAccessTokenRequest {
+            delegates: delegates
+                .iter()
+                .map(|v| format!("projects/-/serviceAccounts/{v}"))
+                .collect(),
+            scope: vec![scope.to_string()],
+            lifetime: format!("{}s", lifetime.as_secs()),
+        };
+
+        let req = http::Request::post(url)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::AUTHORIZATION, {
+                let mut value: http::HeaderValue =
+                    format!("Bearer {}", token.access_token()).parse()?;
+                value.set_sensitive(true);
+                value
+            })
+            .body(serde_json::to_vec(&body)?)?;
+
+        let resp = check_available(self.client.send(req).await)?;
+        if resp.status() != StatusCode::OK {
+            error!(
+                "generate access token got unexpected response: {:?}",
+                resp.status()
+            );
+            return Err(anyhow!(
+                "generate access token failed: {}",
+                String::from_utf8_lossy(resp.body())
+            ));
+        }
+
+        let resp: GenerateAccessTokenResponse = serde_json::from_slice(resp.body())?;
+        let expires_in = (parse_rfc3339(&resp.expire_time)? - now()).num_seconds();
+        Ok(Some(Token::new(
+            &resp.access_token,
+            expires_in.max(0) as usize,
+            scope,
+        )))
+    }
+}
+
+#[async_trait]
+impl CredentialLoad<Token> for TokenLoader {
+    async fn load(&self) -> crate::Result<Option<Token>> {
+        TokenLoader::load(self).await
+    }
+}
+
+/// Error response returned by OAuth2 token endpoints.
+///
+/// Reference: [RFC 6749 Section 5.2](https://datatracker.ietf.org/doc/html/rfc6749#section-5.2)
+#[derive(Deserialize)]
+struct OAuthErrorResponse {
+    error: String,
+    error_description: Option<String>,
+}
+
+/// Convert the failed response of OAuth2 token endpoints into error.
+///
+/// Responses in OAuth2 error format will be [`Error::CredentialRejected`],
+/// others will be kept as unexpected errors.
+fn token_endpoint_error(action: &str, resp: &http::Response<Vec<u8>>) -> anyhow::Error {
+    match serde_json::from_slice::<OAuthErrorResponse>(resp.body()) {
+        Ok(err) => Error::CredentialRejected {
+            status: resp.status(),
+            error: err.error,
+            description: err.error_description,
+        }
+        .into(),
+        Err(_) => anyhow!(
+            "{action} failed with {}: {}",
+            resp.status(),
+            String::from_utf8_lossy(resp.body())
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use chrono::Utc;
+
+    use super::super::credential::CredentialLoader;
+    use super::*;
+    use crate::time::format_rfc3339;
+    use crate::time::set_fake_now;
+
+    #[derive(Debug)]
+    struct StaticTokenLoad;
+
+    #[async_trait]
+    impl TokenLoad for StaticTokenLoad {
+        async fn load(&self, _: Arc<dyn HttpSend>) -> Result<Option<Token>> {
+            Ok(Some(Token::new(
+                "base_token",
+                3600,
+                GOOGLE_CLOUD_PLATFORM_SCOPE,
+            )))
+        }
+    }
+
+    /// Mock IAM Credentials API which returns `token_{n}` for the n-th request.
+    #[derive(Debug, Default)]
+    struct MockIamCredentials {
+        expire_time: String,
+        requests: Mutex<Vec<http::Request<Vec<u8>>>>,
+    }
+
+    #[async_trait]
+    impl HttpSend for MockIamCredentials {
+        async fn send(&self, req: http::Request<Vec<u8>>) -> Result<http::Response<Vec<u8>>> {
+            let mut requests = self.requests.lock().unwrap();
+            let body = format!(
+                r#"{{"accessToken":"token_{}","expireTime":"{}"}}"#,
+                requests.len(),
+                self.expire_time
+            );
+            requests.push(req);
+
+            Ok(http::Response::builder()
+                .status(StatusCode::OK)
+                .body(body.into_bytes())?)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_load_via_impersonation() -> Result<()> {
+        let start = Utc.with_ymd_and_hms(2022, 3, 1, 8, 0, 0).unwrap();
+        let expire_time = start + chrono::Duration::hours(1);
+        let client = Arc::new(MockIamCredentials {
+            expire_time: format_rfc3339(expire_time),
+            ..Default::default()
+        });
+
+        let loader = TokenLoader::new(
+            "https://www.googleapis.com/auth/devstorage.read_only",
+            client.clone(),
+        )
+        .with_customed_token_loader(Box::new(StaticTokenLoad))
+        .with_impersonate_service_account("target@example.iam.gserviceaccount.com")
+        .with_impersonate_delegates(&["delegate@example.iam.gserviceaccount.com"])
+        .with_impersonate_lifetime(Duration::from_secs(1800));
+
+        set_fake_now(Some(start));
+        let token = loader.load().await?.unwrap();
+        assert_eq!(token.access_token(), "token_0");
+        assert_eq!(token.expires_in(), 3600);
+
+        {
+            let requests = client.requests.lock().unwrap();
+            assert_eq!(requests.len(), 1);
+            let req = &requests[0];
+            assert_eq!(req.method(), http::Method::POST);
+            assert_eq!(
+                req.uri().to_string(),
+                "https://iamcredentials.googleapis.com/v1/projects/-/serviceAccounts/target@example.iam.gserviceaccount.com:generateAccessToken"
+            );
+            assert_eq!(req.headers()[header::AUTHORIZATION], "Bearer base_token");
+            let body: serde_json::Value = serde_json::from_slice(req.body())?;
+            assert_eq!(
+                body,
+                serde_json::json!({
+                    "delegates": ["projects/-/serviceAccounts/delegate@example.iam.gserviceaccount.com"],
+                    "scope": ["https://www.googleapis.com/auth/devstorage.read_only"],
+                    "lifetime": "1800s",
+                })
+            );
+        }
+
+        // Token should be cached until expire time.
+        set_fake_now(Some(expire_time - chrono::Duration::minutes(30)));
+        let token = loader.load().await?.unwrap();
+        assert_eq!(token.access_token(), "token_0");
+
+        set_fake_now(Some(expire_time - chrono::Duration::seconds(60)));
+        let token = loader.load().await?.unwrap();
+        assert_eq!(token.access_token(), "token_1");
+
+        set_fake_now(None);
+        Ok(())
+    }
+
+    /// Mock subject token source and STS which returns `sts_token_{n}` for the n-th exchange.
+    #[derive(Debug, Default)]
+    struct MockSts {
+        requests: Mutex<Vec<http::Request<Vec<u8>>>>,
+    }
+
+    #[async_trait]
+    impl HttpSend for MockSts {
+        async fn send(&self, req: http::Request<Vec<u8>>) -> Result<http::Response<Vec<u8>>> {
+            let mut requests = self.requests.lock().unwrap();
+            let body = if req.uri().host() == Some("localhost") {
+                r#"{"access_token":"subject_token"}"#.to_string()
+            } else {
+                let n = requests
+                    .iter()
+                    .filter(|r| r.uri().host() != Some("localhost"))
+                    .count();
+                format!(
+                    r#"{{"access_token":"sts_token_{n}","issued_token_type":"urn:ietf:params:oauth:token-type:access_token","token_type":"Bearer","expires_in":1800}}"#
+                )
+            };
+            requests.push(req);
+
+            Ok(http::Response::builder()
+                .status(StatusCode::OK)
+                .body(body.into_bytes())?)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_load_via_external_account() -> Result<()> {
+        let start = Utc.with_ymd_and_hms(2022, 3, 1, 8, 0, 0).unwrap();
+        let client = Arc::new(MockSts::default());
+
+        let cred = Credential {
+            private_key: String::new(),
+            client_email: String::new(),
+            external_account: Some(serde_json::from_value(serde_json::json!({
+                "audience": "//iam.googleapis.com/projects/123456/locations/global/workloadIdentityPools/pool/providers/provider",
+                "subject_token_type": "urn:ietf:params:oauth:token-type:jwt",
+                "token_url": "https://sts.googleapis.com/v1/token",
+                "credential_source": {
+                    "url": "http://localhost:5000/token",
+                    "headers": {"Metadata": "True"},
+                    "format": {"type": "json", "subject_token_field_name": "access_token"}
+                }
+            }))?),
+        };
+
+        let loader = TokenLoader::new(
+            "https://www.googleapis.com/auth/devstorage.read_only",
+            client.clone(),
+        )
+        .with_credentials(cred)
+        .with_disable_vm_metadata(true);
+
+        set_fake_now(Some(start));
+        let token = loader.load().await?.unwrap();
+        assert_eq!(token.access_token(), "sts_token_0");
+        assert_eq!(token.expires_in(), 1800);
+
+        {
+            let requests = client.requests.lock().unwrap();
+            assert_eq!(requests.len(), 2);
+
+            let subject = &requests[0];
+            assert_eq!(subject.uri().to_string(), "http://localhost:5000/token");
+            assert_eq!(subject.headers()["Metadata"], "True");
+
+            let exchange = &requests[1];
+            assert_eq!(exchange.method(), http::Method::POST);
+            assert_eq!(
+                exchange.uri().to_string(),
+                "https://sts.googleapis.com/v1/token"
+            );
+            let form: Vec<(String, String)> = form_urlencoded::parse(exchange.body())
+                .into_owned()
+                .collect();
+            assert_eq!(
+                form,
+                vec![
+                    (
+                        "grant_type".to_string(),
+                        "urn:ietf:params:oauth:grant-type:token-exchange".to_string()
+                    ),
+                    (
+                        "requested_token_type".to_string(),
+                        "urn:ietf:params:oauth:token-type:access_token".to_string()
+                    ),
+                    (
+                        "audience".to_string(),
+                        "//iam.googleapis.com/projects/123456/locations/global/workloadIdentityPools/pool/providers/provider".to_string()
+                    ),
+                    (
+                        "scope".to_string(),
+                        "https://www.googleapis.com/auth/devstorage.read_only".to_string()
+                    ),
+                    ("subject_token".to_string(), "subject_token".to_string()),
+                    (
+                        "subject_token_type".to_string(),
+                        "urn:ietf:params:oauth:token-type:jwt".to_string()
+                    ),
+                ]
+            );
+        }
+
+        // Token should be cached according to `expires_in`.
+        set_fake_now(Some(start + chrono::Duration::minutes(20)));
+        let token = loader.load().await?.unwrap();
+        assert_eq!(token.access_token(), "sts_token_0");
+
+        set_fake_now(Some(start + chrono::Duration::minutes(29)));
+        let token = loader.load().await?.unwrap();
+        assert_eq!(token.access_token(), "sts_token_1");
+
+        set_fake_now(None);
+        Ok(())
+    }
+
+    /// Mock OAuth2 token endpoint which always rejects the assertion.
+    #[derive(Debug)]
+    struct MockInvalidGrant(StatusCode, &'static str);
+
+    #[async_trait]
+    impl HttpSend for MockInvalidGrant {
+        async fn send(&self, _: http::Request<Vec<u8>>) -> Result<http::Response<Vec<u8>>> {
+            Ok(http::Response::builder()
+                .status(self.0)
+                .body(self.1.as_bytes().to_vec())?)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_load_via_credential_rejected() -> Result<()> {
+        let cred = CredentialLoader::default()
+            .with_path(&format!(
+                "{}/testdata/services/google/test_credential.json",
+                std::env::current_dir()?.to_string_lossy()
+            ))
+            .load()?
+            .expect("credential must be loaded");
+        let load = |client: MockInvalidGrant| {
+            TokenLoader::new(GOOGLE_CLOUD_PLATFORM_SCOPE, Arc::new(client))
+                .with_credentials(cred.clone())
+                .with_disable_vm_metadata(true)
+        };
+
+        let err = load(MockInvalidGrant(
+            StatusCode::BAD_REQUEST,
+            r#"{"error":"invalid_grant","error_description":"Invalid JWT Signature."}"#,
+        ))
+        .load()
+        .await
+        .expect_err("must fail");
+        match &err {
+            crate::Error::CredentialRejected {
+                status,
+                error,
+                description,
+            } => {
+                assert_eq!(*status, StatusCode::BAD_REQUEST);
+                assert_eq!(error, "invalid_grant");
+                assert_eq!(description.as_deref(), Some("Invalid JWT Signature."));
+            }
+            _ => panic!("unexpected error: {err:?}"),
+        }
+        assert_eq!(
+            err.to_string(),
+            "credential is rejected by endpoint with 400 Bad Request: invalid_grant: Invalid JWT Signature."
+        );
+
+        // Server errors are transient.
+        let err = load(MockInvalidGrant(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "unavailable",
+        ))
+        .load()
+        .await
+        .expect_err("must fail");
+        assert!(
+            matches!(err, crate::Error::MetadataUnavailable(_)),
+            "{err:?}"
+        );
+
+        // Responses not in OAuth2 error format are unexpected.
+        let err = load(MockInvalidGrant(StatusCode::FORBIDDEN, "forbidden"))
+            .load()
+            .await
+            .expect_err("must fail");
+        assert!(matches!(err, crate::Error::Unexpected(_)), "{err:?}");
+        assert!(
+            err.to_string().contains("403 Forbidden: forbidden"),
+            "{err}"
+        );
+
+        Ok(())
+    }
 }
@@ -1,27 +1,74 @@
 //! Hash related utils.
+//!
+//! They are the primitives that signers are built on, and are available
+//! without the `std` feature to compute signatures by hand, for example,
+//! the Azure Storage `SharedKey` signature of a string to sign:
+//!
+//! ```
+//! use reqsign::hash::base64_decode;
+//! use reqsign::hash::base64_hmac_sha256;
+//!
+//! let string_to_sign = "GET\n\n\n\n\n\n\n\n\n\n\n\nx-ms-date:Tue, 01 Mar 2022 08:12:34 GMT\nx-ms-version:2019-12-12\n/account/testbucket/testblob";
+//! let signature = base64_hmac_sha256(&base64_decode("YWNjb3VudF9rZXkK"), string_to_sign.as_bytes());
+//! assert_eq!(signature, "NpEB/U3OgYnd8iqyT6h/ruXt9WjpTSwRydkakMOrjmI=");
+//! ```
 
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
+use std::io::Read;
+
+use anyhow::Result;
 use base64::prelude::BASE64_STANDARD;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
 use base64::Engine;
 use hmac::Hmac;
 use hmac::Mac;
 use sha1::Sha1;
 use sha2::Digest;
 use sha2::Sha256;
+use subtle::ConstantTimeEq;
 
 /// Base64 encode
 pub fn base64_encode(content: &[u8]) -> String {
     BASE64_STANDARD.encode(content)
 }
 
-// Base64 decode
+/// Base64 decode
+///
+/// # Panics
+///
+/// Panics if content is not valid base64, it's used for keys in config.
 pub fn base64_decode(content: &str) -> Vec<u8> {
     BASE64_STANDARD
         .decode(content)
         .expect("base64 decode must success")
 }
 
+/// Base64url encode without padding, used by JWT.
+pub fn base64url_encode(content: &[u8]) -> String {
+    BASE64_URL_SAFE_NO_PAD.encode(content)
+}
+
+/// Base64url decode without padding, used by JWT.
+///
+/// Unlike [`base64_decode`], the input is usually from remote, so errors
+/// will be returned instead of panic.
+pub fn base64url_decode(content: &str) -> Result<Vec<u8>> {
+    BASE64_URL_SAFE_NO_PAD
+        .decode(content)
+        .map_err(anyhow::Error::msg)
+}
+
+/// Base64 encoded MD5 hash, used by `Content-MD5` header.
+#[cfg(feature = "services-azblob")]
+pub fn base64_md5(content: &[u8]) -> String {
+    base64_encode(md5::Md5::digest(content).as_slice())
+}
+
 /// SHA256 hash.
-#[allow(dead_code)]
 pub fn sha256(content: &[u8]) -> Vec<u8> {
     Sha256::digest(content).as_slice().to_vec()
 }
@@ -42,6 +89,54 @@ pub fn hex_sha256(content: &[u8]) -> String {
     hex::encode(Sha256::digest(content).as_slice())
 }
 
+/// Hex encoded SHA256 hash of all content read from reader.
+///
+/// The content is hashed while reading without being buffered in memory,
+/// so it's useful to calculate `x-amz-content-sha256` of large bodies
+/// before signing:
+///
+/// ```
+/// use reqsign::hex_sha256_reader;
+///
+/// # fn main() -> reqsign::Result<()> {
+/// let body = std::io::Cursor::new(b"Hello, World!");
+/// assert_eq!(
+///     hex_sha256_reader(body)?,
+///     "dffd6021bb2bd5b0af676290809ec3a53191dd81c7f70a4b28688a362182986f"
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub fn hex_sha256_reader(mut r: impl Read) -> crate::Result<String> {
+    let mut h = Sha256::new();
+    io::copy(&mut r, &mut h).map_err(anyhow::Error::from)?;
+
+    Ok(hex::encode(h.finalize().as_slice()))
+}
+
+/// Hex encoded SHA256 hash of all content read from async reader.
+///
+/// It's the async version of [`hex_sha256_reader`].
+#[cfg(feature = "async_reader")]
+pub async fn hex_sha256_async_reader(
+    mut r: impl tokio::io::AsyncRead + Unpin,
+) -> crate::Result<String> {
+    use tokio::io::AsyncReadExt;
+
+    let mut h = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = r.read(&mut buf).await.map_err(anyhow::Error::from)?;
+        if n == 0 {
+            break;
+        }
+        h.update(&buf[..n]);
+    }
+
+    Ok(hex::encode(h.finalize().as_slice()))
+}
+
 /// HMAC with SHA256 hash.
 pub fn hmac_sha256(key: &[u8], content: &[u8]) -> Vec<u8> {
     let mut h = Hmac::<Sha256>::new_from_slice(key).expect("invalid key length");
@@ -50,6 +145,14 @@ pub fn hmac_sha256(key: &[u8], content: &[u8]) -> Vec<u8> {
     h.finalize().into_bytes().to_vec()
 }
 
+/// HMAC with SHA1 hash.
+pub fn hmac_sha1(key: &[u8], content: &[u8]) -> Vec<u8> {
+    let mut h = Hmac::<Sha1>::new_from_slice(key).expect("invalid key length");
+    h.update(content);
+
+    h.finalize().into_bytes().to_vec()
+}
+
 /// Base64 encoded HMAC with SHA256 hash.
 pub fn base64_hmac_sha256(key: &[u8], content: &[u8]) -> String {
     let mut h = Hmac::<Sha256>::new_from_slice(key).expect("invalid key length");
@@ -58,6 +161,14 @@ pub fn base64_hmac_sha256(key: &[u8], content: &[u8]) -> String {
     base64_encode(&h.finalize().into_bytes())
 }
 
+/// Base64url encoded HMAC with SHA256 hash without padding, used by JWT.
+pub fn base64url_hmac_sha256(key: &[u8], content: &[u8]) -> String {
+    let mut h = Hmac::<Sha256>::new_from_slice(key).expect("invalid key length");
+    h.update(content);
+
+    base64url_encode(&h.finalize().into_bytes())
+}
+
 /// Hex encoded HMAC with SHA1 hash.
 ///
 /// Use this function instead of `hex::encode(hmac_sha1(key, content))` can
@@ -87,3 +198,142 @@ pub fn base64_hmac_sha1(key: &[u8], content: &[u8]) -> String {
 
     base64_encode(&h.finalize().into_bytes())
 }
+
+/// Compare two byte slices in constant time.
+///
+/// Use this function instead of `==` while verifying signatures to avoid
+/// leaking how many leading bytes matched via timing. The length of input
+/// is not treated as secret.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.ct_eq(b).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_hex_sha256_reader() -> crate::Result<()> {
+        // One million repetitions of `a` from FIPS 180-2.
+        let expected = "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0";
+        assert_eq!(
+            hex_sha256_reader(io::repeat(b'a').take(1_000_000))?,
+            expected
+        );
+        assert_eq!(hex_sha256_reader(io::empty())?, hex_sha256(b""));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "async_reader")]
+    #[tokio::test]
+    async fn test_hex_sha256_async_reader() -> crate::Result<()> {
+        let content = vec![b'a'; 1_000_000];
+        assert_eq!(
+            hex_sha256_async_reader(content.as_slice()).await?,
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_base64url() -> Result<()> {
+        // Bytes that encoded into `+` and `/` in standard alphabet.
+        assert_eq!(base64url_encode(&[0xfb, 0xff, 0xfe]), "-__-");
+        assert_eq!(base64url_decode("-__-")?, vec![0xfb, 0xff, 0xfe]);
+        // Padding should be omitted.
+        assert_eq!(base64url_encode(b"a"), "YQ");
+        assert!(base64url_decode("YQ==").is_err());
+        assert!(base64url_decode("invalid!").is_err());
+
+        for content in [&b""[..], b"a", b"ab", b"abc", b"\x00\xff\x10"] {
+            assert_eq!(base64url_decode(&base64url_encode(content))?, content);
+        }
+
+        // Example JWT from <https://jwt.io>.
+        let header = base64url_encode(br#"{"alg":"HS256","typ":"JWT"}"#);
+        assert_eq!(header, "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9");
+        let payload =
+            base64url_encode(br#"{"sub":"1234567890","name":"John Doe","iat":1516239022}"#);
+        assert_eq!(
+            payload,
+            "eyJzdWIiOiIxMjM0NTY3ODkwIiwibmFtZSI6IkpvaG4gRG9lIiwiaWF0IjoxNTE2MjM5MDIyfQ"
+        );
+        assert_eq!(
+            base64url_hmac_sha256(
+                b"your-256-bit-secret",
+                format!("{header}.{payload}").as_bytes()
+            ),
+            "SflKxwRJSMeKKF2QT4fwpMeJf36POk6yJV_adQssw5c"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hmac_sha1() {
+        // Test cases from RFC 2202.
+        let cases: Vec<(Vec<u8>, Vec<u8>, &str, &str)> = vec![
+            (
+                vec![0x0b; 20],
+                b"Hi There".to_vec(),
+                "b617318655057264e28bc0b6fb378c8ef146be00",
+                "thcxhlUFcmTii8C2+zeMjvFGvgA=",
+            ),
+            (
+                b"Jefe".to_vec(),
+                b"what do ya want for nothing?".to_vec(),
+                "effcdf6ae5eb2fa2d27416d5f184df9c259a7c79",
+                "7/zfauXrL6LSdBbV8YTfnCWafHk=",
+            ),
+            (
+                vec![0xaa; 20],
+                vec![0xdd; 50],
+                "125d7342b9ac11cd91a39af48aa17b4f63f175d3",
+                "El1zQrmsEc2Ro5r0iqF7T2PxddM=",
+            ),
+            (
+                (0x01..=0x19).collect(),
+                vec![0xcd; 50],
+                "4c9007f4026250c6bc8414f9bf50c86c2d7235da",
+                "TJAH9AJiUMa8hBT5v1DIbC1yNdo=",
+            ),
+            (
+                vec![0x0c; 20],
+                b"Test With Truncation".to_vec(),
+                "4c1a03424b55e07fe7f27be1d58bb9324a9a5a04",
+                "TBoDQktV4H/n8nvh1Yu5MkqaWgQ=",
+            ),
+            (
+                vec![0xaa; 80],
+                b"Test Using Larger Than Block-Size Key - Hash Key First".to_vec(),
+                "aa4ae5e15272d00e95705637ce8a3b55ed402112",
+                "qkrl4VJy0A6VcFY3zoo7Ve1AIRI=",
+            ),
+            (
+                vec![0xaa; 80],
+                b"Test Using Larger Than Block-Size Key and Larger Than One Block-Size Data"
+                    .to_vec(),
+                "e8e99d0f45237d786d6bbaa7965c7808bbff1a91",
+                "6OmdD0UjfXhta7qnllx4CLv/GpE=",
+            ),
+        ];
+
+        for (key, content, hex, base64) in cases {
+            assert_eq!(hex::encode(hmac_sha1(&key, &content)), hex);
+            assert_eq!(hex_hmac_sha1(&key, &content), hex);
+            assert_eq!(base64_hmac_sha1(&key, &content), base64);
+        }
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"", b""));
+        assert!(constant_time_eq(b"signature", b"signature"));
+        assert!(!constant_time_eq(b"signature", b"signaturf"));
+        assert!(!constant_time_eq(b"signature", b"signatur"));
+        assert!(!constant_time_eq(b"", b"s"));
+    }
+}
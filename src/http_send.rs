@@ -0,0 +1,75 @@
+//! Pluggable http client used by loaders.
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::Error;
+
+/// HttpSend is used by loaders to send http requests.
+///
+/// reqsign implements it for [`reqwest::Client`] under feature `reqwest_client`.
+/// Users can implement it for their own clients to control TLS, proxies,
+/// timeouts and connection pools, or to test loaders without network.
+#[async_trait]
+pub trait HttpSend: 'static + Send + Sync + Debug {
+    /// Send a http request and return the response.
+    ///
+    /// Non-success status codes should be returned as `Ok(resp)`, loaders
+    /// will check them by themselves.
+    async fn send(&self, req: http::Request<Vec<u8>>) -> Result<http::Response<Vec<u8>>>;
+}
+
+#[async_trait]
+impl<T: HttpSend + ?Sized> HttpSend for Arc<T> {
+    async fn send(&self, req: http::Request<Vec<u8>>) -> Result<http::Response<Vec<u8>>> {
+        self.as_ref().send(req).await
+    }
+}
+
+// reqwest's wasm client is not `Send`, users should implement [`HttpSend`]
+// by themselves in wasm runtimes like browsers.
+#[cfg(all(feature = "reqwest_client", not(target_arch = "wasm32")))]
+#[async_trait]
+impl HttpSend for reqwest::Client {
+    async fn send(&self, req: http::Request<Vec<u8>>) -> Result<http::Response<Vec<u8>>> {
+        let resp = self.execute(req.try_into()?).await?;
+
+        let mut builder = http::Response::builder()
+            .status(resp.status())
+            .version(resp.version());
+        if let Some(headers) = builder.headers_mut() {
+            headers.extend(resp.headers().clone());
+        }
+
+        Ok(builder.body(resp.bytes().await?.to_vec())?)
+    }
+}
+
+/// Check the result returned by [`HttpSend::send`] in loaders.
+///
+/// Network errors, `429` and `5xx` responses mean that credential endpoints
+/// are unavailable for now, they will be returned as
+/// [`Error::MetadataUnavailable`] so that callers could retry later.
+pub(crate) fn check_available(
+    result: Result<http::Response<Vec<u8>>>,
+) -> Result<http::Response<Vec<u8>>> {
+    match result {
+        Ok(resp) if is_unavailable(resp.status()) => Err(Error::MetadataUnavailable(anyhow!(
+            "server responded with {}: {}",
+            resp.status(),
+            String::from_utf8_lossy(resp.body())
+        ))
+        .into()),
+        Ok(resp) => Ok(resp),
+        Err(err) => Err(Error::MetadataUnavailable(err).into()),
+    }
+}
+
+/// Whether the status means that the server is unavailable for now.
+pub(crate) fn is_unavailable(status: http::StatusCode) -> bool {
+    status == http::StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
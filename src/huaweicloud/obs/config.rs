@@ -1,3 +1,5 @@
+use crate::error::required_together;
+
 /// Config carries all the configuration for Huawei Cloud OBS services.
 #[derive(Clone, Default)]
 #[cfg_attr(test, derive(Debug))]
@@ -15,3 +17,16 @@ pub struct Config {
     /// - this field if it's `is_some`
     pub security_token: Option<String>,
 }
+
+impl Config {
+    /// Check that `access_key_id` and `secret_access_key` are set together.
+    ///
+    /// It's called while loading credentials, users can call it eagerly to
+    /// find misconfigurations on startup.
+    pub fn validate(&self) -> crate::Result<()> {
+        required_together(&[
+            ("access_key_id", self.access_key_id.is_some()),
+            ("secret_access_key", self.secret_access_key.is_some()),
+        ])
+    }
+}
@@ -2,8 +2,12 @@ use std::sync::Arc;
 use std::sync::Mutex;
 
 use anyhow::Result;
+use async_trait::async_trait;
 
 use super::config::Config;
+use crate::loader::CredentialLoad;
+use crate::trace::credential_refreshed;
+use crate::trace::record_cache_hit;
 
 /// Credential for obs.
 #[derive(Clone)]
@@ -37,13 +41,27 @@ impl CredentialLoader {
     }
 
     /// Load credential
-    pub async fn load(&self) -> Result<Option<Credential>> {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "reqsign::load",
+            level = "debug",
+            skip_all,
+            fields(provider = "huaweicloud", cache_hit = tracing::field::Empty)
+        )
+    )]
+    pub async fn load(&self) -> crate::Result<Option<Credential>> {
         // Return cached credential if it's valid.
         if let Some(cred) = self.credential.lock().expect("lock poisoned").clone() {
+            record_cache_hit(true);
             return Ok(Some(cred));
         }
+        record_cache_hit(false);
 
         let cred = self.load_inner().await?;
+        if cred.is_some() {
+            credential_refreshed("huaweicloud", None);
+        }
 
         let mut lock = self.credential.lock().expect("lock poisoned");
         *lock = cred.clone();
@@ -60,6 +78,8 @@ impl CredentialLoader {
     }
 
     fn load_via_config(&self) -> Result<Option<Credential>> {
+        self.config.validate()?;
+
         if let (Some(ak), Some(sk)) = (&self.config.access_key_id, &self.config.secret_access_key) {
             let cred = Credential {
                 access_key_id: ak.clone(),
@@ -72,3 +92,10 @@ impl CredentialLoader {
         Ok(None)
     }
 }
+
+#[async_trait]
+impl CredentialLoad<Credential> for CredentialLoader {
+    async fn load(&self) -> crate::Result<Option<Credential>> {
+        CredentialLoader::load(self).await
+    }
+}
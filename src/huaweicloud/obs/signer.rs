@@ -8,6 +8,7 @@ use anyhow::Result;
 use http::header::AUTHORIZATION;
 use http::header::CONTENT_TYPE;
 use http::header::DATE;
+use http::HeaderMap;
 use http::HeaderValue;
 use log::debug;
 use once_cell::sync::Lazy;
@@ -18,10 +19,12 @@ use super::credential::Credential;
 use crate::ctx::SigningContext;
 use crate::ctx::SigningMethod;
 use crate::hash::base64_hmac_sha1;
+use crate::request::sign_to_headers;
 use crate::request::SignableRequest;
 use crate::time::format_http_date;
 use crate::time::now;
 use crate::time::DateTime;
+use crate::Error;
 
 /// Singer that implement Huawei Cloud Object Storage Service Authorization.
 ///
@@ -31,6 +34,7 @@ pub struct Signer {
     bucket: String,
 
     time: Option<DateTime>,
+    time_offset: chrono::Duration,
 }
 
 impl Signer {
@@ -39,28 +43,41 @@ impl Signer {
         Self {
             bucket: bucket.to_string(),
             time: None,
+            time_offset: chrono::Duration::zero(),
         }
     }
 
     /// Specify the signing time.
     ///
-    /// # Note
-    ///
-    /// We should always take current time to sign requests.
-    /// Only use this function for testing.
-    #[cfg(test)]
+    /// Current time will be used if not set. Fixed time is useful to
+    /// generate reproducible signatures.
     pub fn with_time(mut self, time: DateTime) -> Self {
         self.time = Some(time);
         self
     }
 
+    /// Specify the clock skew offset, see [`skew_from_response`](crate::time::skew_from_response).
+    pub fn with_time_offset(mut self, offset: chrono::Duration) -> Self {
+        self.time_offset = offset;
+        self
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "reqsign::sign",
+            level = "debug",
+            skip_all,
+            fields(provider = "huaweicloud")
+        )
+    )]
     fn build(
         &self,
         req: &mut impl SignableRequest,
         method: SigningMethod,
         cred: &Credential,
     ) -> Result<SigningContext> {
-        let now = self.time.unwrap_or_else(now);
+        let now = self.time.unwrap_or_else(|| now() + self.time_offset);
         let mut ctx = req.build()?;
 
         let string_to_sign = string_to_sign(&mut ctx, cred, now, method, &self.bucket)?;
@@ -103,6 +120,8 @@ impl Signer {
     ///
     /// ```no_run
     /// use anyhow::Result;
+    /// use reqsign::HuaweicloudObsConfig;
+    /// use reqsign::HuaweicloudObsCredentialLoader;
     /// use reqsign::HuaweicloudObsSigner;
     /// use reqwest::Client;
     /// use reqwest::Request;
@@ -110,26 +129,43 @@ impl Signer {
     ///
     /// #[tokio::main]
     /// async fn main() -> Result<()> {
-    ///     // Signer will load region and credentials from environment by default.
-    ///     let signer = HuaweicloudObsSigner::builder()
-    ///         .access_key("access_key")
-    ///         .secret_key("123456")
-    ///         .bucket("bucket")
-    ///         .build()?;
+    ///     let config = HuaweicloudObsConfig {
+    ///         access_key_id: Some("access_key".to_string()),
+    ///         secret_access_key: Some("123456".to_string()),
+    ///         ..Default::default()
+    ///     };
+    ///     let loader = HuaweicloudObsCredentialLoader::new(config);
+    ///     let signer = HuaweicloudObsSigner::new("bucket");
     ///     // Construct request
     ///     let url = Url::parse("https://bucket.obs.cn-north-4.myhuaweicloud.com/object.txt")?;
     ///     let mut req = Request::new(http::Method::GET, url);
     ///     // Signing request with Signer
-    ///     signer.sign(&mut req)?;
+    ///     let credential = loader.load().await?.unwrap();
+    ///     signer.sign(&mut req, &credential)?;
     ///     // Sending already signed request.
     ///     let resp = Client::new().execute(req).await?;
     ///     println!("resp got status: {}", resp.status());
     ///     Ok(())
     /// }
     /// ```
-    pub fn sign(&self, req: &mut impl SignableRequest, cred: &Credential) -> Result<()> {
-        let ctx = self.build(req, SigningMethod::Header, cred)?;
-        req.apply(ctx)
+    pub fn sign(&self, req: &mut impl SignableRequest, cred: &Credential) -> crate::Result<()> {
+        let ctx = self
+            .build(req, SigningMethod::Header, cred)
+            .map_err(Error::signing)?;
+        req.apply(ctx).map_err(Error::signing)
+    }
+
+    /// Calculate the headers that will be inserted or changed while signing
+    /// with header, the request will be left untouched.
+    ///
+    /// It's useful to apply the headers to another request like proxies
+    /// that re-emit requests.
+    pub fn sign_to_headers(
+        &self,
+        req: &mut impl SignableRequest,
+        cred: &Credential,
+    ) -> crate::Result<HeaderMap> {
+        sign_to_headers(req, |ctx| self.sign(ctx, cred))
     }
 
     /// Signing request with query.
@@ -138,9 +174,11 @@ impl Signer {
         req: &mut impl SignableRequest,
         expire: Duration,
         cred: &Credential,
-    ) -> Result<()> {
-        let ctx = self.build(req, SigningMethod::Query(expire), cred)?;
-        req.apply(ctx)
+    ) -> crate::Result<()> {
+        let ctx = self
+            .build(req, SigningMethod::Query(expire), cred)
+            .map_err(Error::signing)?;
+        req.apply(ctx).map_err(Error::signing)
     }
 }
 
@@ -237,14 +275,17 @@ fn canonicalize_resource(
     if let SigningMethod::Query(_) = method {
         // Insert security token
         if let Some(token) = &cred.security_token {
-            ctx.query
-                .push(("security-token".to_string(), token.to_string()));
+            ctx.query.push((
+                "x-obs-security-token".to_string(),
+                utf8_percent_encode(token, percent_encoding::NON_ALPHANUMERIC).to_string(),
+            ));
         };
     }
 
     let params = ctx.query_to_vec_with_filter(is_sub_resource);
 
-    let params_str = SigningContext::query_to_string(params, "=", "&");
+    // OBS requires that the query string be percent-decoded.
+    let params_str = SigningContext::query_to_percent_decoded_string(params, "=", "&");
 
     if params_str.is_empty() {
         format!("/{bucket}{}", ctx.path)
@@ -453,4 +494,86 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_sign_with_security_token() -> Result<()> {
+        let config = Config {
+            access_key_id: Some("access_key".to_string()),
+            secret_access_key: Some("123456".to_string()),
+            security_token: Some("sts+token/1=".to_string()),
+        };
+        let loader = CredentialLoader::new(config);
+        let cred = loader.load().await?.unwrap();
+
+        let signer = Signer::new("bucket").with_time(
+            chrono::DateTime::parse_from_rfc2822("Mon, 15 Aug 2022 16:50:12 GMT")
+                .unwrap()
+                .with_timezone(&Utc),
+        );
+
+        let get_req = "http://bucket.obs.cn-north-4.myhuaweicloud.com/object.txt";
+        let mut req = http::Request::get(Uri::from_str(get_req)?).body(())?;
+        req.headers_mut().insert(
+            HeaderName::from_str("Content-MD5")?,
+            HeaderValue::from_str("abc")?,
+        );
+        req.headers_mut().insert(
+            HeaderName::from_str("Content-Type")?,
+            HeaderValue::from_str("text/plain")?,
+        );
+        req.headers_mut().insert(
+            HeaderName::from_str("x-obs-storage-class")?,
+            HeaderValue::from_str("STANDARD")?,
+        );
+        req.headers_mut().insert(
+            HeaderName::from_str("x-obs-meta-a")?,
+            HeaderValue::from_str("a")?,
+        );
+
+        // Signing request with Signer
+        signer.sign(&mut req, &cred)?;
+        let headers = req.headers();
+        assert_eq!("sts+token/1=", headers["x-obs-security-token"].to_str()?);
+
+        // CanonicalizedHeaders:
+        // x-obs-meta-a:a\nx-obs-security-token:sts+token/1=\nx-obs-storage-class:STANDARD
+        assert_eq!(
+            "OBS access_key:fq8B2p4O07hF2b/pAh6qmpMKzHk=",
+            headers["Authorization"].to_str()?,
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sign_query_with_security_token() -> Result<()> {
+        let config = Config {
+            access_key_id: Some("access_key".to_string()),
+            secret_access_key: Some("123456".to_string()),
+            security_token: Some("sts+token/1=".to_string()),
+        };
+        let loader = CredentialLoader::new(config);
+        let cred = loader.load().await?.unwrap();
+
+        let signer = Signer::new("bucket").with_time(
+            chrono::DateTime::parse_from_rfc2822("Mon, 15 Aug 2022 16:50:12 GMT")
+                .unwrap()
+                .with_timezone(&Utc),
+        );
+
+        let get_req = "http://bucket.obs.cn-north-4.myhuaweicloud.com/object.txt";
+        let mut req = http::Request::get(Uri::from_str(get_req)?).body(())?;
+
+        // Signing request with Signer
+        signer.sign_query(&mut req, Duration::from_secs(3600), &cred)?;
+        assert!(req.headers().get("x-obs-security-token").is_none());
+
+        // CanonicalizedResource: /bucket/object.txt?x-obs-security-token=sts+token/1=
+        assert_eq!(
+            Some("x-obs-security-token=sts%2Btoken%2F1%3D&AccessKeyId=access_key&Expires=1660585812&Signature=L5qz%2BsvgM4JFPSjmUGcen%2FVDGmo%3D"),
+            req.uri().query(),
+        );
+
+        Ok(())
+    }
 }
@@ -4,7 +4,8 @@
 //!
 //! ```no_run
 //! use anyhow::Result;
-//! use reqsign::AwsConfigLoader;
+//! use reqsign::AwsConfig;
+//! use reqsign::AwsLoader;
 //! use reqsign::AwsV4Signer;
 //! use reqwest::Client;
 //! use reqwest::Request;
@@ -12,19 +13,19 @@
 //!
 //! #[tokio::main]
 //! async fn main() -> Result<()> {
-//!     // Signer can load region and credentials from environment by default.
-//!     let signer = AwsV4Signer::builder()
-//!         .config_loader(AwsConfigLoader::with_loaded())
-//!         .service("s3")
-//!         .allow_anonymous()
-//!         .build()?;
+//!     let client = Client::new();
+//!     // Loader can load region and credentials from environment by default.
+//!     let config = AwsConfig::default().from_profile().from_env();
+//!     let loader = AwsLoader::new(client.clone(), config);
+//!     let signer = AwsV4Signer::new("s3", "us-east-1");
 //!     // Construct request
 //!     let url = Url::parse("https://s3.amazonaws.com/testbucket")?;
 //!     let mut req = reqwest::Request::new(http::Method::GET, url);
 //!     // Signing request with Signer
-//!     signer.sign(&mut req)?;
+//!     let credential = loader.load().await?.unwrap();
+//!     signer.sign(&mut req, &credential)?;
 //!     // Sending already signed request.
-//!     let resp = Client::new().execute(req).await?;
+//!     let resp = client.execute(req).await?;
 //!     println!("resp got status: {}", resp.status());
 //!     Ok(())
 //! }
@@ -34,20 +35,81 @@
 //!
 //! - [Aliyun OSS][crate::AliyunOssSigner] for Aliyun OSS.
 //! - [AWS SigV4][crate::AwsV4Signer] for AWS services like S3.
+//! - [AWS SigV2][crate::AwsV2Signer] for legacy S3-compatible services.
 //! - [Azure Storage][crate::AzureStorageSigner] for Azure Storage services like Azure Blob Service.
+//! - [Backblaze B2][crate::B2Signer] for Backblaze B2 native API.
 //! - [Google][crate::GoogleSigner] for All google cloud services like Google Cloud Storage Service.
 //! - [Huawei Cloud OBS][crate::HuaweicloudObsSigner] for Huawei Cloud Object Storage Service (OBS).
+//! - [Oracle Cloud Infrastructure][crate::OciSigner] for OCI services like Object Storage.
+//!
+//! # Errors
+//!
+//! Signers and loaders return [`Error`], callers can match on it to decide
+//! whether to refresh credentials ([`Error::CredentialExpired`]), retry later
+//! ([`Error::MetadataUnavailable`]) or fail directly.
 //!
 //! # Features
 //!
 //! reqsign support [`http::Request`] by default. Other request types support are hided
 //! under feature gates to reduce dependencies.
 //!
+//! `hyper::Request` is the same type as [`http::Request`], so it's supported without
+//! extra features.
+//!
+//! - `std`: Enabled by default and required by all other features. Without it reqsign is `no_std`
+//!   with `alloc`, only the hash helpers in [`hash`] and the formatting and parsing helpers in
+//!   [`time`] are available to compute signatures by hand. [`SigningContext`] and the signers
+//!   are built on `http` types that require `std`, so they are not available yet.
+//! - `async_reader`: Enable `hex_sha256_async_reader` to hash bodies from `tokio::io::AsyncRead`.
+//! - `blocking`: Enable [`CredentialLoad::load_blocking`] for callers without an async runtime.
+//! - `jwt`: Enable [`jwt`] to sign JWT assertions for OAuth2 token exchange, enabled by `services-google`.
+//! - `refresher`: Enable [`Refresher`] to refresh credentials in the background.
+//! - `testing`: Enable [`testing`] helpers like static loaders for downstream tests.
+//! - `tracing`: Enable `tracing` spans around credential loading and signing, secrets will never be recorded.
+//! - `reqwest_client`: Enable to use [`reqwest::Client`] as [`HttpSend`] in loaders, enabled by services which have loaders.
 //! - `reqwest_request`: Enable to support signing [`reqwest::Request`]
 //! - `reqwest_blocking_request`: Enable to support signing [`reqwest::blocking::Request`]
+//!
+//! Every service is hidden behind its own feature, all of them are enabled by
+//! `services-all` in default features. Users can disable default features
+//! and enable only the services they use to reduce build time and binary size:
+//!
+//! ```toml
+//! reqsign = { version = "0.9", default-features = false, features = ["services-azblob", "reqwest_request"] }
+//! ```
+//!
+//! - `services-aliyun`: Aliyun OSS.
+//! - `services-aws`: AWS SigV4, SigV4a and SigV2.
+//! - `services-azblob`: Azure Storage.
+//! - `services-b2`: Backblaze B2 native API.
+//! - `services-google`: Google services.
+//! - `services-huaweicloud`: Huawei Cloud OBS.
+//! - `services-oracle`: Oracle Cloud Infrastructure.
+//! - `services-tencent`: Tencent COS.
+//!
+//! # WebAssembly
+//!
+//! All signers can be built for `wasm32-unknown-unknown` to sign requests in
+//! browsers or workers like Cloudflare Workers. Signing doesn't touch network.
+//!
+//! Loaders can be used too, but the `reqwest::Client` is not usable as
+//! [`HttpSend`] there, users should implement [`HttpSend`] for the `fetch` API
+//! of their runtimes. Sources like config files and home dir don't exist in
+//! wasm runtimes either, credentials should be provided via config directly.
+//! AWS loaders retry failed requests with tokio timers which are not available
+//! in wasm, please set `max_retries` to `0` in config.
+//!
+//! # Custom Signers
+//!
+//! Signers for services that reqsign doesn't support can be built on top of
+//! [`SignableRequest`] and [`SigningContext`], so that all request types
+//! supported by reqsign and the canonicalization helpers can be reused.
 
 // Make sure all our public APIs have docs.
 #![warn(missing_docs)]
+#![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
+
+extern crate alloc;
 
 #[cfg(feature = "services-aliyun")]
 mod aliyun;
@@ -64,6 +126,11 @@ mod azure;
 #[cfg(feature = "services-azblob")]
 pub use azure::*;
 
+#[cfg(feature = "services-b2")]
+mod b2;
+#[cfg(feature = "services-b2")]
+pub use b2::*;
+
 #[cfg(feature = "services-google")]
 mod google;
 #[cfg(feature = "services-google")]
@@ -74,13 +141,73 @@ mod huaweicloud;
 #[cfg(feature = "services-huaweicloud")]
 pub use huaweicloud::*;
 
+#[cfg(feature = "services-oracle")]
+mod oracle;
+#[cfg(feature = "services-oracle")]
+pub use oracle::*;
+
 #[cfg(feature = "services-tencent")]
 mod tencent;
 #[cfg(feature = "services-tencent")]
 pub use tencent::*;
 
+// Helpers are shared by services, some of them are unused if only part of
+// services are enabled.
+#[cfg(feature = "std")]
+#[cfg_attr(not(feature = "services-all"), allow(dead_code))]
 mod ctx;
+#[cfg(feature = "std")]
+pub use ctx::SigningContext;
+#[cfg(feature = "std")]
+pub use ctx::SigningMethod;
+#[cfg(any(feature = "services-aws", feature = "services-oracle"))]
 mod dirs;
-mod hash;
+#[cfg(feature = "std")]
+#[cfg_attr(not(feature = "services-all"), allow(dead_code))]
+mod error;
+#[cfg(feature = "std")]
+pub use error::Error;
+#[cfg(feature = "std")]
+pub use error::Result;
+pub mod hash;
+pub use hash::constant_time_eq;
+#[cfg(feature = "async_reader")]
+pub use hash::hex_sha256_async_reader;
+#[cfg(feature = "std")]
+pub use hash::hex_sha256_reader;
+#[cfg(feature = "std")]
+#[cfg_attr(not(feature = "services-all"), allow(dead_code))]
+mod http_send;
+#[cfg(feature = "std")]
+pub use http_send::HttpSend;
+#[cfg(feature = "jwt")]
+pub mod jwt;
+#[cfg(feature = "std")]
+mod loader;
+#[cfg(feature = "std")]
+pub use loader::ChainLoader;
+#[cfg(feature = "std")]
+pub use loader::CredentialLoad;
+#[cfg(feature = "std")]
+pub use loader::RoundRobinLoader;
+#[cfg(feature = "refresher")]
+mod refresh;
+#[cfg(feature = "refresher")]
+pub use refresh::spawn_refresher;
+#[cfg(feature = "refresher")]
+pub use refresh::CredentialExpiry;
+#[cfg(feature = "refresher")]
+pub use refresh::RefreshedCredential;
+#[cfg(feature = "refresher")]
+pub use refresh::Refresher;
+#[cfg(feature = "std")]
+#[cfg_attr(not(feature = "services-all"), allow(dead_code))]
 mod request;
-mod time;
+#[cfg(feature = "std")]
+pub use request::SignableRequest;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod time;
+#[cfg(feature = "std")]
+#[cfg_attr(not(feature = "services-all"), allow(dead_code))]
+mod trace;
@@ -0,0 +1,297 @@
+//! Composable credential loaders.
+
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+#[cfg(feature = "blocking")]
+use anyhow::anyhow;
+use async_trait::async_trait;
+
+use crate::Result;
+
+/// CredentialLoad is implemented by everything that can load a credential.
+///
+/// All loaders provided by reqsign implement it, so they can be composed
+/// via [`ChainLoader`] together with users' own loaders.
+///
+/// This trait is kept object safe via `async_trait` so that loaders of
+/// different types can be stored as `Box<dyn CredentialLoad<C>>`, which
+/// means every call allocates a boxed future. Loaders provided by reqsign
+/// also expose an inherent `load` method like `AwsLoader::load` that
+/// returns an unboxed future, call it directly on hot paths when the
+/// concrete type is known.
+#[async_trait]
+pub trait CredentialLoad<C>: Send + Sync {
+    /// Load credential from sources.
+    ///
+    /// - If succeed, return `Ok(Some(cred))`
+    /// - If not found, return `Ok(None)`
+    /// - If unexpected errors happened, return `Err(err)`
+    async fn load(&self) -> Result<Option<C>>;
+
+    /// Load credential in blocking way.
+    ///
+    /// A current-thread runtime will be created to drive [`CredentialLoad::load`].
+    /// Calling this function inside an async runtime will return an error,
+    /// please use [`CredentialLoad::load`] instead.
+    #[cfg(feature = "blocking")]
+    fn load_blocking(&self) -> Result<Option<C>> {
+        if tokio::runtime::Handle::try_current().is_ok() {
+            return Err(anyhow!(
+                "load_blocking can't be called inside an async runtime, use load instead"
+            )
+            .into());
+        }
+
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?
+            .block_on(self.load())
+    }
+}
+
+#[async_trait]
+impl<C, T: CredentialLoad<C> + ?Sized> CredentialLoad<C> for Arc<T> {
+    async fn load(&self) -> Result<Option<C>> {
+        self.as_ref().load().await
+    }
+}
+
+#[async_trait]
+impl<C, T: CredentialLoad<C> + ?Sized> CredentialLoad<C> for Box<T> {
+    async fn load(&self) -> Result<Option<C>> {
+        self.as_ref().load().await
+    }
+}
+
+/// ChainLoader will try loaders in order and return the first credential.
+///
+/// - Loaders returned `Ok(None)` will be skipped.
+/// - Errors will be returned immediately without trying following loaders.
+///
+/// ```
+/// use async_trait::async_trait;
+/// use reqsign::ChainLoader;
+/// use reqsign::CredentialLoad;
+/// use reqsign::Result;
+///
+/// struct Static(&'static str);
+///
+/// #[async_trait]
+/// impl CredentialLoad<String> for Static {
+///     async fn load(&self) -> Result<Option<String>> {
+///         Ok(Some(self.0.to_string()))
+///     }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// let loader = ChainLoader::new().push(Static("a")).push(Static("b"));
+/// assert_eq!(loader.load().await?, Some("a".to_string()));
+/// # Ok(())
+/// # }
+/// ```
+pub struct ChainLoader<C> {
+    loaders: Vec<Box<dyn CredentialLoad<C>>>,
+}
+
+impl<C> Default for ChainLoader<C> {
+    fn default() -> Self {
+        Self {
+            loaders: Vec::new(),
+        }
+    }
+}
+
+impl<C> Debug for ChainLoader<C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChainLoader")
+            .field("loaders", &self.loaders.len())
+            .finish()
+    }
+}
+
+impl<C> ChainLoader<C> {
+    /// Create a new empty chain.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Push a loader to the end of chain.
+    pub fn push(mut self, loader: impl CredentialLoad<C> + 'static) -> Self {
+        self.loaders.push(Box::new(loader));
+        self
+    }
+}
+
+#[async_trait]
+impl<C: Send> CredentialLoad<C> for ChainLoader<C> {
+    async fn load(&self) -> Result<Option<C>> {
+        for loader in &self.loaders {
+            if let Some(cred) = loader.load().await? {
+                return Ok(Some(cred));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// RoundRobinLoader returns credentials from a fixed set in turn.
+///
+/// It's useful to spread requests over several credentials to avoid
+/// per-key throttling. Credentials are returned in order by default, use
+/// [`RoundRobinLoader::with_selector`] to pick them in other ways.
+///
+/// ```
+/// use reqsign::CredentialLoad;
+/// use reqsign::Result;
+/// use reqsign::RoundRobinLoader;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// let loader = RoundRobinLoader::new(vec!["a", "b"]);
+/// assert_eq!(loader.load().await?, Some("a"));
+/// assert_eq!(loader.load().await?, Some("b"));
+/// assert_eq!(loader.load().await?, Some("a"));
+/// # Ok(())
+/// # }
+/// ```
+pub struct RoundRobinLoader<C> {
+    creds: Vec<C>,
+    next: AtomicUsize,
+    selector: Option<Box<dyn Fn(usize) -> usize + Send + Sync>>,
+}
+
+impl<C> Debug for RoundRobinLoader<C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RoundRobinLoader")
+            .field("creds", &self.creds.len())
+            .finish()
+    }
+}
+
+impl<C> RoundRobinLoader<C> {
+    /// Create a new loader with the set of credentials.
+    pub fn new(creds: Vec<C>) -> Self {
+        Self {
+            creds,
+            next: AtomicUsize::new(0),
+            selector: None,
+        }
+    }
+
+    /// Pick credential via selector instead of in order.
+    ///
+    /// The selector is called with the number of credentials and should
+    /// return the index of credential to use, indexes out of range will
+    /// wrap around.
+    pub fn with_selector(mut self, f: impl Fn(usize) -> usize + Send + Sync + 'static) -> Self {
+        self.selector = Some(Box::new(f));
+        self
+    }
+}
+
+#[async_trait]
+impl<C: Clone + Send + Sync> CredentialLoad<C> for RoundRobinLoader<C> {
+    async fn load(&self) -> Result<Option<C>> {
+        if self.creds.is_empty() {
+            return Ok(None);
+        }
+
+        let idx = match &self.selector {
+            Some(f) => f(self.creds.len()),
+            None => self.next.fetch_add(1, Ordering::Relaxed),
+        };
+        Ok(Some(self.creds[idx % self.creds.len()].clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+
+    use super::*;
+
+    struct MockLoader {
+        result: fn() -> Result<Option<String>>,
+        called: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl CredentialLoad<String> for MockLoader {
+        async fn load(&self) -> Result<Option<String>> {
+            self.called.fetch_add(1, Ordering::SeqCst);
+            (self.result)()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chain_loader() -> Result<()> {
+        let called = Arc::new(AtomicUsize::new(0));
+        let mock = |result| MockLoader {
+            result,
+            called: called.clone(),
+        };
+
+        let loader = ChainLoader::new()
+            .push(mock(|| Ok(None)))
+            .push(mock(|| Ok(Some("a".to_string()))))
+            .push(mock(|| Ok(Some("b".to_string()))));
+        assert_eq!(loader.load().await?, Some("a".to_string()));
+        assert_eq!(called.load(Ordering::SeqCst), 2);
+
+        called.store(0, Ordering::SeqCst);
+        let loader = ChainLoader::new()
+            .push(mock(|| Err(anyhow!("boom").into())))
+            .push(mock(|| Ok(Some("a".to_string()))));
+        assert!(loader.load().await.is_err());
+        assert_eq!(called.load(Ordering::SeqCst), 1);
+
+        let loader = ChainLoader::new().push(mock(|| Ok(None)));
+        assert_eq!(loader.load().await?, None);
+        assert_eq!(ChainLoader::<String>::new().load().await?, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_round_robin_loader() -> Result<()> {
+        let loader = RoundRobinLoader::new(vec!["a", "b", "c"]);
+        let mut loaded = Vec::new();
+        for _ in 0..7 {
+            loaded.push(loader.load().await?.expect("credential must be loaded"));
+        }
+        assert_eq!(loaded, vec!["a", "b", "c", "a", "b", "c", "a"]);
+
+        let loader = RoundRobinLoader::new(vec!["a", "b", "c"]).with_selector(|n| n + 1);
+        assert_eq!(loader.load().await?, Some("b"));
+        assert_eq!(loader.load().await?, Some("b"));
+
+        let loader = RoundRobinLoader::<String>::new(Vec::new());
+        assert_eq!(loader.load().await?, None);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "blocking")]
+    #[test]
+    fn test_load_blocking() -> Result<()> {
+        let called = Arc::new(AtomicUsize::new(0));
+        let loader = ChainLoader::new().push(MockLoader {
+            result: || Ok(Some("a".to_string())),
+            called: called.clone(),
+        });
+        assert_eq!(loader.load_blocking()?, Some("a".to_string()));
+
+        let rt = tokio::runtime::Runtime::new()?;
+        let result = rt.block_on(async { loader.load_blocking() });
+        assert!(result.is_err());
+        assert_eq!(called.load(Ordering::SeqCst), 1);
+
+        Ok(())
+    }
+}
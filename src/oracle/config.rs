@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use ini::Ini;
+use log::debug;
+
+use super::constants::*;
+use crate::dirs::expand_homedir;
+use crate::error::required_together;
+
+/// Config carries all the configuration for Oracle Cloud Infrastructure services.
+#[derive(Clone)]
+#[cfg_attr(test, derive(Debug))]
+pub struct Config {
+    /// `config_file` will be loaded from:
+    ///
+    /// - env value: [`OCI_CONFIG_FILE`]
+    /// - default to: `~/.oci/config`
+    pub config_file: String,
+    /// `profile` will be loaded from:
+    ///
+    /// - env value: [`OCI_CLI_PROFILE`]
+    /// - default to: `DEFAULT`
+    pub profile: String,
+
+    /// `tenancy` will be loaded from:
+    ///
+    /// - this field if it's `is_some`
+    /// - env value: [`OCI_CLI_TENANCY`]
+    /// - profile config: `tenancy`
+    pub tenancy: Option<String>,
+    /// `user` will be loaded from:
+    ///
+    /// - this field if it's `is_some`
+    /// - env value: [`OCI_CLI_USER`]
+    /// - profile config: `user`
+    pub user: Option<String>,
+    /// `fingerprint` will be loaded from:
+    ///
+    /// - this field if it's `is_some`
+    /// - env value: [`OCI_CLI_FINGERPRINT`]
+    /// - profile config: `fingerprint`
+    pub fingerprint: Option<String>,
+    /// `key_file` will be loaded from:
+    ///
+    /// - this field if it's `is_some`
+    /// - env value: [`OCI_CLI_KEY_FILE`]
+    /// - profile config: `key_file`
+    pub key_file: Option<String>,
+    /// `region` will be loaded from:
+    ///
+    /// - this field if it's `is_some`
+    /// - env value: [`OCI_CLI_REGION`]
+    /// - profile config: `region`
+    pub region: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            config_file: "~/.oci/config".to_string(),
+            profile: "DEFAULT".to_string(),
+            tenancy: None,
+            user: None,
+            fingerprint: None,
+            key_file: None,
+            region: None,
+        }
+    }
+}
+
+impl Config {
+    /// Load config from env.
+    pub fn from_env(mut self) -> Self {
+        let envs = env::vars().collect::<HashMap<_, _>>();
+
+        if let Some(v) = envs.get(OCI_CONFIG_FILE) {
+            self.config_file = v.to_string();
+        }
+        if let Some(v) = envs.get(OCI_CLI_PROFILE) {
+            self.profile = v.to_string();
+        }
+        if let Some(v) = envs.get(OCI_CLI_TENANCY) {
+            self.tenancy = Some(v.to_string());
+        }
+        if let Some(v) = envs.get(OCI_CLI_USER) {
+            self.user = Some(v.to_string());
+        }
+        if let Some(v) = envs.get(OCI_CLI_FINGERPRINT) {
+            self.fingerprint = Some(v.to_string());
+        }
+        if let Some(v) = envs.get(OCI_CLI_KEY_FILE) {
+            self.key_file = Some(v.to_string());
+        }
+        if let Some(v) = envs.get(OCI_CLI_REGION) {
+            self.region = Some(v.to_string());
+        }
+
+        self
+    }
+
+    /// Load config from profile in `config_file`.
+    pub fn from_profile(mut self) -> Self {
+        // Ignore all errors happened internally.
+        let _ = self
+            .load_via_profile_config_file()
+            .map_err(|err| debug!("load_via_profile_config_file failed: {err:?}"));
+
+        self
+    }
+
+    fn load_via_profile_config_file(&mut self) -> Result<()> {
+        let path =
+            expand_homedir(&self.config_file).ok_or_else(|| anyhow!("expand homedir failed"))?;
+
+        let _ = fs::metadata(&path)?;
+
+        let conf = Ini::load_from_file(path)?;
+
+        let props = conf
+            .section(Some(&self.profile))
+            .ok_or_else(|| anyhow!("section {} is not found", self.profile))?;
+
+        if let Some(v) = props.get("tenancy") {
+            self.tenancy = Some(v.to_string())
+        }
+        if let Some(v) = props.get("user") {
+            self.user = Some(v.to_string())
+        }
+        if let Some(v) = props.get("fingerprint") {
+            self.fingerprint = Some(v.to_string())
+        }
+        if let Some(v) = props.get("key_file") {
+            self.key_file = Some(v.to_string())
+        }
+        if let Some(v) = props.get("region") {
+            self.region = Some(v.to_string())
+        }
+
+        Ok(())
+    }
+
+    /// Check that `tenancy`, `user`, `fingerprint` and `key_file` are set together.
+    ///
+    /// It's called while loading credentials, users can call it eagerly to
+    /// find misconfigurations on startup.
+    pub fn validate(&self) -> crate::Result<()> {
+        required_together(&[
+            ("tenancy", self.tenancy.is_some()),
+            ("user", self.user.is_some()),
+            ("fingerprint", self.fingerprint.is_some()),
+            ("key_file", self.key_file.is_some()),
+        ])
+    }
+}
@@ -0,0 +1,23 @@
+use percent_encoding::AsciiSet;
+use percent_encoding::NON_ALPHANUMERIC;
+
+// Env values used in oracle cloud services.
+pub const OCI_CONFIG_FILE: &str = "OCI_CONFIG_FILE";
+pub const OCI_CLI_PROFILE: &str = "OCI_CLI_PROFILE";
+pub const OCI_CLI_TENANCY: &str = "OCI_CLI_TENANCY";
+pub const OCI_CLI_USER: &str = "OCI_CLI_USER";
+pub const OCI_CLI_FINGERPRINT: &str = "OCI_CLI_FINGERPRINT";
+pub const OCI_CLI_KEY_FILE: &str = "OCI_CLI_KEY_FILE";
+pub const OCI_CLI_REGION: &str = "OCI_CLI_REGION";
+
+// Headers used in oracle cloud services.
+pub const X_CONTENT_SHA256: &str = "x-content-sha256";
+
+/// AsciiSet for encoding query in `(request-target)`.
+///
+/// - URI encode every byte except the unreserved characters: 'A'-'Z', 'a'-'z', '0'-'9', '-', '.', '_', and '~'.
+pub static OCI_QUERY_ENCODE_SET: AsciiSet = NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
@@ -0,0 +1,16 @@
+//! Oracle Cloud Infrastructure (OCI) service signer
+//!
+//! Use [`OciSigner`][crate::OciSigner] to sign requests for OCI services
+//! like Object Storage.
+
+mod config;
+pub use config::Config as OciConfig;
+
+mod credential;
+pub use credential::Credential as OciCredential;
+pub use credential::Loader as OciLoader;
+
+mod signer;
+pub use signer::Signer as OciSigner;
+
+mod constants;
@@ -0,0 +1,330 @@
+//! Oracle Cloud Infrastructure (OCI) signer
+
+use anyhow::anyhow;
+use anyhow::Result;
+use http::header::AUTHORIZATION;
+use http::header::CONTENT_LENGTH;
+use http::header::CONTENT_TYPE;
+use http::header::DATE;
+use http::HeaderMap;
+use http::HeaderValue;
+use http::Method;
+use log::debug;
+use percent_encoding::utf8_percent_encode;
+use rsa::pkcs1::DecodeRsaPrivateKey;
+use rsa::pkcs1v15::SigningKey;
+use rsa::pkcs8::DecodePrivateKey;
+use rsa::signature::SignatureEncoding;
+use rsa::signature::Signer as _;
+
+use super::constants::*;
+use super::credential::Credential;
+use crate::ctx::SigningContext;
+use crate::hash::base64_encode;
+use crate::request::sign_to_headers;
+use crate::request::SignableRequest;
+use crate::time::format_http_date;
+use crate::time::now;
+use crate::time::DateTime;
+use crate::Error;
+
+/// Singer that implement Oracle Cloud Infrastructure request signature.
+///
+/// - [Request Signatures](https://docs.oracle.com/en-us/iaas/Content/API/Concepts/signingrequests.htm)
+#[derive(Debug)]
+pub struct Signer {
+    time: Option<DateTime>,
+    time_offset: chrono::Duration,
+}
+
+impl Default for Signer {
+    fn default() -> Self {
+        Self {
+            time: None,
+            time_offset: chrono::Duration::zero(),
+        }
+    }
+}
+
+impl Signer {
+    /// Create a new signer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Specify the signing time.
+    ///
+    /// Current time will be used if not set. Fixed time is useful to
+    /// generate reproducible signatures.
+    pub fn with_time(mut self, time: DateTime) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    /// Specify the clock skew offset, see [`skew_from_response`](crate::time::skew_from_response).
+    pub fn with_time_offset(mut self, offset: chrono::Duration) -> Self {
+        self.time_offset = offset;
+        self
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "reqsign::sign",
+            level = "debug",
+            skip_all,
+            fields(provider = "oracle")
+        )
+    )]
+    fn build(&self, req: &mut impl SignableRequest, cred: &Credential) -> Result<SigningContext> {
+        let private_key = rsa::RsaPrivateKey::from_pkcs8_pem(&cred.private_key)
+            .or_else(|_| rsa::RsaPrivateKey::from_pkcs1_pem(&cred.private_key))
+            .map_err(|err| {
+                anyhow!("private key of oci credential must be a PEM encoded RSA key: {err}")
+            })?;
+
+        let now = self.time.unwrap_or_else(|| now() + self.time_offset);
+        let mut ctx = req.build()?;
+
+        ctx.headers.insert(DATE, format_http_date(now).parse()?);
+
+        let mut headers = vec!["date", "(request-target)", "host"];
+        // Requests with body must sign body related headers too.
+        if matches!(ctx.method, Method::POST | Method::PUT | Method::PATCH) {
+            if !ctx.headers.contains_key(CONTENT_LENGTH) {
+                return Err(anyhow!(
+                    "content-length is required to sign request with body"
+                ));
+            }
+            if !ctx.headers.contains_key(X_CONTENT_SHA256) {
+                return Err(anyhow!(
+                    "{X_CONTENT_SHA256} is required to sign request with body"
+                ));
+            }
+            if !ctx.headers.contains_key(CONTENT_TYPE) {
+                ctx.headers
+                    .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            }
+
+            headers.extend([
+                CONTENT_LENGTH.as_str(),
+                CONTENT_TYPE.as_str(),
+                X_CONTENT_SHA256,
+            ]);
+        }
+
+        let string_to_sign = string_to_sign(&mut ctx, &headers)?;
+        debug!("calculated string to sign: {string_to_sign}");
+
+        let signing_key = SigningKey::<rsa::sha2::Sha256>::new_with_prefix(private_key);
+        let signature = base64_encode(&signing_key.sign(string_to_sign.as_bytes()).to_bytes());
+
+        ctx.headers.insert(AUTHORIZATION, {
+            let mut value: HeaderValue = format!(
+                r#"Signature version="1",keyId="{}",algorithm="rsa-sha256",headers="{}",signature="{}""#,
+                cred.key_id(),
+                headers.join(" "),
+                signature
+            )
+            .parse()?;
+            value.set_sensitive(true);
+
+            value
+        });
+
+        Ok(ctx)
+    }
+
+    /// Signing request with header.
+    ///
+    /// Requests with body (`POST`, `PUT` and `PATCH`) must carry
+    /// `content-length` and `x-content-sha256` (base64 encoded SHA256 of
+    /// body) headers. `content-type` will be set to `application/json` if
+    /// not specified.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use anyhow::Result;
+    /// use reqsign::OciConfig;
+    /// use reqsign::OciLoader;
+    /// use reqsign::OciSigner;
+    /// use reqwest::Client;
+    /// use reqwest::Request;
+    /// use reqwest::Url;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let loader = OciLoader::new(OciConfig::default().from_profile().from_env());
+    ///     let signer = OciSigner::new();
+    ///     // Construct request
+    ///     let url = Url::parse(
+    ///         "https://objectstorage.us-phoenix-1.oraclecloud.com/n/namespace/b/bucket/o/object",
+    ///     )?;
+    ///     let mut req = reqwest::Request::new(http::Method::GET, url);
+    ///     // Signing request with Signer
+    ///     let credential = loader.load().await?.unwrap();
+    ///     signer.sign(&mut req, &credential)?;
+    ///     // Sending already signed request.
+    ///     let resp = Client::new().execute(req).await?;
+    ///     println!("resp got status: {}", resp.status());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn sign(&self, req: &mut impl SignableRequest, cred: &Credential) -> crate::Result<()> {
+        let ctx = self.build(req, cred).map_err(Error::signing)?;
+        req.apply(ctx).map_err(Error::signing)
+    }
+
+    /// Calculate the headers that will be inserted or changed while signing
+    /// with header, the request will be left untouched.
+    ///
+    /// It's useful to apply the headers to another request like proxies
+    /// that re-emit requests.
+    pub fn sign_to_headers(
+        &self,
+        req: &mut impl SignableRequest,
+        cred: &Credential,
+    ) -> crate::Result<HeaderMap> {
+        sign_to_headers(req, |ctx| self.sign(ctx, cred))
+    }
+}
+
+/// Construct string to sign.
+///
+/// ## Format
+///
+/// ```text
+/// date: Thu, 05 Jan 2014 21:31:40 GMT
+/// (request-target): get /20160918/instances?availabilityDomain=Pjwf%3A%20PHX-AD-1
+/// host: iaas.us-phoenix-1.oraclecloud.com
+/// ```
+fn string_to_sign(ctx: &mut SigningContext, headers: &[&str]) -> Result<String> {
+    // Query in context has been decoded, encode them back so that the
+    // request target we signed is exactly the same as we sent.
+    ctx.query = ctx
+        .query
+        .iter()
+        .map(|(k, v)| {
+            (
+                utf8_percent_encode(k, &OCI_QUERY_ENCODE_SET).to_string(),
+                utf8_percent_encode(v, &OCI_QUERY_ENCODE_SET).to_string(),
+            )
+        })
+        .collect();
+    // Sort query so that the order we signed will be kept after applying.
+    ctx.query.sort();
+
+    let mut s = String::new();
+    for (idx, header) in headers.iter().enumerate() {
+        if idx != 0 {
+            s.push('\n');
+        }
+
+        s.push_str(header);
+        s.push_str(": ");
+        match *header {
+            "(request-target)" => {
+                s.push_str(&ctx.method.as_str().to_lowercase());
+                s.push(' ');
+                s.push_str(&ctx.path);
+                if !ctx.query.is_empty() {
+                    s.push('?');
+                    for (i, (k, v)) in ctx.query.iter().enumerate() {
+                        if i > 0 {
+                            s.push('&');
+                        }
+                        s.push_str(k);
+                        if !v.is_empty() {
+                            s.push('=');
+                            s.push_str(v);
+                        }
+                    }
+                }
+            }
+            "host" => s.push_str(ctx.authority.as_str()),
+            _ => s.push_str(ctx.header_get_or_default(&header.parse()?)?),
+        }
+    }
+
+    Ok(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use chrono::TimeZone;
+    use chrono::Utc;
+    use http::Request;
+
+    use super::*;
+
+    fn test_credential() -> Credential {
+        Credential {
+            tenancy:
+                "ocid1.tenancy.oc1..aaaaaaaaba3pv6wkcr4jqae5f15p2b2m2yt2j6rx32uzr4h25vqstifsfdsq"
+                    .to_string(),
+            user: "ocid1.user.oc1..aaaaaaaat5nvwcna5j6aqzjcaty5eqbb6qt2jvpkanghtgdaqedqw3rynjq"
+                .to_string(),
+            fingerprint: "20:3b:97:13:55:1c:5b:0d:d3:37:d8:50:4e:c5:3a:34".to_string(),
+            private_key: fs::read_to_string("testdata/services/oracle/oci_api_key.pem")
+                .expect("test key must exist"),
+        }
+    }
+
+    fn test_signer() -> Signer {
+        // The documented example uses `Thu, 05 Jan 2014 21:31:40 GMT` which
+        // has a wrong weekday, use the correct one instead.
+        Signer::new().with_time(Utc.with_ymd_and_hms(2014, 1, 5, 21, 31, 40).unwrap())
+    }
+
+    #[test]
+    fn test_sign_get() -> Result<()> {
+        let mut req = Request::get("https://iaas.us-phoenix-1.oraclecloud.com/20160918/instances?availabilityDomain=Pjwf%3A%20PHX-AD-1&compartmentId=ocid1.compartment.oc1..aaaaaaaam3we6vgnherjq5q2idnccdflvjsnog7mlr6rtdb25gilchfeyjxa&displayName=TeamXInstances&volumeId=ocid1.volume.oc1.phx.abyhqljrgvttnlx73nmrwfaux7kcvzfs3s66izvxf2h4lgvyndsdsnoiwr5q")
+            .body(())?;
+        test_signer().sign(&mut req, &test_credential())?;
+
+        assert_eq!(
+            req.uri(),
+            "https://iaas.us-phoenix-1.oraclecloud.com/20160918/instances?availabilityDomain=Pjwf%3A%20PHX-AD-1&compartmentId=ocid1.compartment.oc1..aaaaaaaam3we6vgnherjq5q2idnccdflvjsnog7mlr6rtdb25gilchfeyjxa&displayName=TeamXInstances&volumeId=ocid1.volume.oc1.phx.abyhqljrgvttnlx73nmrwfaux7kcvzfs3s66izvxf2h4lgvyndsdsnoiwr5q"
+        );
+        assert_eq!(req.headers()[DATE], "Sun, 05 Jan 2014 21:31:40 GMT");
+        assert_eq!(
+            req.headers()[AUTHORIZATION].to_str()?,
+            r#"Signature version="1",keyId="ocid1.tenancy.oc1..aaaaaaaaba3pv6wkcr4jqae5f15p2b2m2yt2j6rx32uzr4h25vqstifsfdsq/ocid1.user.oc1..aaaaaaaat5nvwcna5j6aqzjcaty5eqbb6qt2jvpkanghtgdaqedqw3rynjq/20:3b:97:13:55:1c:5b:0d:d3:37:d8:50:4e:c5:3a:34",algorithm="rsa-sha256",headers="date (request-target) host",signature="CkScWh8P292aJX0j/lsgA+av9PhfW2/czY1nj+sH3JYUoIIO8joJw85SPpiEmaeTQOHODiV1iyE7FpP+FcELvEo7iz/LkuDtwmteZAPwJjNEKjJ2DfOO1sA3mqxClxoiOI5WoMESlmAPhuyUuB9VTI5S8/eqKJJu423U0QWiKD7GntKKJU9Hi59InX7f+X059t5wu3Ln81HCRrY2uo7iofLs/YS+yYp8DUQu7L6yKOHdVixamCWgFQBMNVcTr6DEwbuVTRhz6ZcwgeXOc/bKDEf3BhwCfOLSqJ7hoOYHFTpKV5hUzg8CkV5Csz46IgMoaRY+v1CBSFtGK5Stl3mc7Q==""#
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_put() -> Result<()> {
+        let mut req = Request::put(
+            "https://objectstorage.us-phoenix-1.oraclecloud.com/n/namespace/b/bucket/o/hello.txt",
+        )
+        .header(CONTENT_LENGTH, "13")
+        .header(CONTENT_TYPE, "text/plain")
+        .header(
+            X_CONTENT_SHA256,
+            "3/1gIbsr1bCvZ2KQgJ7DpTGR3YHH9wpLKGiKNiGCmG8=",
+        )
+        .body("Hello, World!")?;
+        test_signer().sign(&mut req, &test_credential())?;
+
+        assert_eq!(
+            req.headers()[AUTHORIZATION].to_str()?,
+            r#"Signature version="1",keyId="ocid1.tenancy.oc1..aaaaaaaaba3pv6wkcr4jqae5f15p2b2m2yt2j6rx32uzr4h25vqstifsfdsq/ocid1.user.oc1..aaaaaaaat5nvwcna5j6aqzjcaty5eqbb6qt2jvpkanghtgdaqedqw3rynjq/20:3b:97:13:55:1c:5b:0d:d3:37:d8:50:4e:c5:3a:34",algorithm="rsa-sha256",headers="date (request-target) host content-length content-type x-content-sha256",signature="H2+du44D8plUjfwIH48MW5RxoHmJ8muLGwifILRHbKSZDPV+ZkST9WrQBLZpNVqLRH15ukPSI8lDMGkGGYZ5PRf4ib/bS2XeyBXd+sUeW34ee18ONZKd066G6elqVljBZXTh3reTHmnFcnVVjdLt5i8cZxCU3ORXyt6YAvueRFfzhND+WgElTfaMQZQYaP545Spltsi/HdNlQEmKCoINMEOh0lss1xbkxqQ8+Jn0cAJfEvrasnf/5aVS9KPK2o5KehaAC40g/DBAjhrd0XlhC262DX9K9plBkbubQHukr2Pb444kNXZUF2N/pzF3dmZRACu+utevr4qGcNYMr68AuQ==""#
+        );
+
+        // Requests with body must carry the content sha256.
+        let mut req = Request::put(
+            "https://objectstorage.us-phoenix-1.oraclecloud.com/n/namespace/b/bucket/o/hello.txt",
+        )
+        .header(CONTENT_LENGTH, "13")
+        .body("Hello, World!")?;
+        assert!(test_signer().sign(&mut req, &test_credential()).is_err());
+
+        Ok(())
+    }
+}
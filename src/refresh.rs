@@ -0,0 +1,306 @@
+//! Refresh credentials proactively in the background.
+
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::sync::Arc;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use log::debug;
+use log::warn;
+use rand::Rng;
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+
+use crate::loader::CredentialLoad;
+use crate::time::now;
+use crate::time::DateTime;
+use crate::Result;
+
+/// CredentialExpiry is implemented by credentials that could be expired.
+pub trait CredentialExpiry {
+    /// The time when this credential will be expired.
+    ///
+    /// Returns `None` if this credential will never be expired.
+    fn expires_at(&self) -> Option<DateTime>;
+}
+
+/// Refresher refreshes credentials in the background before they are expired.
+///
+/// The credential is reloaded at a random point between `fraction` and
+/// `fraction + jitter` of its remaining lifetime, so that requests never
+/// wait for loading credentials and many processes will not refresh at the
+/// same time.
+///
+/// Loaders may return their cached credential until it's near expiry, the
+/// refresher will keep retrying at the shrunk lifetime until a new one is
+/// returned.
+#[derive(Debug, Clone)]
+pub struct Refresher {
+    fraction: f64,
+    jitter: f64,
+    retry_interval: Duration,
+    static_interval: Duration,
+}
+
+impl Default for Refresher {
+    fn default() -> Self {
+        Self {
+            fraction: 0.5,
+            jitter: 0.25,
+            retry_interval: Duration::from_secs(10),
+            static_interval: Duration::from_secs(300),
+        }
+    }
+}
+
+impl Refresher {
+    /// Create a new refresher.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Specify the fraction of credential lifetime to refresh at.
+    ///
+    /// Default to `0.5`, the value will be clamped to `[0, 1]`.
+    pub fn refresh_fraction(mut self, fraction: f64) -> Self {
+        self.fraction = fraction.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Specify the random fraction of credential lifetime that added to
+    /// `refresh_fraction`.
+    ///
+    /// Default to `0.25`, the sum of them will be clamped to `[0, 1]`.
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Specify the interval to retry if loading failed or returned no
+    /// credential.
+    ///
+    /// Default to 10 seconds.
+    pub fn retry_interval(mut self, interval: Duration) -> Self {
+        self.retry_interval = interval;
+        self
+    }
+
+    /// Specify the interval to reload credentials that never be expired.
+    ///
+    /// Default to 5 minutes.
+    pub fn static_interval(mut self, interval: Duration) -> Self {
+        self.static_interval = interval;
+        self
+    }
+
+    /// Spawn the refresher on the given runtime.
+    ///
+    /// The first credential is loaded in the background too, the returned
+    /// [`RefreshedCredential`] will be empty until it's loaded.
+    pub fn spawn<C, L>(self, loader: L, handle: &Handle) -> RefreshedCredential<C>
+    where
+        C: CredentialExpiry + Send + Sync + 'static,
+        L: CredentialLoad<C> + 'static,
+    {
+        let current = Arc::new(RwLock::new(None));
+
+        let task = {
+            let current = current.clone();
+            handle.spawn(async move {
+                loop {
+                    let delay = match loader.load().await {
+                        Ok(Some(cred)) => {
+                            let delay = self.next_refresh(cred.expires_at());
+                            *current.write().expect("lock poisoned") = Some(Arc::new(cred));
+                            delay
+                        }
+                        Ok(None) => {
+                            warn!("refresher loaded no credential, retry later");
+                            self.retry_interval
+                        }
+                        Err(err) => {
+                            warn!("refresher failed to load credential, retry later: {err}");
+                            self.retry_interval
+                        }
+                    };
+
+                    debug!("refresher will refresh credential after {delay:?}");
+                    tokio::time::sleep(delay).await;
+                }
+            })
+        };
+
+        RefreshedCredential { current, task }
+    }
+
+    /// Calculate the delay before next refresh.
+    fn next_refresh(&self, expires_at: Option<DateTime>) -> Duration {
+        let Some(expires_at) = expires_at else {
+            return self.static_interval;
+        };
+
+        let lifetime = match (expires_at - now()).to_std() {
+            Ok(lifetime) if !lifetime.is_zero() => lifetime,
+            // Credential has been expired, retry later.
+            _ => return self.retry_interval,
+        };
+
+        let fraction = self.fraction + rand::thread_rng().gen_range(0.0..=self.jitter);
+        lifetime.mul_f64(fraction.min(1.0))
+    }
+}
+
+/// Spawn a [`Refresher`] with default settings on the given runtime.
+///
+/// ```no_run
+/// use reqsign::spawn_refresher;
+/// use reqsign::AwsConfig;
+/// use reqsign::AwsLoader;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let loader = AwsLoader::new(reqwest::Client::new(), AwsConfig::default().from_env());
+/// let cred = spawn_refresher(loader, &tokio::runtime::Handle::current());
+///
+/// // Read the current credential without waiting for loading.
+/// if let Some(cred) = cred.current() {
+///     // sign requests with cred.
+/// }
+/// # }
+/// ```
+pub fn spawn_refresher<C, L>(loader: L, handle: &Handle) -> RefreshedCredential<C>
+where
+    C: CredentialExpiry + Send + Sync + 'static,
+    L: CredentialLoad<C> + 'static,
+{
+    Refresher::new().spawn(loader, handle)
+}
+
+/// RefreshedCredential holds the credential that kept fresh by [`Refresher`].
+///
+/// The background task will be aborted while it's dropped.
+pub struct RefreshedCredential<C> {
+    current: Arc<RwLock<Option<Arc<C>>>>,
+    task: JoinHandle<()>,
+}
+
+impl<C> RefreshedCredential<C> {
+    /// Get the current credential.
+    ///
+    /// Returns `None` if the first credential has not been loaded yet.
+    pub fn current(&self) -> Option<Arc<C>> {
+        self.current.read().expect("lock poisoned").clone()
+    }
+}
+
+impl<C> Debug for RefreshedCredential<C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RefreshedCredential")
+            .field(
+                "loaded",
+                &self.current.read().expect("lock poisoned").is_some(),
+            )
+            .finish()
+    }
+}
+
+impl<C> Drop for RefreshedCredential<C> {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// RefreshedCredential can be used as a loader, for example, in the front
+/// of a [`ChainLoader`][crate::ChainLoader].
+#[async_trait]
+impl<C: Clone + Send + Sync> CredentialLoad<C> for RefreshedCredential<C> {
+    async fn load(&self) -> Result<Option<C>> {
+        Ok(self.current().map(|cred| cred.as_ref().clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+
+    #[derive(Clone)]
+    struct MockCredential(usize, Option<DateTime>);
+
+    impl CredentialExpiry for MockCredential {
+        fn expires_at(&self) -> Option<DateTime> {
+            self.1
+        }
+    }
+
+    struct MockLoader {
+        lifetime: Option<chrono::Duration>,
+        called: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl CredentialLoad<MockCredential> for MockLoader {
+        async fn load(&self) -> Result<Option<MockCredential>> {
+            let n = self.called.fetch_add(1, Ordering::SeqCst);
+            Ok(Some(MockCredential(n, self.lifetime.map(|v| now() + v))))
+        }
+    }
+
+    #[test]
+    fn test_next_refresh() {
+        let refresher = Refresher::new()
+            .retry_interval(Duration::from_secs(1))
+            .static_interval(Duration::from_secs(2));
+        assert_eq!(refresher.next_refresh(None), Duration::from_secs(2));
+        assert_eq!(
+            refresher.next_refresh(Some(now() - chrono::Duration::seconds(1))),
+            Duration::from_secs(1)
+        );
+
+        for _ in 0..100 {
+            let delay = refresher.next_refresh(Some(now() + chrono::Duration::seconds(1000)));
+            assert!(delay >= Duration::from_secs(499), "{delay:?}");
+            assert!(delay <= Duration::from_secs(750), "{delay:?}");
+        }
+
+        let refresher = Refresher::new().refresh_fraction(0.9).jitter(0.5);
+        let delay = refresher.next_refresh(Some(now() + chrono::Duration::seconds(1000)));
+        assert!(delay <= Duration::from_secs(1000), "{delay:?}");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_refresher() -> Result<()> {
+        let called = Arc::new(AtomicUsize::new(0));
+        let loader = MockLoader {
+            lifetime: Some(chrono::Duration::milliseconds(200)),
+            called: called.clone(),
+        };
+
+        let cred = Refresher::new()
+            .refresh_fraction(0.5)
+            .jitter(0.0)
+            .spawn(loader, &Handle::current());
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(cred.current().expect("credential must be loaded").0, 0);
+        assert_eq!(cred.load().await?.expect("credential must be loaded").0, 0);
+
+        // Credential should be refreshed every 100ms.
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(called.load(Ordering::SeqCst) >= 3);
+        assert!(cred.current().expect("credential must be loaded").0 >= 2);
+
+        // Background task should be aborted after dropped.
+        drop(cred);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let n = called.load(Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(called.load(Ordering::SeqCst), n);
+
+        Ok(())
+    }
+}
@@ -1,3 +1,5 @@
+use crate::error::required_together;
+
 /// Config carries all the configuration for Tencent COS services.
 #[derive(Clone, Default)]
 #[cfg_attr(test, derive(Debug))]
@@ -14,4 +16,24 @@ pub struct Config {
     ///
     /// - this field if it's `is_some`
     pub security_token: Option<String>,
+    /// `cvm_role_name` will be loaded from
+    ///
+    /// - this field if it's `is_some`
+    ///
+    /// If not set, the role bound to the cvm instance will be discovered
+    /// from metadata service.
+    pub cvm_role_name: Option<String>,
+}
+
+impl Config {
+    /// Check that `access_key_id` and `secret_access_key` are set together.
+    ///
+    /// It's called while loading credentials, users can call it eagerly to
+    /// find misconfigurations on startup.
+    pub fn validate(&self) -> crate::Result<()> {
+        required_together(&[
+            ("access_key_id", self.access_key_id.is_some()),
+            ("secret_access_key", self.secret_access_key.is_some()),
+        ])
+    }
 }
@@ -0,0 +1,14 @@
+use percent_encoding::AsciiSet;
+use percent_encoding::NON_ALPHANUMERIC;
+
+// Default endpoint of cvm metadata service.
+pub const CVM_METADATA_ENDPOINT: &str = "http://metadata.tencentyun.com";
+
+/// AsciiSet for [Tencent COS UrlEncode](https://cloud.tencent.com/document/product/436/7778)
+///
+/// - URI encode every byte except the unreserved characters: 'A'-'Z', 'a'-'z', '0'-'9', '-', '.', '_', and '~'.
+pub static TENCENT_URI_ENCODE_SET: AsciiSet = NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
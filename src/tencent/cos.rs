@@ -5,24 +5,37 @@ use std::time::Duration;
 use anyhow::Result;
 use http::header::AUTHORIZATION;
 use http::header::DATE;
+use http::HeaderMap;
 use http::HeaderValue;
 use percent_encoding::percent_decode_str;
 use percent_encoding::utf8_percent_encode;
 
+use super::constants::TENCENT_URI_ENCODE_SET;
 use super::credential::Credential;
 use crate::ctx::SigningContext;
 use crate::ctx::SigningMethod;
 use crate::hash::hex_hmac_sha1;
 use crate::hash::hex_sha1;
+use crate::request::sign_to_headers;
 use crate::request::SignableRequest;
 use crate::time;
 use crate::time::format_http_date;
 use crate::time::DateTime;
+use crate::Error;
 
 /// Singer for Tencent COS.
-#[derive(Default)]
 pub struct Signer {
     time: Option<DateTime>,
+    time_offset: chrono::Duration,
+}
+
+impl Default for Signer {
+    fn default() -> Self {
+        Self {
+            time: None,
+            time_offset: chrono::Duration::zero(),
+        }
+    }
 }
 
 impl Signer {
@@ -38,36 +51,45 @@ impl Signer {
 
     /// Specify the signing time.
     ///
-    /// # Note
-    ///
-    /// We should always take current time to sign requests.
-    /// Only use this function for testing.
-    #[cfg(test)]
+    /// Current time will be used if not set. Fixed time is useful to
+    /// generate reproducible signatures.
     pub fn with_time(mut self, time: DateTime) -> Self {
         self.time = Some(time);
         self
     }
 
+    /// Specify the clock skew offset, see [`skew_from_response`](crate::time::skew_from_response).
+    pub fn with_time_offset(mut self, offset: chrono::Duration) -> Self {
+        self.time_offset = offset;
+        self
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "reqsign::sign",
+            level = "debug",
+            skip_all,
+            fields(provider = "tencent")
+        )
+    )]
     fn build(
         &self,
         req: &mut impl SignableRequest,
         method: SigningMethod,
         cred: &Credential,
     ) -> Result<SigningContext> {
-        let now = self.time.unwrap_or_else(time::now);
+        if cred.is_expired() {
+            return Err(Error::CredentialExpired.into());
+        }
+
+        let now = self.time.unwrap_or_else(|| time::now() + self.time_offset);
         let mut ctx = req.build()?;
 
+        // Security token must be inserted before building signature so that
+        // it could be signed.
         match method {
             SigningMethod::Header => {
-                let signature = build_signature(&mut ctx, cred, now, Duration::from_secs(3600));
-
-                ctx.headers.insert(DATE, format_http_date(now).parse()?);
-                ctx.headers.insert(AUTHORIZATION, {
-                    let mut value: HeaderValue = signature.parse()?;
-                    value.set_sensitive(true);
-                    value
-                });
-
                 if let Some(token) = &cred.security_token {
                     ctx.headers.insert("x-cos-security-token", {
                         let mut value: HeaderValue = token.parse()?;
@@ -76,19 +98,28 @@ impl Signer {
                         value
                     });
                 }
-            }
-            SigningMethod::Query(expire) => {
-                let signature = build_signature(&mut ctx, cred, now, expire);
 
-                ctx.headers.insert(DATE, format_http_date(now).parse()?);
-                ctx.query_append(&signature);
+                let signature = build_signature(&mut ctx, cred, now, Duration::from_secs(3600));
 
+                ctx.headers.insert(DATE, format_http_date(now).parse()?);
+                ctx.headers.insert(AUTHORIZATION, {
+                    let mut value: HeaderValue = signature.parse()?;
+                    value.set_sensitive(true);
+                    value
+                });
+            }
+            SigningMethod::Query(expire) => {
                 if let Some(token) = &cred.security_token {
                     ctx.query_push(
                         "x-cos-security-token".to_string(),
-                        utf8_percent_encode(token, percent_encoding::NON_ALPHANUMERIC).to_string(),
+                        utf8_percent_encode(token, &TENCENT_URI_ENCODE_SET).to_string(),
                     );
                 }
+
+                let signature = build_signature(&mut ctx, cred, now, expire);
+
+                ctx.headers.insert(DATE, format_http_date(now).parse()?);
+                ctx.query_append(&signature);
             }
         }
 
@@ -96,9 +127,24 @@ impl Signer {
     }
 
     /// Signing request with header.
-    pub fn sign(&self, req: &mut impl SignableRequest, cred: &Credential) -> Result<()> {
-        let ctx = self.build(req, SigningMethod::Header, cred)?;
-        req.apply(ctx)
+    pub fn sign(&self, req: &mut impl SignableRequest, cred: &Credential) -> crate::Result<()> {
+        let ctx = self
+            .build(req, SigningMethod::Header, cred)
+            .map_err(Error::signing)?;
+        req.apply(ctx).map_err(Error::signing)
+    }
+
+    /// Calculate the headers that will be inserted or changed while signing
+    /// with header, the request will be left untouched.
+    ///
+    /// It's useful to apply the headers to another request like proxies
+    /// that re-emit requests.
+    pub fn sign_to_headers(
+        &self,
+        req: &mut impl SignableRequest,
+        cred: &Credential,
+    ) -> crate::Result<HeaderMap> {
+        sign_to_headers(req, |ctx| self.sign(ctx, cred))
     }
 
     /// Signing request with query.
@@ -107,9 +153,11 @@ impl Signer {
         req: &mut impl SignableRequest,
         expire: Duration,
         cred: &Credential,
-    ) -> Result<()> {
-        let ctx = self.build(req, SigningMethod::Query(expire), cred)?;
-        req.apply(ctx)
+    ) -> crate::Result<()> {
+        let ctx = self
+            .build(req, SigningMethod::Query(expire), cred)
+            .map_err(Error::signing)?;
+        req.apply(ctx).map_err(Error::signing)
     }
 }
 
@@ -131,11 +179,13 @@ fn build_signature(
         .query
         .iter()
         .map(|(k, v)| {
+            // Query in context has been percent encoded, decode it before
+            // encoding to avoid double encoding.
+            let k = percent_decode_str(k).decode_utf8_lossy().to_lowercase();
+            let v = percent_decode_str(v).decode_utf8_lossy();
             (
-                utf8_percent_encode(&k.to_lowercase(), percent_encoding::NON_ALPHANUMERIC)
-                    .to_string(),
-                utf8_percent_encode(&v.to_lowercase(), percent_encoding::NON_ALPHANUMERIC)
-                    .to_string(),
+                utf8_percent_encode(&k, &TENCENT_URI_ENCODE_SET).to_string(),
+                utf8_percent_encode(&v, &TENCENT_URI_ENCODE_SET).to_string(),
             )
         })
         .collect::<Vec<_>>();
@@ -158,7 +208,15 @@ fn build_signature(
     http_string.push_str(&SigningContext::query_to_string(params, "=", "&"));
     http_string.push('\n');
     http_string.push_str(&SigningContext::header_to_string(
-        ctx.header_to_vec_with_prefix(""),
+        ctx.header_to_vec_with_prefix("")
+            .into_iter()
+            .map(|(k, v)| {
+                (
+                    k,
+                    utf8_percent_encode(&v, &TENCENT_URI_ENCODE_SET).to_string(),
+                )
+            })
+            .collect(),
         "=",
         "&",
     ));
@@ -174,5 +232,64 @@ fn build_signature(
 
     let signature = hex_hmac_sha1(sign_key.as_bytes(), string_to_sign.as_bytes());
 
-    format!("q-sign-algorithm=sha1&q-ak={}&q-sign-time={}&q-key-time={}&q-header-list={}&q-url-param-list={}&q-signature={}", cred.access_key_id, sign_key, key_time, header_list, param_list, signature)
+    format!("q-sign-algorithm=sha1&q-ak={}&q-sign-time={}&q-key-time={}&q-header-list={}&q-url-param-list={}&q-signature={}", cred.access_key_id, key_time, key_time, header_list, param_list, signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use chrono::Utc;
+
+    use super::*;
+
+    fn sts_credential() -> Credential {
+        Credential {
+            access_key_id: "access_key_id".to_string(),
+            secret_access_key: "secret".to_string(),
+            security_token: Some("sts+token/1=".to_string()),
+            expires_in: None,
+        }
+    }
+
+    #[test]
+    fn test_sign_with_security_token() -> Result<()> {
+        let now = Utc.with_ymd_and_hms(2022, 3, 1, 8, 12, 34).unwrap();
+
+        let mut req = http::Request::get(
+            "https://examplebucket-1250000000.cos.ap-beijing.myqcloud.com/example.txt",
+        )
+        .body("")?;
+        Signer::new()
+            .with_time(now)
+            .sign(&mut req, &sts_credential())?;
+
+        assert_eq!(req.headers()["x-cos-security-token"], "sts+token/1=");
+        assert_eq!(
+            req.headers()[AUTHORIZATION].to_str()?,
+            "q-sign-algorithm=sha1&q-ak=access_key_id&q-sign-time=1646122354;1646125954&q-key-time=1646122354;1646125954&q-header-list=x-cos-security-token&q-url-param-list=&q-signature=ea445aaa58329caf20f91cdd1e5d1bdbee74b920"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_query_with_security_token() -> Result<()> {
+        let now = Utc.with_ymd_and_hms(2022, 3, 1, 8, 12, 34).unwrap();
+
+        let mut req = http::Request::get(
+            "https://examplebucket-1250000000.cos.ap-beijing.myqcloud.com/example.txt",
+        )
+        .body("")?;
+        Signer::new().with_time(now).sign_query(
+            &mut req,
+            Duration::from_secs(3600),
+            &sts_credential(),
+        )?;
+
+        assert!(req.headers().get("x-cos-security-token").is_none());
+        assert_eq!(
+            req.uri().query(),
+            Some("x-cos-security-token=sts%2Btoken%2F1%3D&q-sign-algorithm=sha1&q-ak=access_key_id&q-sign-time=1646122354;1646125954&q-key-time=1646122354;1646125954&q-header-list=&q-url-param-list=x-cos-security-token&q-signature=813844f1bf9668594f798c272d8592a612f098df")
+        );
+        Ok(())
+    }
 }
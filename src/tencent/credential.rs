@@ -1,9 +1,22 @@
 use std::sync::Arc;
 use std::sync::Mutex;
 
+use anyhow::anyhow;
 use anyhow::Result;
+use async_trait::async_trait;
+use log::debug;
+use serde::Deserialize;
 
 use super::config::Config;
+use super::constants::CVM_METADATA_ENDPOINT;
+use crate::http_send::check_available;
+use crate::http_send::HttpSend;
+use crate::loader::CredentialLoad;
+use crate::time::now;
+use crate::time::parse_rfc3339;
+use crate::time::DateTime;
+use crate::trace::credential_refreshed;
+use crate::trace::record_cache_hit;
 
 /// Credential for cos.
 #[derive(Clone)]
@@ -15,35 +28,127 @@ pub struct Credential {
     pub secret_access_key: String,
     /// security_token
     pub security_token: Option<String>,
+    /// expires in for credential.
+    pub expires_in: Option<DateTime>,
+}
+
+impl Credential {
+    /// is current cred is valid?
+    pub fn is_valid(&self) -> bool {
+        if self.access_key_id.is_empty() || self.secret_access_key.is_empty() {
+            return false;
+        }
+        // Take 120s as buffer to avoid edge cases.
+        if let Some(valid) = self
+            .expires_in
+            .map(|v| v > now() + chrono::Duration::minutes(2))
+        {
+            return valid;
+        }
+
+        true
+    }
+
+    /// The time when this credential will be expired.
+    ///
+    /// Returns `None` for long-lived credentials that never expire.
+    pub fn expires_at(&self) -> Option<DateTime> {
+        self.expires_in
+    }
+
+    /// Whether this credential has been expired.
+    pub fn is_expired(&self) -> bool {
+        self.expires_in.is_some_and(|v| v <= now())
+    }
+
+    /// Whether this credential will be expired within `duration`.
+    ///
+    /// Long-lived credentials that never expire always return `false`.
+    pub fn is_expired_within(&self, duration: std::time::Duration) -> bool {
+        let expires_in = match self.expires_in {
+            Some(v) => v,
+            None => return false,
+        };
+        match chrono::Duration::from_std(duration)
+            .ok()
+            .and_then(|d| now().checked_add_signed(d))
+        {
+            Some(deadline) => expires_in <= deadline,
+            // Duration is too large to represent, the credential must be
+            // expired within it.
+            None => true,
+        }
+    }
+}
+
+#[cfg(feature = "refresher")]
+impl crate::CredentialExpiry for Credential {
+    fn expires_at(&self) -> Option<DateTime> {
+        self.expires_in
+    }
 }
 
 /// CredentialLoader will load credential from different methods.
-#[derive(Default)]
 #[cfg_attr(test, derive(Debug))]
 pub struct CredentialLoader {
+    client: Arc<dyn HttpSend>,
     config: Config,
 
+    disable_cvm_metadata: bool,
+
     credential: Arc<Mutex<Option<Credential>>>,
+    refresh_lock: Arc<tokio::sync::Mutex<()>>,
 }
 
 impl CredentialLoader {
-    /// Create a new loader via config.
-    pub fn new(config: Config) -> Self {
+    /// Create a new loader via client and config.
+    pub fn new(client: impl HttpSend, config: Config) -> Self {
         Self {
+            client: Arc::new(client),
             config,
 
+            disable_cvm_metadata: false,
+
             credential: Arc::default(),
+            refresh_lock: Arc::default(),
         }
     }
 
+    /// Disable load from cvm metadata.
+    pub fn with_disable_cvm_metadata(mut self) -> Self {
+        self.disable_cvm_metadata = true;
+        self
+    }
+
     /// Load credential
-    pub async fn load(&self) -> Result<Option<Credential>> {
-        // Return cached credential if it's valid.
-        if let Some(cred) = self.credential.lock().expect("lock poisoned").clone() {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "reqsign::load",
+            level = "debug",
+            skip_all,
+            fields(provider = "tencent", cache_hit = tracing::field::Empty)
+        )
+    )]
+    pub async fn load(&self) -> crate::Result<Option<Credential>> {
+        if let Some(cred) = self.cached() {
+            record_cache_hit(true);
             return Ok(Some(cred));
         }
 
+        // Only one task loads credential at the same time, others will wait
+        // and reuse its result.
+        let _guard = self.refresh_lock.lock().await;
+        if let Some(cred) = self.cached() {
+            record_cache_hit(true);
+            return Ok(Some(cred));
+        }
+        record_cache_hit(false);
+
         let cred = self.load_inner().await?;
+        if let Some(cred) = &cred {
+            credential_refreshed("tencent", cred.expires_in);
+        }
 
         let mut lock = self.credential.lock().expect("lock poisoned");
         *lock = cred.clone();
@@ -51,24 +156,216 @@ impl CredentialLoader {
         Ok(cred)
     }
 
+    /// Return cached credential if it's valid.
+    fn cached(&self) -> Option<Credential> {
+        self.credential
+            .lock()
+            .expect("lock poisoned")
+            .clone()
+            .filter(|cred| cred.is_valid())
+    }
+
     async fn load_inner(&self) -> Result<Option<Credential>> {
         if let Some(cred) = self.load_via_config()? {
             return Ok(Some(cred));
         }
 
+        if let Ok(Some(cred)) = self
+            .load_via_cvm_metadata()
+            .await
+            .map_err(|err| debug!("load credential via cvm_metadata failed: {err:?}"))
+        {
+            return Ok(Some(cred));
+        }
+
         Ok(None)
     }
 
     fn load_via_config(&self) -> Result<Option<Credential>> {
+        self.config.validate()?;
+
         if let (Some(ak), Some(sk)) = (&self.config.access_key_id, &self.config.secret_access_key) {
             let cred = Credential {
                 access_key_id: ak.clone(),
                 secret_access_key: sk.clone(),
                 security_token: self.config.security_token.clone(),
+                expires_in: None,
             };
             return Ok(Some(cred));
         }
 
         Ok(None)
     }
+
+    /// Load temporary credential of the CAM role bound to cvm instance.
+    ///
+    /// Reference: [Instance role](https://cloud.tencent.com/document/product/213/47668)
+    async fn load_via_cvm_metadata(&self) -> Result<Option<Credential>> {
+        if self.disable_cvm_metadata {
+            return Ok(None);
+        }
+
+        let role_name = match &self.config.cvm_role_name {
+            Some(role_name) => role_name.clone(),
+            None => {
+                let content = self
+                    .cvm_metadata_get("/latest/meta-data/cam/security-credentials/")
+                    .await?;
+                match content.lines().next() {
+                    Some(role_name) if !role_name.trim().is_empty() => role_name.trim().to_string(),
+                    _ => return Ok(None),
+                }
+            }
+        };
+
+        let content = self
+            .cvm_metadata_get(&format!(
+                "/latest/meta-data/cam/security-credentials/{role_name}"
+            ))
+            .await?;
+        let resp: CvmMetadataCamSecurityCredentials = serde_json::from_str(&content)?;
+        if resp.code != "Success" {
+            return Err(anyhow!(
+                "request to Tencent CVM Metadata Services failed: {content}"
+            ));
+        }
+
+        let cred = Credential {
+            access_key_id: resp.tmp_secret_id,
+            secret_access_key: resp.tmp_secret_key,
+            security_token: Some(resp.token),
+            expires_in: Some(parse_rfc3339(&resp.expiration)?),
+        };
+
+        Ok(Some(cred))
+    }
+
+    async fn cvm_metadata_get(&self, path: &str) -> Result<String> {
+        let req = http::Request::get(format!("{CVM_METADATA_ENDPOINT}{path}")).body(Vec::new())?;
+
+        let resp = check_available(self.client.send(req).await)?;
+        if resp.status() != http::StatusCode::OK {
+            let content = String::from_utf8_lossy(resp.body());
+            return Err(anyhow!(
+                "request to Tencent CVM Metadata Services failed: {content}"
+            ));
+        }
+
+        Ok(String::from_utf8(resp.into_body())?)
+    }
+}
+
+#[async_trait]
+impl CredentialLoad<Credential> for CredentialLoader {
+    async fn load(&self) -> crate::Result<Option<Credential>> {
+        CredentialLoader::load(self).await
+    }
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+struct CvmMetadataCamSecurityCredentials {
+    tmp_secret_id: String,
+    tmp_secret_key: String,
+    token: String,
+    expiration: String,
+
+    code: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use http::StatusCode;
+
+    use super::*;
+
+    /// Mock cvm metadata service which has role `test_role` bound.
+    #[derive(Debug, Default)]
+    struct MockCvmMetadata {
+        requests: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl HttpSend for MockCvmMetadata {
+        async fn send(&self, req: http::Request<Vec<u8>>) -> Result<http::Response<Vec<u8>>> {
+            let (status, body) = match req.uri().path() {
+                "/latest/meta-data/cam/security-credentials/" => {
+                    (StatusCode::OK, "test_role".to_string())
+                }
+                "/latest/meta-data/cam/security-credentials/test_role" => (
+                    StatusCode::OK,
+                    r#"{"TmpSecretId":"tmp_secret_id","TmpSecretKey":"tmp_secret_key","ExpiredTime":4070908800,"Expiration":"2099-01-01T00:00:00Z","Token":"token","Code":"Success"}"#.to_string(),
+                ),
+                _ => (StatusCode::NOT_FOUND, String::new()),
+            };
+            self.requests.lock().unwrap().push(req.uri().to_string());
+
+            Ok(http::Response::builder()
+                .status(status)
+                .body(body.into_bytes())?)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_load_via_cvm_metadata() -> Result<()> {
+        let client = Arc::new(MockCvmMetadata::default());
+        let loader = CredentialLoader::new(client.clone(), Config::default());
+
+        let cred = loader.load().await?.expect("credential must exist");
+        assert_eq!(cred.access_key_id, "tmp_secret_id");
+        assert_eq!(cred.secret_access_key, "tmp_secret_key");
+        assert_eq!(cred.security_token.as_deref(), Some("token"));
+        assert_eq!(
+            cred.expires_in,
+            Some(parse_rfc3339("2099-01-01T00:00:00Z")?)
+        );
+        assert_eq!(
+            *client.requests.lock().unwrap(),
+            vec![
+                "http://metadata.tencentyun.com/latest/meta-data/cam/security-credentials/",
+                "http://metadata.tencentyun.com/latest/meta-data/cam/security-credentials/test_role",
+            ]
+        );
+
+        // Credential should be cached.
+        loader.load().await?.expect("credential must exist");
+        assert_eq!(client.requests.lock().unwrap().len(), 2);
+
+        // Role name in config should be used directly.
+        let client = Arc::new(MockCvmMetadata::default());
+        let loader = CredentialLoader::new(
+            client.clone(),
+            Config {
+                cvm_role_name: Some("test_role".to_string()),
+                ..Default::default()
+            },
+        );
+        loader.load().await?.expect("credential must exist");
+        assert_eq!(client.requests.lock().unwrap().len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_load_with_partial_config() -> Result<()> {
+        let client = Arc::new(MockCvmMetadata::default());
+        let loader = CredentialLoader::new(
+            client.clone(),
+            Config {
+                secret_access_key: Some("secret_access_key".to_string()),
+                ..Default::default()
+            },
+        );
+
+        // Misconfigurations should not fall back to cvm metadata.
+        let err = loader.load().await.expect_err("load must fail");
+        assert!(matches!(err, crate::Error::InvalidConfig(_)), "{err:?}");
+        assert_eq!(
+            err.to_string(),
+            "invalid config: access_key_id is required when secret_access_key is set"
+        );
+        assert!(client.requests.lock().unwrap().is_empty());
+
+        Ok(())
+    }
 }
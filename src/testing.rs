@@ -0,0 +1,277 @@
+//! Helpers for testing signing integrations without real credentials.
+//!
+//! ```
+//! use reqsign::testing::assert_signed_eq;
+//! use reqsign::testing::fixed_time;
+//! use reqsign::testing::StaticLoader;
+//! use reqsign::AwsCredential;
+//! use reqsign::AwsV4Signer;
+//! use reqsign::CredentialLoad;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> reqsign::Result<()> {
+//! let loader = StaticLoader::new(AwsCredential {
+//!     access_key_id: "access_key_id".to_string(),
+//!     secret_access_key: "secret_access_key".to_string(),
+//!     ..Default::default()
+//! });
+//! let cred = loader.load().await?.expect("credential must be loaded");
+//! let signer = AwsV4Signer::new("s3", "us-east-1").with_time(fixed_time());
+//!
+//! let mut left = http::Request::get("https://s3.amazonaws.com/bucket").body(()).unwrap();
+//! signer.sign(&mut left, &cred)?;
+//! let mut right = http::Request::get("https://s3.amazonaws.com/bucket").body(()).unwrap();
+//! AwsV4Signer::new("s3", "us-east-1").sign(&mut right, &cred)?;
+//!
+//! assert_signed_eq(&left, &right);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use chrono::TimeZone;
+use chrono::Utc;
+use http::header::AUTHORIZATION;
+use http::Request;
+
+use crate::loader::CredentialLoad;
+use crate::request::SignableRequest;
+use crate::time::DateTime;
+use crate::Error;
+use crate::Result;
+
+/// Headers whose values depend on signing time.
+const TIME_DEPENDENT_HEADERS: &[&str] = &[
+    "date",
+    "x-amz-date",
+    "x-goog-date",
+    "x-ms-date",
+    "x-obs-date",
+    "x-oss-date",
+];
+
+/// Query parameters whose values depend on signing time.
+const TIME_DEPENDENT_QUERIES: &[&str] = &[
+    // AWS
+    "x-amz-credential",
+    "x-amz-date",
+    "x-amz-signature",
+    // Google
+    "x-goog-credential",
+    "x-goog-date",
+    "x-goog-signature",
+    // Aliyun and Huawei Cloud
+    "expires",
+    "signature",
+    // Azure SAS
+    "se",
+    "st",
+    "sig",
+    // Tencent
+    "q-key-time",
+    "q-sign-time",
+    "q-signature",
+];
+
+/// The fixed time `2013-05-24T00:00:00Z` that used in AWS examples.
+///
+/// Pass it to `with_time` of signers to generate reproducible signatures.
+pub fn fixed_time() -> DateTime {
+    Utc.with_ymd_and_hms(2013, 5, 24, 0, 0, 0)
+        .single()
+        .expect("fixed time must be valid")
+}
+
+/// StaticLoader always returns the credential provided by caller.
+///
+/// It can be used anywhere a [`CredentialLoad`] is expected, for example,
+/// in the front of a [`ChainLoader`][crate::ChainLoader].
+#[derive(Debug, Clone)]
+pub struct StaticLoader<C> {
+    cred: Option<C>,
+}
+
+impl<C> StaticLoader<C> {
+    /// Create a loader that returns the given credential.
+    pub fn new(cred: C) -> Self {
+        Self { cred: Some(cred) }
+    }
+
+    /// Create a loader that never returns a credential.
+    pub fn empty() -> Self {
+        Self { cred: None }
+    }
+}
+
+#[async_trait]
+impl<C: Clone + Send + Sync> CredentialLoad<C> for StaticLoader<C> {
+    async fn load(&self) -> Result<Option<C>> {
+        Ok(self.cred.clone())
+    }
+}
+
+/// NoopSigner accepts any credential and leaves requests untouched.
+///
+/// It's useful to test code paths that need a signer but don't care about
+/// the signature. Requests are still checked to be signable, like having a
+/// valid authority.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopSigner;
+
+impl NoopSigner {
+    /// Create a new noop signer.
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Sign request without changing anything.
+    pub fn sign<C>(&self, req: &mut impl SignableRequest, _: &C) -> Result<()> {
+        let ctx = req.build().map_err(Error::signing)?;
+        req.apply(ctx).map_err(Error::signing)
+    }
+}
+
+/// Assert two signed requests are equivalent modulo signing time.
+///
+/// Method, path, query and headers are compared, except that:
+///
+/// - Values of time dependent headers like `x-amz-date` are ignored.
+/// - Values of time dependent query parameters like `X-Amz-Signature` are
+///   ignored.
+/// - Value of `authorization` header is ignored since the signature in it
+///   depends on signing time.
+///
+/// Their presence is still compared. Sign both requests with
+/// [`fixed_time`] to compare signatures exactly.
+///
+/// # Panics
+///
+/// Panics with the difference if two requests are not equivalent.
+#[track_caller]
+pub fn assert_signed_eq<A, B>(left: &Request<A>, right: &Request<B>) {
+    assert_eq!(
+        normalize(left),
+        normalize(right),
+        "signed requests are not equivalent"
+    );
+}
+
+type Normalized = (
+    String,
+    String,
+    Vec<(String, String)>,
+    BTreeMap<String, Vec<String>>,
+);
+
+fn normalize<T>(req: &Request<T>) -> Normalized {
+    let mut query: Vec<(String, String)> = req
+        .uri()
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|v| !v.is_empty())
+        .map(|v| {
+            let (k, v) = v.split_once('=').unwrap_or((v, ""));
+            if TIME_DEPENDENT_QUERIES.contains(&k.to_lowercase().as_str()) {
+                (k.to_string(), "<ignored>".to_string())
+            } else {
+                (k.to_string(), v.to_string())
+            }
+        })
+        .collect();
+    query.sort();
+
+    let mut headers: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (k, v) in req.headers() {
+        let value = if k == AUTHORIZATION || TIME_DEPENDENT_HEADERS.contains(&k.as_str()) {
+            "<ignored>".to_string()
+        } else {
+            String::from_utf8_lossy(v.as_bytes()).to_string()
+        };
+        headers.entry(k.to_string()).or_default().push(value);
+    }
+
+    (
+        req.method().to_string(),
+        format!(
+            "{}{}",
+            req.uri()
+                .authority()
+                .map(|v| v.as_str())
+                .unwrap_or_default(),
+            req.uri().path()
+        ),
+        query,
+        headers,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_static_loader() -> Result<()> {
+        let loader = StaticLoader::new("cred".to_string());
+        assert_eq!(loader.load().await?, Some("cred".to_string()));
+
+        let loader = StaticLoader::<String>::empty();
+        assert_eq!(loader.load().await?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_noop_signer() -> Result<()> {
+        let mut req = http::Request::get("https://example.com/path?a=b")
+            .header("x-custom", "value")
+            .body(())
+            .expect("request must be valid");
+        NoopSigner::new().sign(&mut req, &())?;
+        assert_eq!(req.uri(), "https://example.com/path?a=b");
+        assert_eq!(req.headers().len(), 1);
+
+        let mut req = http::Request::get("/path")
+            .body(())
+            .expect("request must be valid");
+        assert!(NoopSigner::new().sign(&mut req, &()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_assert_signed_eq() {
+        let req = |date: &str, query: &str, extra: Option<&str>| {
+            let mut builder = http::Request::get(format!("https://example.com/path?{query}"))
+                .header("x-amz-date", date)
+                .header(AUTHORIZATION, format!("AWS4-HMAC-SHA256 {date}"));
+            if let Some(v) = extra {
+                builder = builder.header("x-custom", v);
+            }
+            builder.body(()).expect("request must be valid")
+        };
+
+        assert_signed_eq(
+            &req("20130524T000000Z", "a=b&X-Amz-Signature=1", None),
+            &req("20230524T000000Z", "X-Amz-Signature=2&a=b", None),
+        );
+
+        let result = std::panic::catch_unwind(|| {
+            assert_signed_eq(
+                &req("20130524T000000Z", "a=b", None),
+                &req("20130524T000000Z", "a=c", None),
+            )
+        });
+        assert!(result.is_err(), "query value must be compared");
+
+        let result = std::panic::catch_unwind(|| {
+            assert_signed_eq(
+                &req("20130524T000000Z", "a=b", None),
+                &req("20130524T000000Z", "a=b", Some("v")),
+            )
+        });
+        assert!(result.is_err(), "headers must be compared");
+    }
+}
@@ -1,20 +1,56 @@
 //! Time related utils.
 
+#[cfg(test)]
+use std::cell::Cell;
+
+use alloc::string::String;
+use alloc::string::ToString;
+
 use anyhow::Result;
 use chrono::format::Fixed;
 use chrono::format::Item;
 use chrono::format::Numeric;
 use chrono::format::Pad;
 use chrono::SecondsFormat;
+use chrono::Timelike;
 use chrono::Utc;
 
+/// DateTime in UTC that used across reqsign.
 pub type DateTime = chrono::DateTime<Utc>;
 
+#[cfg(test)]
+thread_local! {
+    static FAKE_NOW: Cell<Option<DateTime>> = const { Cell::new(None) };
+}
+
 /// Create datetime of now.
+#[cfg(feature = "std")]
 pub fn now() -> DateTime {
+    #[cfg(test)]
+    if let Some(t) = FAKE_NOW.with(|v| v.get()) {
+        return t;
+    }
+
     Utc::now()
 }
 
+/// Set a fake time that returned by [`now`] in current thread.
+///
+/// Pass `None` to restore the real clock.
+#[cfg(test)]
+pub fn set_fake_now(t: Option<DateTime>) {
+    FAKE_NOW.with(|v| v.set(t));
+}
+
+/// Drop the sub-second component of time before formatting.
+///
+/// All formats are in whole seconds, and leap seconds which are represented
+/// as nanoseconds larger than one second must not be formatted as `60`.
+fn truncate(t: DateTime) -> DateTime {
+    // Zero nanosecond is always valid.
+    t.with_nanosecond(0).unwrap_or(t)
+}
+
 /// DATE is a time format like `20220301`
 const DATE: &[Item<'static>] = &[
     Item::Numeric(Numeric::Year, Pad::Zero),
@@ -24,7 +60,7 @@ const DATE: &[Item<'static>] = &[
 
 /// Format time into date: `20220301`
 pub fn format_date(t: DateTime) -> String {
-    t.format_with_items(DATE.iter()).to_string()
+    truncate(t).format_with_items(DATE.iter()).to_string()
 }
 
 /// ISO8601 is a time format like `20220313T072004Z`.
@@ -41,7 +77,17 @@ const ISO8601: &[Item<'static>] = &[
 
 /// Format time into ISO8601: `20220313T072004Z`
 pub fn format_iso8601(t: DateTime) -> String {
-    t.format_with_items(ISO8601.iter()).to_string()
+    truncate(t).format_with_items(ISO8601.iter()).to_string()
+}
+
+/// Parse time from ISO8601: `20220313T072004Z`
+pub fn parse_iso8601(s: &str) -> Result<DateTime> {
+    let mut parsed = chrono::format::Parsed::new();
+    // Errors of chrono only implement `Error` with std.
+    chrono::format::parse(&mut parsed, s, ISO8601.iter()).map_err(anyhow::Error::msg)?;
+    parsed
+        .to_datetime_with_timezone(&Utc)
+        .map_err(anyhow::Error::msg)
 }
 
 /// HTTP_DATE is a time format like `Sun, 06 Nov 1994 08:49:37 GMT`.
@@ -71,12 +117,22 @@ const HTTP_DATE: &[Item<'static>] = &[
 /// - Timezone is fixed to GMT.
 /// - Day must be 2 digit.
 pub fn format_http_date(t: DateTime) -> String {
-    t.format_with_items(HTTP_DATE.iter()).to_string()
+    truncate(t).format_with_items(HTTP_DATE.iter()).to_string()
+}
+
+/// Parse time from http date: `Sun, 06 Nov 1994 08:49:37 GMT`
+pub fn parse_http_date(s: &str) -> Result<DateTime> {
+    let mut parsed = chrono::format::Parsed::new();
+    // Errors of chrono only implement `Error` with std.
+    chrono::format::parse(&mut parsed, s, HTTP_DATE.iter()).map_err(anyhow::Error::msg)?;
+    parsed
+        .to_datetime_with_timezone(&Utc)
+        .map_err(anyhow::Error::msg)
 }
 
 /// Format time into RFC3339: `2022-03-13T07:20:04Z`
 pub fn format_rfc3339(t: DateTime) -> String {
-    t.to_rfc3339_opts(SecondsFormat::Secs, true)
+    truncate(t).to_rfc3339_opts(SecondsFormat::Secs, true)
 }
 
 /// Parse time from RFC3339.
@@ -87,7 +143,29 @@ pub fn format_rfc3339(t: DateTime) -> String {
 /// - `2022-03-01T08:12:34+00:00`
 /// - `2022-03-01T08:12:34.00+00:00`
 pub fn parse_rfc3339(s: &str) -> Result<DateTime> {
-    Ok(chrono::DateTime::parse_from_rfc3339(s)?.with_timezone(&Utc))
+    Ok(chrono::DateTime::parse_from_rfc3339(s)
+        .map_err(anyhow::Error::msg)?
+        .with_timezone(&Utc))
+}
+
+/// Calculate the clock skew between server and local via the `Date`
+/// header in response.
+///
+/// The returned duration is `server_time - local_time` which could be
+/// negative. Returns `None` if the `Date` header is missing or invalid.
+///
+/// Pass it to signer's `with_time_offset` and the offset will be added to
+/// current time while signing, so that following requests will not fail
+/// for the local clock skew. The offset is ignored if a fixed time is set
+/// by signer's `with_time`.
+#[cfg(feature = "std")]
+pub fn skew_from_response<T>(resp: &http::Response<T>) -> Option<chrono::Duration> {
+    let date = resp.headers().get(http::header::DATE)?.to_str().ok()?;
+    let server_time = chrono::DateTime::parse_from_rfc2822(date)
+        .ok()?
+        .with_timezone(&Utc);
+
+    Some(server_time - now())
 }
 
 #[cfg(test)]
@@ -112,12 +190,67 @@ mod tests {
         assert_eq!("20220301T081234Z", format_iso8601(t))
     }
 
+    #[test]
+    fn test_parse_iso8601() {
+        let t = test_time();
+        assert_eq!(t, parse_iso8601("20220301T081234Z").expect("must be valid"));
+        assert!(parse_iso8601("2022-03-01T08:12:34Z").is_err());
+    }
+
+    #[test]
+    fn test_format_edge_cases() {
+        use chrono::FixedOffset;
+
+        // Sub-second component must be dropped instead of rounded.
+        let t = Utc
+            .with_ymd_and_hms(2022, 1, 1, 0, 0, 0)
+            .unwrap()
+            .with_nanosecond(500_000_000)
+            .unwrap();
+        assert_eq!("20220101T000000Z", format_iso8601(t));
+        assert_eq!("Sat, 01 Jan 2022 00:00:00 GMT", format_http_date(t));
+        assert_eq!("2022-01-01T00:00:00Z", format_rfc3339(t));
+        let t = t.with_nanosecond(999_999_999).unwrap();
+        assert_eq!("20220101T000000Z", format_iso8601(t));
+
+        // Leap second must not be formatted as `60`.
+        let t = Utc
+            .with_ymd_and_hms(2016, 12, 31, 23, 59, 59)
+            .unwrap()
+            .with_nanosecond(1_500_000_000)
+            .unwrap();
+        assert_eq!("20161231T235959Z", format_iso8601(t));
+        assert_eq!("Sat, 31 Dec 2016 23:59:59 GMT", format_http_date(t));
+        assert_eq!("2016-12-31T23:59:59Z", format_rfc3339(t));
+
+        // Non-UTC input must be normalized to UTC, including the date.
+        let t: DateTime = FixedOffset::east_opt(8 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2022, 3, 1, 7, 2, 3)
+            .unwrap()
+            .into();
+        assert_eq!("20220228", format_date(t));
+        assert_eq!("20220228T230203Z", format_iso8601(t));
+        assert_eq!("Mon, 28 Feb 2022 23:02:03 GMT", format_http_date(t));
+        assert_eq!("2022-02-28T23:02:03Z", format_rfc3339(t));
+    }
+
     #[test]
     fn test_format_http_date() {
         let t = test_time();
         assert_eq!("Tue, 01 Mar 2022 08:12:34 GMT", format_http_date(t))
     }
 
+    #[test]
+    fn test_parse_http_date() {
+        let t = test_time();
+        assert_eq!(
+            t,
+            parse_http_date("Tue, 01 Mar 2022 08:12:34 GMT").expect("must be valid")
+        );
+        assert!(parse_http_date("20220301T081234Z").is_err());
+    }
+
     #[test]
     fn test_format_rfc3339() {
         let t = test_time();
@@ -136,4 +269,38 @@ mod tests {
             assert_eq!(t, parse_rfc3339(v).expect("must be valid time"));
         }
     }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_skew_from_response() {
+        let t = test_time();
+        set_fake_now(Some(t));
+
+        let resp = http::Response::builder()
+            .header(http::header::DATE, "Tue, 01 Mar 2022 08:17:34 GMT")
+            .body(())
+            .unwrap();
+        assert_eq!(
+            skew_from_response(&resp),
+            Some(chrono::Duration::minutes(5))
+        );
+
+        let resp = http::Response::builder()
+            .header(http::header::DATE, "Tue, 01 Mar 2022 08:12:04 GMT")
+            .body(())
+            .unwrap();
+        assert_eq!(
+            skew_from_response(&resp),
+            Some(chrono::Duration::seconds(-30))
+        );
+
+        let resp = http::Response::builder()
+            .header(http::header::DATE, "invalid")
+            .body(())
+            .unwrap();
+        assert_eq!(skew_from_response(&resp), None);
+        assert_eq!(skew_from_response(&http::Response::new(())), None);
+
+        set_fake_now(None);
+    }
 }
@@ -0,0 +1,108 @@
+//! Tracing helpers shared by loaders and signers.
+//!
+//! All helpers are no-op if `tracing` feature is disabled. They only accept
+//! non-secret values so that keys, tokens and signatures will never be
+//! recorded.
+
+use crate::time::DateTime;
+
+/// Record whether current `reqsign::load` span is served from cache.
+#[inline]
+pub(crate) fn record_cache_hit(hit: bool) {
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record("cache_hit", hit);
+    #[cfg(not(feature = "tracing"))]
+    let _ = hit;
+}
+
+/// Emit an event that a new credential has been loaded from its source.
+#[inline]
+pub(crate) fn credential_refreshed(provider: &'static str, expires_at: Option<DateTime>) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        provider,
+        expires_at = ?expires_at.map(|v| v.to_rfc3339()),
+        "reqsign credential refreshed"
+    );
+    #[cfg(not(feature = "tracing"))]
+    let _ = (provider, expires_at);
+}
+
+#[cfg(all(test, feature = "tracing", feature = "services-huaweicloud"))]
+mod tests {
+    use std::io::Write;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use anyhow::Result;
+    use tracing_subscriber::fmt::format::FmtSpan;
+    use tracing_subscriber::fmt::MakeWriter;
+
+    use crate::HuaweicloudObsConfig;
+    use crate::HuaweicloudObsCredentialLoader;
+    use crate::HuaweicloudObsSigner;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Buffer {
+        type Writer = Buffer;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn test_tracing_without_secrets() -> Result<()> {
+        let buf = Buffer::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .with_span_events(FmtSpan::CLOSE)
+            .with_ansi(false)
+            .with_writer(buf.clone())
+            .finish();
+
+        let auth = tracing::subscriber::with_default(subscriber, || -> Result<String> {
+            let loader = HuaweicloudObsCredentialLoader::new(HuaweicloudObsConfig {
+                access_key_id: Some("access_key_id".to_string()),
+                secret_access_key: Some("secret_access_key".to_string()),
+                security_token: Some("security_token".to_string()),
+            });
+            let rt = tokio::runtime::Builder::new_current_thread().build()?;
+            let cred = rt
+                .block_on(loader.load())?
+                .expect("credential must be loaded");
+            rt.block_on(loader.load())?
+                .expect("credential must be loaded");
+
+            let mut req =
+                http::Request::get("https://bucket.obs.cn-north-4.myhuaweicloud.com/k").body(())?;
+            HuaweicloudObsSigner::new("bucket").sign(&mut req, &cred)?;
+            Ok(req.headers()["authorization"].to_str()?.to_string())
+        })?;
+
+        let output = String::from_utf8(buf.0.lock().unwrap().clone())?;
+        assert!(output.contains("reqsign credential refreshed"), "{output}");
+        assert!(output.contains("cache_hit=false"), "{output}");
+        assert!(output.contains("cache_hit=true"), "{output}");
+        assert!(output.contains("reqsign::sign"), "{output}");
+
+        let signature = auth.rsplit(':').next().unwrap();
+        for secret in ["secret_access_key", "security_token", signature] {
+            assert!(!output.contains(secret), "{secret} leaked: {output}");
+        }
+
+        Ok(())
+    }
+}
@@ -35,11 +35,49 @@ fn init_signer() -> Option<(AzureStorageLoader, AzureStorageSigner)> {
         ..Default::default()
     };
 
-    let loader = AzureStorageLoader::new(config);
+    let loader = AzureStorageLoader::new(Client::new(), config);
 
     Some((loader, AzureStorageSigner::new()))
 }
 
+#[tokio::test]
+async fn test_sign_hyper_request() -> Result<()> {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let loader = AzureStorageLoader::new(
+        Client::new(),
+        AzureStorageConfig {
+            account_name: Some("account".to_string()),
+            account_key: Some("YWNjb3VudF9rZXkK".to_string()),
+            ..Default::default()
+        },
+    );
+    let cred = loader.load().await?.unwrap();
+
+    let mut req = hyper::Request::builder()
+        .method(http::Method::GET)
+        .uri("https://account.blob.core.windows.net/testbucket/testblob")
+        .body(hyper::Body::from("Hello, World!"))?;
+
+    AzureStorageSigner::new()
+        .sign(&mut req, &cred)
+        .expect("sign request must success");
+
+    let auth = req
+        .headers()
+        .get(http::header::AUTHORIZATION)
+        .expect("authorization must be set")
+        .to_str()?;
+    assert!(auth.starts_with("SharedKey account:"));
+    assert!(req.headers().get("x-ms-date").is_some());
+
+    // Body should be left untouched.
+    let body = hyper::body::to_bytes(req.into_body()).await?;
+    assert_eq!(&body[..], b"Hello, World!");
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_head_blob() -> Result<()> {
     let signer = init_signer();
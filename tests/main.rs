@@ -1,5 +1,10 @@
+#[cfg(feature = "services-aliyun")]
 mod aliyun;
+#[cfg(feature = "services-aws")]
 mod aws;
+#[cfg(feature = "services-azblob")]
 mod azure;
+#[cfg(feature = "services-google")]
 mod google;
+#[cfg(feature = "services-tencent")]
 mod tencent;
@@ -36,7 +36,7 @@ fn init_signer() -> Option<(TencentCosCredentialLoader, TencentCosSigner)> {
         ),
         ..Default::default()
     };
-    let loader = TencentCosCredentialLoader::new(config);
+    let loader = TencentCosCredentialLoader::new(Client::new(), config);
 
     Some((loader, TencentCosSigner::new()))
 }